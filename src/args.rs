@@ -0,0 +1,704 @@
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+use crate::buffer::NewlineEof;
+use crate::cache;
+use crate::files;
+use crate::glob::Glob;
+use crate::grammar::{CheckMode, GrammarConfig, ProviderKind};
+use crate::lang_config;
+use crate::language::{init_supported_languages, Language, UnsupportedLanguageError};
+
+/// Output format of the CLI
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputFormat {
+    /// The corrected source code
+    #[default]
+    Text,
+    /// A JSON report of the changed comments
+    Json,
+    /// A JSON object per changed comment, one per line, printed as soon as each file is checked
+    Jsonl,
+}
+
+impl OutputFormat {
+    /// Convert a [`str`] to [`OutputFormat`]
+    pub fn from(string: &str) -> Result<OutputFormat, String> {
+        match string {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            _ => Err(format!("Invalid format: {}", string)),
+        }
+    }
+}
+
+/// When the diff of `--dry-run` is colored
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColorChoice {
+    /// Only when stdout is a terminal
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Convert a [`str`] to [`ColorChoice`]
+    pub fn from(string: &str) -> Result<ColorChoice, String> {
+        match string {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("Invalid color: {}", string)),
+        }
+    }
+
+    /// Whether the output is colored
+    ///
+    /// # Params
+    /// * `stdout_is_terminal`: Whether stdout is attached to a terminal
+    pub fn enabled(&self, stdout_is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => stdout_is_terminal,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// Parsed CLI arguments
+pub struct Args {
+    /// Language selected with `--lang` or `--file`, inferred per file when `None`
+    pub language: Option<Language>,
+    /// Files to process, stdin is read when empty
+    pub paths: Vec<PathBuf>,
+    /// Patterns of the paths skipped when walking the directories
+    pub exclude: Vec<Glob>,
+    /// Write the corrections back to the files
+    pub write: bool,
+    /// Configuration of the grammar check
+    pub config: GrammarConfig,
+    /// Print the diff of the corrections instead of the corrected text
+    pub dry_run: bool,
+    /// Exit with an error when corrections are needed, without printing the corrected text
+    pub check: bool,
+    /// Output format
+    pub format: OutputFormat,
+    /// Log the steps of the pipeline to stderr
+    pub verbose: bool,
+    /// Don't print the progress and the summary to stderr, only the errors
+    pub quiet: bool,
+    /// Print the estimated tokens and cost instead of calling the model
+    pub estimate: bool,
+    /// Print the counts of the comments instead of calling the model
+    pub stats: bool,
+    /// Number of files checked concurrently
+    pub jobs: usize,
+    /// Read stdin even if it is a terminal, e.g. to type the input by hand
+    pub stdin: bool,
+    /// When the diff of `--dry-run` is colored
+    pub color: ColorChoice,
+    /// Write the corrections and exit with an error if any file changed, e.g. in a pre-commit hook
+    pub fix: bool,
+    /// Ask for each correction before writing it
+    pub interactive: bool,
+}
+
+impl Args {
+    /// Whether the input must be read from stdin but nothing is piped to it
+    ///
+    /// # Params
+    /// * `stdin_is_terminal`: Whether stdin is attached to a terminal
+    pub fn missing_input(&self, stdin_is_terminal: bool) -> bool {
+        self.paths.is_empty() && !self.stdin && stdin_is_terminal
+    }
+}
+
+/// Error returned when the CLI arguments are invalid
+#[derive(Debug, PartialEq)]
+pub enum ArgsError {
+    /// Neither `--lang`, `--file` nor files to process were provided
+    MissingLanguage,
+    /// A flag was provided without its value
+    MissingValue {
+        flag: String,
+        example: &'static str,
+    },
+    /// The language of `--lang` is not supported
+    UnsupportedLanguage(UnsupportedLanguageError),
+    /// The language cannot be inferred from the extension of `--file`
+    UnknownExtension(PathBuf),
+    /// The value of a flag or an environment variable is invalid
+    InvalidValue(String),
+    /// A flag that is not supported, e.g. a typo
+    UnknownFlag(String),
+    /// `--write`, `--fix` or `--interactive` was provided without files to write
+    WriteWithoutFiles,
+    /// No files were provided and nothing is piped to stdin
+    MissingInput,
+    /// `--help` was provided, the [`usage`] must be printed instead of running the check
+    Help,
+    /// `--version` was provided, the [`version`] must be printed instead of running the check
+    Version,
+}
+
+impl std::fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgsError::MissingLanguage => write!(f, "The --lang attribute is required. (e.g. --lang python)"),
+            ArgsError::MissingValue { flag, example } => write!(f, "Value of {} not found (e.g. {})", flag, example),
+            ArgsError::UnsupportedLanguage(err) => write!(f, "Error: {}", err),
+            ArgsError::UnknownExtension(path) => {
+                write!(f, "Error: Cannot infer the language of {}", path.display())
+            }
+            ArgsError::InvalidValue(message) => write!(f, "Error: {}", message),
+            ArgsError::UnknownFlag(flag) => write!(f, "Error: Unknown flag {}, see --help", flag),
+            ArgsError::WriteWithoutFiles => write!(f, "Error: --write, --fix and --interactive require at least one file"),
+            ArgsError::MissingInput => write!(
+                f,
+                "No input, pipe the source code or pass the files to check (e.g. neospeller --lang python < main.py \
+                 or neospeller src/), use --stdin to type it in the terminal"
+            ),
+            ArgsError::Help => write!(f, "{}", usage()),
+            ArgsError::Version => write!(f, "{}", version()),
+        }
+    }
+}
+
+impl std::error::Error for ArgsError {}
+
+/// Name and version of the crate, e.g. `neospeller 0.1.0`
+pub fn version() -> String {
+    format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+/// Usage of the CLI, with every flag and the supported languages
+pub fn usage() -> String {
+    let languages: Vec<String> = init_supported_languages()
+        .languages
+        .into_iter()
+        .map(|l| l.name)
+        .collect();
+
+    format!(
+        "{}
+Check the spelling and grammar of the comments in source code
+
+Usage: neospeller [OPTIONS] [FILES]...
+       neospeller --lang <LANG> [OPTIONS] < file
+
+Files and directories are checked in place of stdin, the language is inferred from the extensions.
+
+Options:
+  --lang <LANG>            Language of the source code
+  --file <FILE>            Infer the language of stdin from the extension of a file name
+  --lang-config <FILE>     JSON file that defines custom languages, only JSON is accepted
+  --write                  Write the corrections back to the files
+  --fix                    Write the corrections and exit with code 1 if any file changed
+  --interactive            Accept, reject or edit each correction before writing it
+  --dry-run                Print a diff of the corrections instead of the corrected code
+  --color <WHEN>           Color the diff: auto, always or never
+  --check                  Exit with code 1 when some comment needs corrections
+  --format <FORMAT>        Output format: text, json or jsonl
+  --provider <PROVIDER>    Grammar backend: openai, anthropic, azure, ollama or offline
+  --model <MODEL>          Model of the provider
+  --temperature <TEMP>     Sampling temperature, between 0.0 and 2.0
+  --max-tokens <N>         Maximum tokens of the response
+  --timeout <SECS>         Time limit of each request to the provider, 30 by default
+  --retries <N>            Repeat the requests that fail temporarily, e.g. rate limits
+  --dump-dir <DIR>         Write each request and raw response to a directory, the API keys are redacted
+  --mode <MODE>            What to correct: spelling, grammar or both
+  --prompt-file <FILE>     Template that replaces the built-in prompt
+  --ignore-words <FILE>    Words that are never changed, one per line
+  --dict <FILE>            Word list of the offline provider, required by it
+  --api-key-file <FILE>    File containing the API key
+  --strict                 Fail on a mismatched response or an unterminated comment
+  --skip-code              Don't check the comments that look like code
+  --straight-quotes        Replace the smart quotes introduced by the model with straight quotes
+  --keep-case              Keep the case of the first letter and the acronyms of each comment
+  --min-words <N>          Don't check the comments with fewer words
+  --only-lines <START:END> Only check the comments of a range of lines
+  --newline-eof <MODE>     End of the output: preserve, ensure or strip the final newline
+  --exclude <PATTERN>      Skip the matching paths of the directories, e.g. vendor/ or *.min.js
+  --jobs <N>               Number of files checked at the same time
+  --no-cache               Always call the model, without the cache of corrections
+  --estimate               Print the estimated tokens and cost without calling the model
+  --stats                  Print the counts of the comments without calling the model
+  --stdin                  Read stdin even if it is a terminal
+  --verbose                Log the steps of the pipeline to stderr
+  --quiet                  Only print the errors to stderr, without warnings, progress or summary
+  --help                   Print this help
+  --version                Print the version
+
+Languages: {}",
+        version(),
+        languages.join(", ")
+    )
+}
+
+/// Handle the CLI args of the process, see [`parse_args`]
+///
+/// The patterns of the [`files::IGNORE_FILE`] of the working directory apply on top of the `--exclude` ones.
+///
+/// Fails instead of waiting forever when the input must be read from stdin
+/// but stdin is a terminal, unless `--stdin` is provided.
+pub fn handle_args() -> Result<Args, ArgsError> {
+    let mut args = parse_args(env::args())?;
+
+    let ignore_file = Path::new(files::IGNORE_FILE);
+    if ignore_file.is_file() {
+        args.exclude.extend(files::read_ignore_file(ignore_file).map_err(ArgsError::InvalidValue)?);
+    }
+
+    if args.missing_input(io::stdin().is_terminal()) {
+        return Err(ArgsError::MissingInput);
+    }
+
+    Ok(args)
+}
+
+/// Take the value of a flag from the arguments
+fn next_value(
+    args: &mut impl Iterator<Item = String>,
+    flag: &str,
+    example: &'static str,
+) -> Result<String, ArgsError> {
+    args.next().ok_or_else(|| ArgsError::MissingValue {
+        flag: flag.to_string(),
+        example,
+    })
+}
+
+/// Parse the CLI args, the first one is the program name
+///
+/// The language is taken from `--lang`, or inferred from the extension
+/// of `--file` when `--lang` is absent. Positional arguments are files to
+/// process, each one with the language inferred from its extension.
+///
+/// # Params
+/// * `args`: The arguments, e.g. `["neospeller", "--lang", "python"]`
+///
+/// # Returns
+/// * The parsed [`Args`] or an [`ArgsError`]
+pub fn parse_args<I: Iterator<Item = String>>(args: I) -> Result<Args, ArgsError> {
+    let mut args = args.skip(1); // Program name
+
+    let mut lang: Option<String> = None;
+    let mut lang_config: Option<PathBuf> = None;
+    let mut file: Option<PathBuf> = None;
+    let mut config = GrammarConfig::from_env().map_err(ArgsError::InvalidValue)?;
+    let mut dry_run = false;
+    let mut check = false;
+    let mut format = OutputFormat::default();
+    let mut paths: Vec<PathBuf> = Vec::new();
+    let mut exclude: Vec<Glob> = Vec::new();
+    let mut write = false;
+    let mut verbose = false;
+    let mut quiet = false;
+    let mut estimate = false;
+    let mut stats = false;
+    let mut no_cache = false;
+    let mut jobs = files::DEFAULT_JOBS;
+    let mut stdin = false;
+    let mut color = ColorChoice::default();
+    let mut fix = false;
+    let mut interactive = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" | "-h" => return Err(ArgsError::Help),
+            "--version" | "-V" => return Err(ArgsError::Version),
+            "--lang" => lang = Some(next_value(&mut args, &arg, "python")?),
+            "--file" => file = Some(PathBuf::from(next_value(&mut args, &arg, "main.py")?)),
+            "--lang-config" => lang_config = Some(PathBuf::from(next_value(&mut args, &arg, "languages.json")?)),
+            "--provider" => {
+                config.provider =
+                    ProviderKind::from(&next_value(&mut args, &arg, "openai")?).map_err(ArgsError::InvalidValue)?
+            }
+            "--model" => config.model = Some(next_value(&mut args, &arg, "gpt-4o")?),
+            "--temperature" => {
+                config.temperature = GrammarConfig::parse_temperature(&next_value(&mut args, &arg, "0.0")?)
+                    .map_err(ArgsError::InvalidValue)?
+            }
+            "--max-tokens" => {
+                config.max_tokens = GrammarConfig::parse_max_tokens(&next_value(&mut args, &arg, "4000")?)
+                    .map_err(ArgsError::InvalidValue)?
+            }
+            "--timeout" => {
+                config.timeout = GrammarConfig::parse_timeout(&next_value(&mut args, &arg, "60")?)
+                    .map_err(ArgsError::InvalidValue)?
+            }
+            "--retries" => {
+                config.retries = GrammarConfig::parse_retries(&next_value(&mut args, &arg, "3")?)
+                    .map_err(ArgsError::InvalidValue)?
+            }
+            "--dump-dir" => config.dump_dir = Some(PathBuf::from(next_value(&mut args, &arg, "dumps")?)),
+            "--dict" => config.dict = Some(PathBuf::from(next_value(&mut args, &arg, "words.txt")?)),
+            "--api-key-file" => {
+                config.api_key_file = Some(PathBuf::from(next_value(&mut args, &arg, "~/.openai_key")?))
+            }
+            "--mode" => {
+                config.mode =
+                    CheckMode::from(&next_value(&mut args, &arg, "spelling")?).map_err(ArgsError::InvalidValue)?
+            }
+            "--prompt-file" => {
+                config.prompt_file = Some(PathBuf::from(next_value(&mut args, &arg, "prompt.txt")?))
+            }
+            "--ignore-words" => {
+                config.ignore_words = Some(PathBuf::from(next_value(&mut args, &arg, "words.txt")?))
+            }
+            "--strict" => config.strict = true,
+            "--skip-code" => config.skip_code = true,
+            "--straight-quotes" => config.straight_quotes = true,
+            "--keep-case" => config.keep_case = true,
+            "--min-words" => {
+                config.min_words = GrammarConfig::parse_min_words(&next_value(&mut args, &arg, "3")?)
+                    .map_err(ArgsError::InvalidValue)?
+            }
+            "--only-lines" => {
+                config.only_lines = Some(
+                    GrammarConfig::parse_line_range(&next_value(&mut args, &arg, "10:25")?)
+                        .map_err(ArgsError::InvalidValue)?,
+                )
+            }
+            "--newline-eof" => {
+                config.newline_eof =
+                    NewlineEof::from(&next_value(&mut args, &arg, "ensure")?).map_err(ArgsError::InvalidValue)?
+            }
+            "--dry-run" => dry_run = true,
+            "--check" => check = true,
+            "--verbose" => verbose = true,
+            "--quiet" => quiet = true,
+            "--estimate" => estimate = true,
+            "--stats" => stats = true,
+            "--no-cache" => no_cache = true,
+            "--stdin" => stdin = true,
+            "--color" => {
+                color = ColorChoice::from(&next_value(&mut args, &arg, "always")?).map_err(ArgsError::InvalidValue)?
+            }
+            "--exclude" => {
+                exclude.push(Glob::new(&next_value(&mut args, &arg, "vendor/")?).map_err(ArgsError::InvalidValue)?)
+            }
+            "--jobs" => jobs = parse_jobs(&next_value(&mut args, &arg, "4")?).map_err(ArgsError::InvalidValue)?,
+            "--format" => {
+                format = OutputFormat::from(&next_value(&mut args, &arg, "json")?).map_err(ArgsError::InvalidValue)?
+            }
+            "--write" => write = true,
+            "--fix" => {
+                fix = true;
+                write = true;
+            }
+            "--interactive" => {
+                interactive = true;
+                write = true;
+            }
+            path if path == "-" || !path.starts_with('-') => paths.push(PathBuf::from(path)),
+            flag => return Err(ArgsError::UnknownFlag(flag.to_string())),
+        }
+    }
+
+    // The custom languages must be known before resolving `--lang` and `--file`
+    config.languages = lang_config::load_languages(lang_config.as_deref()).map_err(ArgsError::InvalidValue)?;
+
+    let language = if let Some(lang) = lang {
+        Some(lang_config::language_from_name(&lang, &config.languages).map_err(ArgsError::UnsupportedLanguage)?)
+    } else if let Some(file) = file {
+        Some(files::language_for_path(&file, None, &config.languages).map_err(|_| ArgsError::UnknownExtension(file))?)
+    } else {
+        None
+    };
+
+    if language.is_none() && paths.is_empty() {
+        return Err(ArgsError::MissingLanguage);
+    }
+
+    if write && paths.is_empty() {
+        return Err(ArgsError::WriteWithoutFiles);
+    }
+
+    if !no_cache {
+        config.cache_dir = cache::default_dir();
+    }
+
+    Ok(Args {
+        language,
+        paths,
+        exclude,
+        write,
+        config,
+        dry_run,
+        check,
+        format,
+        verbose,
+        quiet,
+        estimate,
+        stats,
+        jobs,
+        stdin,
+        color,
+        fix,
+        interactive,
+    })
+}
+
+/// Parse and validate the number of jobs, it must be greater than zero
+fn parse_jobs(value: &str) -> Result<usize, String> {
+    match value.trim().parse() {
+        Ok(jobs) if jobs > 0 => Ok(jobs),
+        _ => Err(format!("Invalid jobs: {}, it must be a positive integer", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Synthetic argv, with the program name
+    fn argv(args: &[&str]) -> impl Iterator<Item = String> {
+        std::iter::once("neospeller")
+            .chain(args.iter().copied())
+            .map(String::from)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse_args(argv(&["--lang", "python"])).unwrap();
+        assert_eq!(args.language.unwrap().name, "python");
+        assert!(args.paths.is_empty());
+        assert!(!args.dry_run);
+        assert!(!args.verbose);
+
+        let args = parse_args(argv(&["--file", "main.rs", "--dry-run", "--format", "json"])).unwrap();
+        assert_eq!(args.language.unwrap().name, "rust");
+        assert!(args.dry_run);
+        assert_eq!(args.format, OutputFormat::Json);
+
+        let args = parse_args(argv(&["--lang", "python", "--format", "jsonl"])).unwrap();
+        assert_eq!(args.format, OutputFormat::Jsonl);
+
+        // `--lang` wins over the extension of `--file`
+        let args = parse_args(argv(&["--file", "main.rs", "--lang", "go"])).unwrap();
+        assert_eq!(args.language.unwrap().name, "go");
+
+        let args = parse_args(argv(&["--write", "src/", "main.py"])).unwrap();
+        assert!(args.language.is_none());
+        assert!(args.write);
+        assert_eq!(args.paths, vec![PathBuf::from("src/"), PathBuf::from("main.py")]);
+
+        let args = parse_args(argv(&["--lang", "rust", "--model", "gpt-4o", "--strict"])).unwrap();
+        assert_eq!(args.config.model.as_deref(), Some("gpt-4o"));
+        assert!(args.config.strict);
+        assert!(!args.config.skip_code);
+        assert!(!args.config.straight_quotes);
+
+        let args = parse_args(argv(&["--lang", "python", "--skip-code", "--min-words", "3"])).unwrap();
+        assert!(args.config.skip_code);
+        assert_eq!(args.config.min_words, 3);
+
+        let args = parse_args(argv(&["--lang", "python", "--straight-quotes"])).unwrap();
+        assert!(args.config.straight_quotes);
+        assert!(!args.config.keep_case);
+
+        let args = parse_args(argv(&["--lang", "python", "--keep-case"])).unwrap();
+        assert!(args.config.keep_case);
+
+        let args = parse_args(argv(&["--lang", "python", "--only-lines", "10:25"])).unwrap();
+        assert_eq!(args.config.only_lines, Some(9..=24));
+        assert_eq!(args.config.newline_eof, NewlineEof::Preserve);
+
+        let args = parse_args(argv(&["--lang", "python", "--newline-eof", "strip"])).unwrap();
+        assert_eq!(args.config.newline_eof, NewlineEof::Strip);
+
+        let args = parse_args(argv(&["--lang", "python", "--timeout", "2.5", "--retries", "3"])).unwrap();
+        assert_eq!(args.config.timeout, std::time::Duration::from_millis(2500));
+        assert_eq!(args.config.retries, 3);
+        assert_eq!(args.config.dump_dir, None);
+
+        let args = parse_args(argv(&["--lang", "python", "--dump-dir", "dumps"])).unwrap();
+        assert_eq!(args.config.dump_dir, Some(PathBuf::from("dumps")));
+
+        let args = parse_args(argv(&["--lang", "rust", "--verbose", "--estimate"])).unwrap();
+        assert!(args.verbose);
+        assert!(!args.quiet);
+        assert!(args.estimate);
+        assert!(!args.stats);
+        assert!(args.config.cache_dir.is_some());
+
+        let args = parse_args(argv(&["--stats", "src/"])).unwrap();
+        assert!(args.stats);
+
+        let args = parse_args(argv(&["--lang", "rust", "--no-cache"])).unwrap();
+        assert!(args.config.cache_dir.is_none());
+        assert_eq!(args.jobs, files::DEFAULT_JOBS);
+
+        let args = parse_args(argv(&["--fix", "main.py", "lib.rs"])).unwrap();
+        assert!(args.fix);
+        assert!(args.write);
+        assert!(!args.interactive);
+
+        let args = parse_args(argv(&["--interactive", "main.py"])).unwrap();
+        assert!(args.interactive);
+        assert!(args.write);
+
+        let args = parse_args(argv(&["--jobs", "8", "src/"])).unwrap();
+        assert_eq!(args.jobs, 8);
+
+        let args = parse_args(argv(&["--exclude", "vendor/", "--exclude", "*.min.js", "src/"])).unwrap();
+        let exclude: Vec<&str> = args.exclude.iter().map(Glob::as_str).collect();
+        assert_eq!(exclude, vec!["vendor/", "*.min.js"]);
+        assert!(matches!(
+            parse_args(argv(&["--exclude", "[abc", "src/"])).err(),
+            Some(ArgsError::InvalidValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_args_lang_config() {
+        let path = env::temp_dir().join(format!("neospeller-lang-config-{}.json", std::process::id()));
+        let config = r##"{"languages": [{"name": "nim", "comment_symbols": ["#"], "block_symbols": [["#[", "]#"]]}]}"##;
+        std::fs::write(&path, config).unwrap();
+
+        let args = parse_args(argv(&["--lang-config", path.to_str().unwrap(), "--lang", "nim"])).unwrap();
+        assert_eq!(args.language.unwrap().ml_comment_symbols, vec![("#[".to_string(), "]#".to_string())]);
+        assert_eq!(args.config.languages.len(), 1);
+        assert!(Language::from_name("nim").is_err());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            parse_args(argv(&["--lang-config", path.to_str().unwrap(), "--lang", "python"])),
+            Err(ArgsError::InvalidValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_args_help_and_version() {
+        assert_eq!(parse_args(argv(&["--help"])).err(), Some(ArgsError::Help));
+        assert_eq!(parse_args(argv(&["--lang", "python", "-h"])).err(), Some(ArgsError::Help));
+        assert_eq!(parse_args(argv(&["--version"])).err(), Some(ArgsError::Version));
+        assert_eq!(parse_args(argv(&["-V", "src/"])).err(), Some(ArgsError::Version));
+
+        // A flag value is never taken as `--help` or `--version`
+        let args = parse_args(argv(&["--lang", "python", "--exclude", "-V"])).unwrap();
+        assert_eq!(args.exclude[0].as_str(), "-V");
+    }
+
+    #[test]
+    fn test_parse_args_unknown_flag() {
+        let err = parse_args(argv(&["--lang", "python", "--dry_run"])).err().unwrap();
+        assert!(matches!(&err, ArgsError::UnknownFlag(flag) if flag == "--dry_run"));
+        assert_eq!(err.to_string(), "Error: Unknown flag --dry_run, see --help");
+
+        for flag in ["-q", "-lang"] {
+            let err = parse_args(argv(&["src/", flag, "python"])).err().unwrap();
+            assert_eq!(err, ArgsError::UnknownFlag(flag.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_parse_args_unknown_language() {
+        let err = parse_args(argv(&["--lang", "cobol"])).err().unwrap();
+        match err {
+            ArgsError::UnsupportedLanguage(err) => assert_eq!(err.name, "cobol"),
+            err => panic!("Unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn test_color_choice() {
+        let args = parse_args(argv(&["--lang", "python", "--dry-run"])).unwrap();
+        assert_eq!(args.color, ColorChoice::Auto);
+        assert!(args.color.enabled(true));
+        assert!(!args.color.enabled(false));
+
+        let args = parse_args(argv(&["--lang", "python", "--dry-run", "--color", "never"])).unwrap();
+        assert!(!args.color.enabled(true));
+
+        let args = parse_args(argv(&["--lang", "python", "--dry-run", "--color", "always"])).unwrap();
+        assert!(args.color.enabled(false));
+
+        assert!(matches!(
+            parse_args(argv(&["--lang", "python", "--color", "sometimes"])).err(),
+            Some(ArgsError::InvalidValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_version_and_usage() {
+        assert_eq!(version(), format!("neospeller {}", env!("CARGO_PKG_VERSION")));
+
+        let usage = usage();
+        assert!(usage.starts_with(&version()));
+        for language in init_supported_languages().languages {
+            assert!(usage.contains(&language.name), "{} is not listed", language.name);
+        }
+        assert!(usage.contains("--lang <LANG>"));
+        assert!(usage.contains("--version"));
+    }
+
+    #[test]
+    fn test_missing_input() {
+        let args = parse_args(argv(&["--lang", "python"])).unwrap();
+        assert!(args.missing_input(true));
+        assert!(!args.missing_input(false));
+
+        let args = parse_args(argv(&["--lang", "python", "--stdin"])).unwrap();
+        assert!(!args.missing_input(true));
+
+        // Files are read instead of stdin
+        let args = parse_args(argv(&["main.py"])).unwrap();
+        assert!(!args.missing_input(true));
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert_eq!(parse_args(argv(&[])).err(), Some(ArgsError::MissingLanguage));
+        assert_eq!(parse_args(argv(&["--dry-run"])).err(), Some(ArgsError::MissingLanguage));
+        assert_eq!(
+            parse_args(argv(&["--lang"])).err(),
+            Some(ArgsError::MissingValue {
+                flag: "--lang".to_string(),
+                example: "python"
+            })
+        );
+        assert_eq!(
+            parse_args(argv(&["--file", "Makefile"])).err(),
+            Some(ArgsError::UnknownExtension(PathBuf::from("Makefile")))
+        );
+        assert!(matches!(
+            parse_args(argv(&["--lang", "python", "--temperature", "9"])).err(),
+            Some(ArgsError::InvalidValue(_))
+        ));
+        assert!(matches!(
+            parse_args(argv(&["--lang", "python", "--min-words", "-1"])).err(),
+            Some(ArgsError::InvalidValue(_))
+        ));
+        assert!(matches!(
+            parse_args(argv(&["--lang", "python", "--only-lines", "25:10"])).err(),
+            Some(ArgsError::InvalidValue(_))
+        ));
+        assert!(matches!(
+            parse_args(argv(&["--lang", "python", "--timeout", "0"])).err(),
+            Some(ArgsError::InvalidValue(_))
+        ));
+        assert!(matches!(
+            parse_args(argv(&["--jobs", "0", "src/"])).err(),
+            Some(ArgsError::InvalidValue(_))
+        ));
+        assert_eq!(
+            parse_args(argv(&["--lang", "python", "--write"])).err(),
+            Some(ArgsError::WriteWithoutFiles)
+        );
+        assert_eq!(
+            parse_args(argv(&["--lang", "python", "--fix"])).err(),
+            Some(ArgsError::WriteWithoutFiles)
+        );
+        assert_eq!(
+            parse_args(argv(&["--lang", "python", "--interactive"])).err(),
+            Some(ArgsError::WriteWithoutFiles)
+        );
+    }
+}
@@ -22,7 +22,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     eprintln!("  Local ~/.neospeller: {}", local.len());
     logs.extend(local);
 
-    logs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    logs.sort_by_key(|l| std::cmp::Reverse(l.created_at));
 
     eprintln!("Writing logs.html ({} entries)...", logs.len());
     let html = render(&logs);
@@ -1,12 +1,45 @@
+use std::collections::HashMap;
 use std::fmt::Display;
+use std::io::{self, BufRead};
+
+use crate::error::NeospellerError;
+use crate::language::{leading_whitespace, Comment, CommentCollection, CommentType, Language};
+
+/// How the end of the file is written
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NewlineEof {
+    /// Keep the end of the original text
+    #[default]
+    Preserve,
+    /// End with exactly one line terminator
+    Ensure,
+    /// End without line terminators
+    Strip,
+}
 
-use crate::language::{Comment, CommentCollection, CommentType, Language};
+impl NewlineEof {
+    /// Convert a [`str`] to [`NewlineEof`]
+    pub fn from(string: &str) -> Result<NewlineEof, String> {
+        match string.trim().to_lowercase().as_str() {
+            "preserve" => Ok(NewlineEof::Preserve),
+            "ensure" => Ok(NewlineEof::Ensure),
+            "strip" => Ok(NewlineEof::Strip),
+            _ => Err(format!("Invalid newline-eof: {}", string)),
+        }
+    }
+}
 
 /// Text Buffer
 pub struct Buffer {
     pub lines: Vec<String>,
     pub comments: Vec<Comment>,
     pub language: Language,
+    /// Whether the source uses CRLF line endings
+    pub crlf: bool,
+    /// Whether the source ends with a line terminator
+    pub trailing_newline: bool,
+    /// Lines where a multi-line comment opens and is never closed
+    pub unterminated_comments: Vec<usize>,
 }
 
 impl Buffer {
@@ -16,6 +49,9 @@ impl Buffer {
             lines: Vec::new(),
             comments: Vec::new(),
             language,
+            crlf: false,
+            trailing_newline: false,
+            unterminated_comments: Vec::new(),
         }
     }
 
@@ -26,6 +62,74 @@ impl Buffer {
             lines,
             comments: Vec::new(),
             language,
+            crlf: uses_crlf(&s),
+            trailing_newline: s.ends_with('\n'),
+            unterminated_comments: Vec::new(),
+        }
+    }
+
+    /// Create a new buffer from a reader, line by line, without holding the whole input in memory
+    ///
+    /// # Params
+    /// * `reader`: Source of the text, e.g. a [`std::io::BufReader`] over a file or stdin
+    /// * `language`: The programming language of the text
+    ///
+    /// # Returns
+    /// * The same [`Buffer`] as [`Buffer::from_string`] or an error if the reader fails,
+    ///   e.g. invalid UTF-8
+    pub fn from_reader<R: BufRead>(mut reader: R, language: Language) -> io::Result<Self> {
+        let mut lines = Vec::new();
+        let mut crlf = 0;
+        let mut lf = 0;
+        let mut trailing_newline = false;
+        let mut line = String::new();
+
+        while reader.read_line(&mut line)? > 0 {
+            trailing_newline = line.ends_with('\n');
+            if trailing_newline {
+                line.pop();
+                if line.ends_with('\r') {
+                    line.pop();
+                    crlf += 1;
+                } else {
+                    lf += 1;
+                }
+            }
+            lines.push(std::mem::take(&mut line));
+        }
+
+        // Same rule as `uses_crlf`, the line breaks are counted as they are read
+        Ok(Self {
+            lines,
+            comments: Vec::new(),
+            language,
+            crlf: crlf > lf,
+            trailing_newline,
+            unterminated_comments: Vec::new(),
+        })
+    }
+
+    /// Normalize the line terminators at the end of the text
+    ///
+    /// The blank lines at the end are removed by [`NewlineEof::Ensure`] and
+    /// [`NewlineEof::Strip`], an empty text is kept empty.
+    pub fn normalize_eof(&mut self, newline_eof: NewlineEof) {
+        if newline_eof == NewlineEof::Preserve {
+            return;
+        }
+
+        while self.lines.last().is_some_and(|line| line.is_empty()) {
+            self.lines.pop();
+        }
+        self.trailing_newline = newline_eof == NewlineEof::Ensure && !self.lines.is_empty();
+    }
+
+    /// Line terminator used by the source
+    pub fn line_ending(&self) -> &'static str {
+        if self.crlf {
+            "\r\n"
+        } else {
+            "\n"
         }
     }
 
@@ -34,32 +138,79 @@ impl Buffer {
         self.lines.push(line);
     }
 
+    /// Treat every non-empty line of the buffer as a single line comment
+    ///
+    /// The fenced code blocks are skipped, fences included, when the language has Markdown code,
+    /// and so are the indented code blocks outside the lists.
     pub fn plain_text_to_comments(&mut self) {
+        let mut comments = Vec::new();
+        let mut open_fence: Option<(char, usize)> = None;
+        // An indented block is code after a blank line, unless it continues a list item
+        let mut previous_blank = true;
+        let mut in_code_block = false;
+        let mut in_list = false;
+
         for (i, text) in self.lines.iter().enumerate() {
-            self.comments.push(Comment {
-                line: i,
-                text: text.clone(),
-                comment_type: CommentType::Multi,
-            });
+            if self.language.markdown_code && open_fence.is_none() {
+                let blank = text.trim().is_empty();
+                let indented = text.starts_with('\t') || text.starts_with("    ");
+
+                in_code_block = indented && !in_list && (previous_blank || in_code_block);
+                if !blank && !indented {
+                    in_list = is_list_item(text);
+                } else if is_list_item(text) {
+                    in_list = true;
+                }
+                previous_blank = blank;
+
+                if in_code_block {
+                    continue;
+                }
+            }
+
+            if self.language.markdown_code {
+                let fence = code_fence(text);
+                match (open_fence, fence) {
+                    (None, Some((symbol, len, _))) => open_fence = Some((symbol, len)),
+                    // The closing fence is at least as long as the opening one, without info string
+                    (Some((symbol, len)), Some((close, close_len, info)))
+                        if close == symbol && close_len >= len && info.is_empty() =>
+                    {
+                        open_fence = None
+                    }
+                    _ => {}
+                }
+
+                if open_fence.is_some() || fence.is_some() {
+                    continue;
+                }
+            }
+
+            if !text.trim().is_empty() {
+                comments.push(Comment::new(i, text.clone(), CommentType::Single));
+            }
         }
+
+        self.comments = comments;
     }
 
     /// Retrieve comments from the text in the buffer
     pub fn get_comments(&mut self) -> &Vec<Comment> {
-        // If the language is plain text, process directly
-        if self.language.name == "text" {
+        // If the whole text is a comment, e.g. plain text, process directly
+        if self.language.whole_line_is_comment {
             self.plain_text_to_comments();
             return &self.comments;
         }
 
         let mut comments = Vec::new();
+        let mut unterminated_comments = Vec::new();
         let mut i = 0;
 
         while i < self.lines.len() {
             let line = &self.lines[i];
 
-            // Skip empty lines
-            if line.trim().is_empty() {
+            // Skip empty lines and the shebang, it is an interpreter path and not a comment
+            if line.trim().is_empty() || (i == 0 && self.language.is_shebang(line)) {
                 i += 1;
                 continue;
             }
@@ -70,6 +221,9 @@ impl Buffer {
             if let Ok(parse_state) = Comment::parse_comment(&self.language, &self.lines[i..], i, comment_type)
             {
                 if parse_state.lines_parsed > 0 {
+                    if parse_state.unterminated {
+                        unterminated_comments.push(i);
+                    }
                     comments.extend(parse_state.comments);
                     i += parse_state.lines_parsed;
                     continue;
@@ -79,9 +233,53 @@ impl Buffer {
         }
 
         self.comments = comments;
+        self.unterminated_comments = unterminated_comments;
         &self.comments
     }
 
+    /// Iterate over the parsed comments, in the order of the text
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use neospeller::buffer::Buffer;
+    /// use neospeller::language::Language;
+    ///
+    /// let language = Language::from_name("python").unwrap();
+    /// let mut buffer = Buffer::from_string("# First\nx = 5  # Second\n\"\"\"Docstring\"\"\"\n".to_string(), language);
+    /// buffer.get_comments();
+    ///
+    /// let texts: Vec<&str> = buffer.comments_iter().map(|c| c.text.as_str()).collect();
+    /// assert_eq!(texts, vec!["First", "Second", "Docstring"]);
+    ///
+    /// assert_eq!(buffer.comment_count(), 3);
+    /// assert_eq!(buffer.single_comments().count(), 2);
+    /// assert_eq!(buffer.multi_comments().next().unwrap().line, 2);
+    /// ```
+    pub fn comments_iter(&self) -> impl Iterator<Item = &Comment> {
+        self.comments.iter()
+    }
+
+    /// Iterate over the single line comments
+    pub fn single_comments(&self) -> impl Iterator<Item = &Comment> {
+        self.comments_of(CommentType::Single)
+    }
+
+    /// Iterate over the lines of the multi-line comments
+    pub fn multi_comments(&self) -> impl Iterator<Item = &Comment> {
+        self.comments_of(CommentType::Multi)
+    }
+
+    /// Number of parsed comments, each line of a multi-line comment is counted
+    pub fn comment_count(&self) -> usize {
+        self.comments.len()
+    }
+
+    /// Iterate over the comments of a type
+    fn comments_of(&self, comment_type: CommentType) -> impl Iterator<Item = &Comment> {
+        self.comments.iter().filter(move |c| c.comment_type == comment_type)
+    }
+
     /// Replace comments in text, overwrite old comments with fixed comments
     ///
     /// # Params
@@ -89,20 +287,41 @@ impl Buffer {
     ///
     /// # Returns
     /// * Error it the comment cannot be replaced
-    pub fn replace_comments(&mut self, new_comments: &[Comment]) -> Result<(), &'static str> {
-        for (i, comment) in new_comments.iter().enumerate() {
-            let line = self.lines.get_mut(comment.line).ok_or("Line not found")?;
+    pub fn replace_comments(&mut self, new_comments: &[Comment]) -> Result<(), NeospellerError> {
+        // Match the new comments with the original ones by position in the text,
+        // the new comments can be a subset of the original ones or be reordered
+        let original: HashMap<(usize, CommentType), &Comment> = self
+            .comments
+            .iter()
+            .map(|c| ((c.line, c.comment_type), c))
+            .collect();
+
+        let mut replaced = 0;
+        for comment in new_comments.iter() {
+            let Some(old) = original.get(&(comment.line, comment.comment_type)) else {
+                // There is no original comment in that position, nothing to replace
+                continue;
+            };
+
+            if old.text == comment.text {
+                continue;
+            }
+
+            let line = self
+                .lines
+                .get_mut(comment.line)
+                .ok_or_else(|| NeospellerError::CommentReplace(format!("Line {} not found", comment.line + 1)))?;
 
             let new_line = match comment.comment_type {
-                CommentType::Single => replace_single_comment(line, &self.comments[i].text, &comment.text),
-                CommentType::Multi => {
-                    replace_multi_comment(line, &self.comments[i].text, &comment.text, &self.language)
-                }
+                CommentType::Single => replace_single_comment(line, old, &comment.text),
+                CommentType::Multi => replace_multi_comment(line, old, &comment.text, &self.language),
             };
 
-            *line = new_line?;
+            *line = new_line.map_err(|err| NeospellerError::CommentReplace(err.to_string()))?;
+            replaced += 1;
         }
 
+        log::debug!("{} comment(s) replaced", replaced);
         Ok(())
     }
 
@@ -110,10 +329,20 @@ impl Buffer {
     ///
     /// # Params
     /// * `json_string`: Json to convert
-    pub fn json_to_comments(&mut self, json_string: &str) -> Result<&Vec<Comment>, &'static str> {
-        let comments: CommentCollection =
-            serde_json::from_str(json_string).map_err(|_| "Error parsing json string")?;
+    pub fn json_to_comments(&mut self, json_string: &str) -> Result<&Vec<Comment>, NeospellerError> {
+        let comments: CommentCollection = serde_json::from_str(extract_json(json_string))?;
+
+        self.collection_to_comments(&comments)
+    }
 
+    /// Replace the buffer's comments with a [`CommentCollection`] and order by line number
+    ///
+    /// # Params
+    /// * `comments`: Collection with the new comments
+    pub fn collection_to_comments(
+        &mut self,
+        comments: &CommentCollection,
+    ) -> Result<&Vec<Comment>, NeospellerError> {
         let comments = sort_comments_by_line_number(comments.to_comments());
         self.replace_comments(&comments)?;
         self.comments = comments;
@@ -125,10 +354,26 @@ impl Buffer {
 impl Display for Buffer {
     /// Convert [`Buffer`] to a string
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.lines.join("\n"))
+        write!(f, "{}", self.lines.join(self.line_ending()))?;
+
+        if self.trailing_newline {
+            write!(f, "{}", self.line_ending())?;
+        }
+
+        Ok(())
     }
 }
 
+/// Detect the dominant line ending of a text
+///
+/// # Returns
+/// * `true` if most of the lines end with CRLF
+fn uses_crlf(s: &str) -> bool {
+    let crlf = s.matches("\r\n").count();
+    let lf = s.matches('\n').count() - crlf;
+    crlf > lf
+}
+
 /// Orders comments by line number using an efficient sort
 ///
 /// # Arguments
@@ -145,70 +390,118 @@ pub fn sort_comments_by_line_number(mut comments: Vec<Comment>) -> Vec<Comment>
     comments
 }
 
+/// Extract the JSON object from the model content, ignoring Markdown code
+/// fences and surrounding prose, e.g. "Here you go: ```json {...} ```"
+///
+/// # Returns
+/// * The text between the first `{` and the last `}`, or the whole content if there is none
+pub fn extract_json(content: &str) -> &str {
+    match (content.find('{'), content.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &content[start..=end],
+        _ => content,
+    }
+}
+
 /// Replace a single line comment
 ///
+/// The text is replaced at the column where it was parsed, so the same text
+/// appearing before it as code is never replaced. The code and the spacing
+/// up to the comment text are kept as they are.
+///
 /// # Params
 /// * `line`: Line where comment is located
-/// * `old_comment`: Old comment text
+/// * `old_comment`: The parsed [`Comment`] to replace
 /// * `new_comment`: New comment text
 ///
 /// # Returns
 /// * The new line text or an Error if it cannot be replaced
 fn replace_single_comment(
-    line: &mut str,
-    old_comment: &str,
+    line: &str,
+    old_comment: &Comment,
     new_comment: &str,
 ) -> Result<String, &'static str> {
-    let mut result = String::new();
+    let (code, _) = comment_span(line, old_comment)?;
 
-    if let Some(sym_index) = line.find(old_comment) {
-        if sym_index > 0 {
-            result.push_str(&line[..sym_index]);
-        }
-
-        result.push_str(new_comment);
-        return Ok(result);
-    }
-
-    Err("No comment found")
+    // The spacing after the symbol is already kept, don't duplicate it if the model added it,
+    // the indentation of a whole line comment is restored, e.g. a nested Markdown list item
+    Ok(format!("{}{}{}", code, leading_whitespace(&old_comment.text), new_comment.trim_start()))
 }
 
 /// Replace a multi line comment
 ///
 /// # Params
 /// * `line`: Line where comment is located
-/// * `old_comment`: Old comment text
+/// * `old_comment`: The parsed [`Comment`] to replace
 /// * `new_comment`: New comment text
+/// * `language`: [`Language`] of the text
 ///
 /// # Returns
 /// * The new line text or an Error if it cannot be replaced
 fn replace_multi_comment(
-    line: &mut str,
-    old_comment: &str,
+    line: &str,
+    old_comment: &Comment,
     new_comment: &str,
     language: &Language,
 ) -> Result<String, &'static str> {
-    let mut result = String::new();
+    let (prefix, rest) = comment_span(line, old_comment)?;
+    let mut result = prefix.to_string();
 
-    if let Some(sym_index) = line.find(old_comment) {
-        if sym_index > 0 {
-            result.push_str(&line[..sym_index]);
-        }
+    // The margin asterisk of the line is kept, don't duplicate it if the model added it
+    let mut new_comment = new_comment.trim_start();
+    if prefix.trim() == "*" {
+        new_comment = new_comment.strip_prefix("* ").unwrap_or(new_comment);
+    }
 
-        result.push_str(new_comment);
+    // Restore the indentation of the comment body, the model can drop it
+    result.push_str(leading_whitespace(&old_comment.text));
+    result.push_str(new_comment);
 
-        if line[sym_index..].contains(&language.ml_comment_symbol_close) {
-            if sym_index > 0 && line.as_bytes()[sym_index - 1] == b' ' {
-                result.push(' ');
-            }
+    // Keep everything after the comment when it is closed in the same line: the spacing,
+    // the close symbol and the code that follows it, e.g. `int y;` in `int x = 5; /* init */ int y;`
+    if language.ml_comment_symbols.iter().any(|(_, close)| rest.contains(close.as_str())) {
+        result.push_str(rest);
+    }
 
-            result.push_str(&language.ml_comment_symbol_close);
-        }
+    Ok(result)
+}
 
-        return Ok(result);
-    }
+/// Whether a line is a Markdown list item, e.g. `- item`, `* item` or `1. item`
+fn is_list_item(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    let marker = trimmed.trim_start_matches(|c: char| c.is_ascii_digit());
+    let marker = if marker.len() < trimmed.len() {
+        marker.strip_prefix(['.', ')'])
+    } else {
+        marker.strip_prefix(['-', '*', '+'])
+    };
+
+    marker.is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t']))
+}
+
+/// Parse a Markdown code fence, e.g. ```` ```rust ```` or `~~~`
+///
+/// # Returns
+/// * The fence symbol, its length and the info string after it, or `None` if the line is not a fence
+fn code_fence(line: &str) -> Option<(char, usize, &str)> {
+    let trimmed = line.trim_start();
+    let symbol = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.len() - trimmed.trim_start_matches(symbol).len();
+
+    (len >= 3).then(|| (symbol, len, trimmed[len..].trim()))
+}
 
-    Err("No comment found")
+/// Split a line around the text of a parsed comment
+///
+/// # Returns
+/// * The text before and after the comment, or an error if the line doesn't
+///   have the comment text at its column
+fn comment_span<'a>(line: &'a str, comment: &Comment) -> Result<(&'a str, &'a str), &'static str> {
+    let end = comment.col + comment.text.len();
+
+    match line.get(comment.col..end) {
+        Some(text) if text == comment.text => Ok((&line[..comment.col], &line[end..])),
+        _ => Err("No comment found"),
+    }
 }
 
 #[cfg(test)]
@@ -216,7 +509,49 @@ mod tests {
     use crate::language::init_supported_languages;
 
     use super::*;
-    use std::collections::HashMap;
+
+    /// Check the comments found in a fixture, then that correcting them only changes
+    /// the corrected words, the comment markers and the margins are kept in the output
+    ///
+    /// # Params
+    /// * `fixture`: Source code with misspelled comments
+    /// * `language`: Language of the fixture
+    /// * `expected`: Line, text and type of each comment of the fixture
+    /// * `fixes`: Misspelled words and their corrections, unique in the fixture
+    fn assert_round_trip(
+        fixture: &str,
+        language: Language,
+        expected: &[(usize, &str, CommentType)],
+        fixes: &[(&str, &str)],
+    ) {
+        let fix = |text: &str| fixes.iter().fold(text.to_string(), |text, (from, to)| text.replace(from, to));
+
+        let mut buffer = Buffer::from_string(fixture.to_string(), language);
+        let comments = buffer.get_comments().clone();
+        let texts: Vec<(usize, &str, CommentType)> =
+            comments.iter().map(|c| (c.line, c.text.as_str(), c.comment_type)).collect();
+        assert_eq!(texts, expected);
+
+        let mut new_comments = comments.clone();
+        for comment in new_comments.iter_mut() {
+            comment.text = fix(&comment.text);
+        }
+        buffer.replace_comments(&new_comments).unwrap();
+        assert_eq!(buffer.to_string(), fix(fixture));
+    }
+
+    #[test]
+    fn test_extract_json() {
+        let json = r#"{"single_comments":{"1":"A comment"},"multiline_comments":{}}"#;
+
+        assert_eq!(extract_json(json), json);
+        assert_eq!(extract_json(&format!("```json\n{}\n```", json)), json);
+        assert_eq!(
+            extract_json(&format!("Here are the corrected comments:\n```\n{}\n```\nDone.", json)),
+            json
+        );
+        assert_eq!(extract_json("no json"), "no json");
+    }
 
     const RUST_FIXTURE: &str = r#"// this is a single line comment
 let x = 5;
@@ -281,12 +616,7 @@ CONSTANT = 5
 
     #[test]
     fn test_get_comments_rust() {
-        let language = Language {
-            name: "rust".to_string(),
-            comment_symbol: "//".to_string(),
-            ml_comment_symbol: "/*".to_string(),
-            ml_comment_symbol_close: "*/".to_string(),
-        };
+        let language = Language::new("rust", "//", "/*", "*/");
 
         let mut buffer = Buffer::from_string(RUST_FIXTURE.to_string(), language);
         let comments = buffer.get_comments();
@@ -324,142 +654,1175 @@ CONSTANT = 5
         assert_eq!(comments[6].line, 15);
         assert_eq!(comments[6].comment_type, CommentType::Multi);
 
-        assert_eq!(comments[7].text, "/ * Documentation code");
+        assert_eq!(comments[7].text, "* Documentation code");
         assert_eq!(comments[7].line, 21);
         assert_eq!(comments[7].comment_type, CommentType::Single);
     }
 
+    const GO_FIXTURE: &str = r#"// Package main is a demo
+package main
+
+import "fmt"
+
+/*
+this is a
+multi-line comment
+*/
+
+func main() {
+    fmt.Println("http://x")
+    fmt.Println("http://x") // print the url
+    /* Another multi-line comment, but in a single line */
+}
+"#;
+
     #[test]
-    fn test_get_comments_python() {
-        let language = Language {
-            name: "python".to_string(),
-            comment_symbol: "#".to_string(),
-            ml_comment_symbol: "\"\"\"".to_string(),
-            ml_comment_symbol_close: "\"\"\"".to_string(),
-        };
+    fn test_get_comments_go() {
+        let language = init_supported_languages()
+            .languages
+            .into_iter()
+            .find(|l| l.name == "go")
+            .unwrap();
 
-        let mut buffer = Buffer::from_string(PYTHON_FIXTURE.to_string(), language);
+        let mut buffer = Buffer::from_string(GO_FIXTURE.to_string(), language);
         let comments = buffer.get_comments();
 
-        assert_eq!(comments.len(), 13);
+        assert_eq!(comments.len(), 5);
 
-        assert_eq!(comments[0].line, 1);
-        assert_eq!(comments[0].text, "this is a");
-        assert_eq!(comments[0].comment_type, CommentType::Multi);
+        assert_eq!(comments[0].line, 0);
+        assert_eq!(comments[0].text, "Package main is a demo");
+        assert_eq!(comments[0].comment_type, CommentType::Single);
 
-        assert_eq!(comments[1].text, "multi-line comment");
+        assert_eq!(comments[1].line, 6);
+        assert_eq!(comments[1].text, "this is a");
         assert_eq!(comments[1].comment_type, CommentType::Multi);
-        assert_eq!(comments[1].line, 2);
 
-        assert_eq!(comments[2].line, 5);
-        assert_eq!(comments[2].text, "this is a single line comment");
-        assert_eq!(comments[2].comment_type, CommentType::Single);
+        assert_eq!(comments[2].line, 7);
+        assert_eq!(comments[2].text, "multi-line comment");
 
+        assert_eq!(comments[3].line, 12);
+        assert_eq!(comments[3].text, "print the url");
+        assert_eq!(comments[3].comment_type, CommentType::Single);
+
+        assert_eq!(comments[4].line, 13);
         assert_eq!(
-            comments[3].text,
+            comments[4].text,
             "Another multi-line comment, but in a single line"
         );
-        assert_eq!(comments[3].line, 9);
-        assert_eq!(comments[3].comment_type, CommentType::Multi);
-
-        assert_eq!(comments[4].text, "Unestruturated multi-line comment");
-        assert_eq!(comments[4].line, 11);
         assert_eq!(comments[4].comment_type, CommentType::Multi);
+    }
 
-        assert_eq!(comments[5].text, "Another unestruturated multi-line comment");
-        assert_eq!(comments[5].line, 15);
+    const HASKELL_FIXTURE: &str = r#"-- | Main module
+module Main where
+
+{- outer
+   {- inner -}
+   still outer
+-}
+main :: IO ()
+main = putStrLn "hello" -- print a greeting
+{- outer {- inner -} still outer -}
+"#;
+
+    #[test]
+    fn test_get_comments_haskell() {
+        let language = Language::from_name("haskell").unwrap();
+
+        let mut buffer = Buffer::from_string(HASKELL_FIXTURE.to_string(), language);
+        let comments = buffer.get_comments();
+
+        assert_eq!(comments.len(), 6);
+
+        assert_eq!(comments[0].line, 0);
+        assert_eq!(comments[0].text, "| Main module");
+
+        // The nested block is a single span, it doesn't end at the first `-}`
+        assert_eq!(comments[1].line, 3);
+        assert_eq!(comments[1].text, "outer");
+        assert_eq!(comments[2].line, 4);
+        assert_eq!(comments[2].text, "   {- inner -}");
+        assert_eq!(comments[3].line, 5);
+        assert_eq!(comments[3].text, "   still outer");
+        assert!(comments[1..4].iter().all(|c| c.comment_type == CommentType::Multi));
+
+        assert_eq!(comments[4].line, 8);
+        assert_eq!(comments[4].text, "print a greeting");
+        assert_eq!(comments[4].comment_type, CommentType::Single);
+
+        assert_eq!(comments[5].line, 9);
+        assert_eq!(comments[5].text, "outer {- inner -} still outer");
         assert_eq!(comments[5].comment_type, CommentType::Multi);
+    }
 
-        assert_eq!(comments[6].text, "With multiples lines");
-        assert_eq!(comments[6].line, 16);
-        assert_eq!(comments[6].comment_type, CommentType::Multi);
+    const CPP_FIXTURE: &str = r#"/**
+ * @brief Compute the sum
+ * of two numbrs.
+ *
+ *     add(1, 2);
+ */
+int add(int a, int b); //!< Returns the sum
+
+/*! Qt style bloc */
+"#;
+
+    #[test]
+    fn test_get_comments_cpp_doxygen() {
+        let language = Language::from_extension("cpp").unwrap();
+
+        let mut buffer = Buffer::from_string(CPP_FIXTURE.to_string(), language);
+        let comments = buffer.get_comments().clone();
 
+        let texts: Vec<(usize, &str)> = comments.iter().map(|c| (c.line, c.text.as_str())).collect();
         assert_eq!(
-            comments[7].text,
-            "Print debug information to compare with the visual content in the browser and verify the order."
+            texts,
+            vec![
+                (1, "@brief Compute the sum"),
+                (2, "of two numbrs."),
+                (4, "    add(1, 2);"),
+                (6, "Returns the sum"),
+                (8, "Qt style bloc"),
+            ]
         );
-        assert_eq!(comments[8].text, "Profiles online should be in the positions: [7, 57] and [3, 15, 17] according to the get_profiles_display_group_settings function.");
-        assert_eq!(comments[9].text, "If you change the initial online IDs, another filter may capture them first. Check if this occurs.");
+
+        // The margin asterisks are kept in the output
+        let mut new_comments = comments.clone();
+        for comment in new_comments.iter_mut() {
+            comment.text = comment.text.replace("numbrs", "numbers").replace("bloc", "block");
+        }
+        buffer.replace_comments(&new_comments).unwrap();
+
         assert_eq!(
-            comments[10].text,
-            "print(f\"profile_list[{position}]: {profiles_list[position]}\")"
+            buffer.to_string(),
+            CPP_FIXTURE.replace("numbrs", "numbers").replace("bloc ", "block ")
         );
-        assert_eq!(comments[11].text, "last");
-        assert_eq!(comments[12].text, "comment");
     }
 
     #[test]
-    fn test_replace_comments() {
-        let language = Language {
-            name: "python".to_string(),
-            comment_symbol: "#".to_string(),
-            ml_comment_symbol: "\"\"\"".to_string(),
-            ml_comment_symbol_close: "\"\"\"".to_string(),
+    fn test_normalize_eof() {
+        let language = Language::from_name("python").unwrap();
+        let normalize = |input: &str, newline_eof: NewlineEof| {
+            let mut buffer = Buffer::from_string(input.to_string(), language.clone());
+            buffer.normalize_eof(newline_eof);
+            buffer.to_string()
         };
 
-        let mut buffer = Buffer::from_string(PYTHON_FIXTURE.to_string(), language);
-        let comments = buffer.get_comments();
+        for input in ["x = 5\n", "x = 5", "x = 5\n\n\n", ""] {
+            assert_eq!(normalize(input, NewlineEof::Preserve), input);
+        }
+
+        assert_eq!(normalize("x = 5\n", NewlineEof::Ensure), "x = 5\n");
+        assert_eq!(normalize("x = 5", NewlineEof::Ensure), "x = 5\n");
+        assert_eq!(normalize("x = 5\n\n\n", NewlineEof::Ensure), "x = 5\n");
+        assert_eq!(normalize("x = 5\r\ny = 6", NewlineEof::Ensure), "x = 5\r\ny = 6\r\n");
+        assert_eq!(normalize("", NewlineEof::Ensure), "");
+
+        assert_eq!(normalize("x = 5\n", NewlineEof::Strip), "x = 5");
+        assert_eq!(normalize("x = 5", NewlineEof::Strip), "x = 5");
+        assert_eq!(normalize("x = 5\n\n\n", NewlineEof::Strip), "x = 5");
+        assert_eq!(normalize("\n", NewlineEof::Strip), "");
+
+        assert_eq!(NewlineEof::from("ensure"), Ok(NewlineEof::Ensure));
+        assert!(NewlineEof::from("always").is_err());
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let language = Language::from_name("cpp").unwrap();
+        let inputs = [
+            CPP_FIXTURE.to_string(),
+            CPP_FIXTURE.replace('\n', "\r\n"),
+            CPP_FIXTURE.trim_end().to_string(),
+            String::new(),
+        ];
 
-        let mut new_comments = vec![];
+        for input in inputs {
+            let mut expected = Buffer::from_string(input.clone(), language.clone());
+            let mut buffer = Buffer::from_reader(std::io::Cursor::new(input.as_bytes()), language.clone()).unwrap();
 
-        for comment in comments.iter() {
-            new_comments.push(Comment {
-                line: comment.line,
-                text: comment.text.replace('a', "e"),
-                comment_type: comment.comment_type,
-            })
+            assert_eq!(buffer.lines, expected.lines);
+            assert_eq!(buffer.crlf, expected.crlf);
+            assert_eq!(buffer.trailing_newline, expected.trailing_newline);
+            assert_eq!(buffer.get_comments(), expected.get_comments());
+            assert_eq!(buffer.to_string(), input);
         }
+    }
+
+    #[test]
+    fn test_unterminated_comment() {
+        let language = Language::from_name("c").unwrap();
+        let input = "int x = 5; /* The answr\nint y = compute(x);\n// A line coment\nreturn y;\n";
+
+        let mut buffer = Buffer::from_string(input.to_string(), language);
+        let comments = buffer.get_comments().clone();
+
+        // The code below the open symbol is not captured, the later comments are still found
+        let texts: Vec<(usize, &str)> = comments.iter().map(|c| (c.line, c.text.as_str())).collect();
+        assert_eq!(texts, vec![(0, "The answr"), (2, "A line coment")]);
+        assert_eq!(buffer.unterminated_comments, vec![0]);
+    }
+
+    #[test]
+    fn test_get_comments_js_margin() {
+        let language = Language::from_name("javascript").unwrap();
+        let input = "/** \n * line one\n * line too\n */\nlet x = 5;\n/*\n * plain blok\n */\n";
+
+        let mut buffer = Buffer::from_string(input.to_string(), language);
+        let comments = buffer.get_comments().clone();
 
+        let texts: Vec<(usize, &str)> = comments.iter().map(|c| (c.line, c.text.as_str())).collect();
+        assert_eq!(texts, vec![(1, "line one"), (2, "line too"), (6, "plain blok")]);
+
+        // The margin is re-emitted once, even if the model adds it back
+        let new_comments = vec![
+            Comment::new(2, "* line two".to_string(), CommentType::Multi),
+            Comment::new(6, "plain block".to_string(), CommentType::Multi),
+        ];
         buffer.replace_comments(&new_comments).unwrap();
 
-        assert_eq!(buffer.lines[0].trim(), "\"\"\"");
-        assert_eq!(buffer.lines[1].trim(), "this is e");
-        assert_eq!(buffer.lines[2].trim(), "multi-line comment"); // Not change
-        assert_eq!(buffer.lines[3].trim(), "\"\"\"");
-        assert_eq!(buffer.lines[4].trim(), "");
-        assert_eq!(buffer.lines[5].trim(), "# this is e single line comment");
         assert_eq!(
-            buffer.lines[9].trim(),
-            "\"\"\"Another multi-line comment, but in e single line\"\"\""
+            buffer.to_string(),
+            "/** \n * line one\n * line two\n */\nlet x = 5;\n/*\n * plain block\n */\n"
         );
     }
 
+    const HTML_FIXTURE: &str = r#"<!DOCTYPE html>
+<html>
+  <!-- Main page of the aplication -->
+  <body class="main"># not a comment // neither</body>
+  <!--
+    Footer with the contact
+    informations
+  -->
+</html>
+"#;
+
     #[test]
-    fn test_json_to_comments() {
-        let json_string = r#"{"single_comments": {"1": "A class that represents a HttpRequest"},"multiline_comments": {"122": "Args:","124": "count -> int: The counter of a loop"}}"#;
-        let comments = serde_json::from_str::<CommentCollection>(json_string)
-            .unwrap()
-            .to_comments();
+    fn test_get_comments_html() {
+        let language = Language::from_extension("html").unwrap();
+        assert_eq!(language.get_comment_type("<p>text</p>"), CommentType::Multi);
 
-        // Create a map for easier verification
-        let comments_map: HashMap<_, _> = comments
-            .iter()
-            .map(|c| (c.line, (c.text.as_str(), c.comment_type)))
-            .collect();
+        let mut buffer = Buffer::from_string(HTML_FIXTURE.to_string(), language);
+        let comments = buffer.get_comments().clone();
 
+        let texts: Vec<(usize, &str)> = comments.iter().map(|c| (c.line, c.text.as_str())).collect();
         assert_eq!(
-            comments_map.get(&1),
-            Some(&("A class that represents a HttpRequest", CommentType::Single))
+            texts,
+            vec![
+                (2, "Main page of the aplication"),
+                (5, "  Footer with the contact"),
+                (6, "  informations")
+            ]
         );
-        assert_eq!(comments_map.get(&122), Some(&("Args:", CommentType::Multi)));
+        assert!(comments.iter().all(|c| c.comment_type == CommentType::Multi));
+
+        let mut new_comments = comments.clone();
+        for comment in new_comments.iter_mut() {
+            comment.text = comment.text.replace("aplication", "application").replace("informations", "information");
+        }
+        buffer.replace_comments(&new_comments).unwrap();
+
         assert_eq!(
-            comments_map.get(&124),
-            Some(&("count -> int: The counter of a loop", CommentType::Multi))
+            buffer.to_string(),
+            HTML_FIXTURE.replace("aplication", "application").replace("informations", "information")
         );
+    }
 
-        let json_string = r#"{  "single_comments": {    "42": "Another comment with spelling mistakes?"  },  "multiline_comments": {    "4": "Docstring of a function",    "6": "Args:",    "7": "dictarg (dict): A dictionary argument",    "19": "Multiline comment that is not a docstring",    "26": "Alice's Adventures in Wonderland by Lewis Carroll",    "27": "A Project Gutenberg eBook"  }}"#;
-        let comments = serde_json::from_str::<CommentCollection>(json_string)
-            .unwrap()
-            .to_comments();
+    const SQL_FIXTURE: &str = r#"-- Select the activ users
+SELECT name FROM users WHERE note = '-- not a comment' AND title = 'it''s -- not either';
+/* Users without
+   an email adress */
+SELECT name FROM users WHERE email IS NULL; -- Trailing coment
+"#;
 
-        let comments_map: HashMap<_, _> = comments
-            .iter()
-            .map(|c| (c.line, (c.text.as_str(), c.comment_type)))
-            .collect();
+    #[test]
+    fn test_get_comments_sql() {
+        let language = Language::from_extension("sql").unwrap();
 
+        let mut buffer = Buffer::from_string(SQL_FIXTURE.to_string(), language);
+        let comments = buffer.get_comments();
+
+        let texts: Vec<(usize, &str, CommentType)> =
+            comments.iter().map(|c| (c.line, c.text.as_str(), c.comment_type)).collect();
         assert_eq!(
-            comments_map.get(&42),
-            Some(&("Another comment with spelling mistakes?", CommentType::Single))
+            texts,
+            vec![
+                (0, "Select the activ users", CommentType::Single),
+                (2, "Users without", CommentType::Multi),
+                (3, "   an email adress", CommentType::Multi),
+                (4, "Trailing coment", CommentType::Single),
+            ]
+        );
+
+        // MySQL also uses `#`
+        let mysql = Language::from_name("mysql").unwrap();
+        let mut buffer = Buffer::from_string("SELECT 1; # A coment\nSELECT '#1';\n".to_string(), mysql);
+        let comments = buffer.get_comments();
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].text, "A coment");
+    }
+
+    const YAML_FIXTURE: &str = r##"# Colors of the them
+primary: "#FF0000"
+secondary: '#00FF00' # Used for the bordres
+"##;
+
+    const TOML_FIXTURE: &str = r##"# Package metdata
+[package]
+name = "neospeller"
+color = "#FF0000"  # Brand colr
+"##;
+
+    #[test]
+    fn test_get_comments_yaml_toml() {
+        let yaml = Language::from_extension("yml").unwrap();
+        let mut buffer = Buffer::from_string(YAML_FIXTURE.to_string(), yaml);
+        let texts: Vec<(usize, String)> = buffer.get_comments().iter().map(|c| (c.line, c.text.clone())).collect();
+        assert_eq!(
+            texts,
+            vec![(0, "Colors of the them".to_string()), (2, "Used for the bordres".to_string())]
+        );
+
+        let toml = Language::from_extension("toml").unwrap();
+        let mut buffer = Buffer::from_string(TOML_FIXTURE.to_string(), toml);
+        let texts: Vec<(usize, String)> = buffer.get_comments().iter().map(|c| (c.line, c.text.clone())).collect();
+        assert_eq!(texts, vec![(0, "Package metdata".to_string()), (3, "Brand colr".to_string())]);
+        assert!(buffer.comments.iter().all(|c| c.comment_type == CommentType::Single));
+    }
+
+    const PERL_FIXTURE: &str = r##"#!/usr/bin/perl
+my $total = 5; # Totl of items
+
+=head1 NAME
+
+Counter - count the itmes
+
+=cut
+
+my $x
+  = 5;
+"##;
+
+    #[test]
+    fn test_get_comments_perl() {
+        let language = Language::from_extension("pl").unwrap();
+
+        let mut buffer = Buffer::from_string(PERL_FIXTURE.to_string(), language);
+        let comments = buffer.get_comments();
+
+        let texts: Vec<(usize, &str, CommentType)> =
+            comments.iter().map(|c| (c.line, c.text.as_str(), c.comment_type)).collect();
+        assert_eq!(
+            texts,
+            vec![
+                (1, "Totl of items", CommentType::Single),
+                (3, "NAME", CommentType::Multi),
+                (5, "Counter - count the itmes", CommentType::Multi),
+            ]
+        );
+
+        let mut buffer = Buffer::from_string(PERL_FIXTURE.to_string(), Language::from_name("perl").unwrap());
+        buffer.get_comments();
+        buffer
+            .replace_comments(&[Comment::new(5, "Counter - count the items".to_string(), CommentType::Multi)])
+            .unwrap();
+        assert!(buffer.to_string().contains("\nCounter - count the items\n\n=cut\n"));
+    }
+
+    const CLOJURE_FIXTURE: &str = r##";;;; Utilties for numbers
+(defn square [x]
+  ;; header comment
+  (* x x)) ; Multiply by itslef
+"##;
+
+    #[test]
+    fn test_get_comments_lisp() {
+        let language = Language::from_extension("clj").unwrap();
+
+        let mut buffer = Buffer::from_string(CLOJURE_FIXTURE.to_string(), language);
+        let texts: Vec<(usize, String)> = buffer.get_comments().iter().map(|c| (c.line, c.text.clone())).collect();
+        assert_eq!(
+            texts,
+            vec![
+                (0, "Utilties for numbers".to_string()),
+                (2, "header comment".to_string()),
+                (3, "Multiply by itslef".to_string()),
+            ]
+        );
+
+        buffer
+            .replace_comments(&[Comment::new(0, "Utilities for numbers".to_string(), CommentType::Single)])
+            .unwrap();
+        assert!(buffer.to_string().starts_with(";;;; Utilities for numbers\n"));
+
+        let language = Language::from_extension("scm").unwrap();
+        assert_eq!(language.name, "lisp");
+    }
+
+    const POWERSHELL_FIXTURE: &str = r##"<#
+.SYNOPSIS
+    Gets the itmes of a list.
+.PARAMETER Name
+    The nmae of the list.
+#>
+function Get-Items($Name) {
+    <# Inline blok #> Get-Content $Name
+}
+"##;
+
+    #[test]
+    fn test_get_comments_powershell() {
+        let language = Language::from_extension("ps1").unwrap();
+
+        let mut buffer = Buffer::from_string(POWERSHELL_FIXTURE.to_string(), language);
+        let texts: Vec<(usize, &str, CommentType)> = buffer
+            .get_comments()
+            .iter()
+            .map(|c| (c.line, c.text.as_str(), c.comment_type))
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                (1, ".SYNOPSIS", CommentType::Multi),
+                (2, "    Gets the itmes of a list.", CommentType::Multi),
+                (3, ".PARAMETER Name", CommentType::Multi),
+                (4, "    The nmae of the list.", CommentType::Multi),
+                (7, "Inline blok", CommentType::Multi),
+            ]
+        );
+
+        // The help keywords are kept when the model only fixes the text
+        buffer
+            .replace_comments(&[
+                Comment::new(1, ".SYNOPSIS".to_string(), CommentType::Multi),
+                Comment::new(2, "Gets the items of a list.".to_string(), CommentType::Multi),
+                Comment::new(3, ".PARAMETER Name".to_string(), CommentType::Multi),
+                Comment::new(4, "The name of the list.".to_string(), CommentType::Multi),
+            ])
+            .unwrap();
+        assert!(buffer.to_string().starts_with(
+            "<#\n.SYNOPSIS\n    Gets the items of a list.\n.PARAMETER Name\n    The name of the list.\n#>\n"
+        ));
+    }
+
+    const OCAML_FIXTURE: &str = r##"(** Compute the sqare of a number *)
+let square x = x * x
+
+(* Outer coment (* inner
+   coment *) still outer *)
+let cube x = x * square x
+"##;
+
+    #[test]
+    fn test_get_comments_ocaml() {
+        let language = Language::from_extension("ml").unwrap();
+
+        let mut buffer = Buffer::from_string(OCAML_FIXTURE.to_string(), language);
+        let texts: Vec<(usize, &str, CommentType)> = buffer
+            .get_comments()
+            .iter()
+            .map(|c| (c.line, c.text.as_str(), c.comment_type))
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                (0, "Compute the sqare of a number", CommentType::Multi),
+                (3, "Outer coment (* inner", CommentType::Multi),
+                (4, "   coment *) still outer", CommentType::Multi),
+            ]
+        );
+
+        buffer
+            .replace_comments(&[Comment::new(0, "Compute the square of a number".to_string(), CommentType::Multi)])
+            .unwrap();
+        assert!(buffer.to_string().starts_with("(** Compute the square of a number *)\nlet square x = x * x\n"));
+
+        // F# also has line comments
+        let language = Language::from_extension("fs").unwrap();
+        let mut buffer = Buffer::from_string("let x = 1 // A coment\n(* a (* b *) c *)\n".to_string(), language);
+        let texts: Vec<&str> = buffer.get_comments().iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["A coment", "a (* b *) c"]);
+    }
+
+    const FORTRAN_FIXTURE: &str = r##"! Compute the totl of the array
+program total
+  integer :: x = 1 ! Initial valeu
+  print *, 'Hello! It''s me' ! Greet the usr
+  print *, "Done!"
+end program total
+"##;
+
+    #[test]
+    fn test_get_comments_fortran() {
+        let language = Language::from_extension("f90").unwrap();
+
+        let mut buffer = Buffer::from_string(FORTRAN_FIXTURE.to_string(), language);
+        let texts: Vec<(usize, &str)> = buffer.get_comments().iter().map(|c| (c.line, c.text.as_str())).collect();
+        assert_eq!(
+            texts,
+            vec![
+                (0, "Compute the totl of the array"),
+                (2, "Initial valeu"),
+                (3, "Greet the usr"),
+            ]
+        );
+
+        buffer
+            .replace_comments(&[
+                Comment::new(2, "Initial value".to_string(), CommentType::Single),
+                Comment::new(3, "Greet the user".to_string(), CommentType::Single),
+            ])
+            .unwrap();
+        let output = buffer.to_string();
+        assert!(output.contains("\n  integer :: x = 1 ! Initial value\n  print *, 'Hello! It''s me' ! Greet the user\n"));
+    }
+
+    const ASM_FIXTURE: &str = r##"; Entry pont of the program
+section .data
+    msg db 'Hello; world # 1', 0 ; The mesage
+_start:
+    # Load the adress
+    movl $msg, %eax # Into the acumulator
+"##;
+
+    #[test]
+    fn test_get_comments_asm() {
+        let language = Language::from_extension("asm").unwrap();
+
+        let mut buffer = Buffer::from_string(ASM_FIXTURE.to_string(), language);
+        let texts: Vec<(usize, &str)> = buffer.get_comments().iter().map(|c| (c.line, c.text.as_str())).collect();
+        assert_eq!(
+            texts,
+            vec![
+                (0, "Entry pont of the program"),
+                (2, "The mesage"),
+                (4, "Load the adress"),
+                (5, "Into the acumulator"),
+            ]
+        );
+
+        buffer
+            .replace_comments(&[
+                Comment::new(2, "The message".to_string(), CommentType::Single),
+                Comment::new(5, "Into the accumulator".to_string(), CommentType::Single),
+            ])
+            .unwrap();
+        let output = buffer.to_string();
+        assert!(output.contains("\n    msg db 'Hello; world # 1', 0 ; The message\n"));
+        assert!(output.contains("\n    movl $msg, %eax # Into the accumulator\n"));
+
+        assert_eq!(Language::from_extension("S").unwrap().name, "asm");
+    }
+
+    const SWIFT_FIXTURE: &str = r##"/// Compute the `sum` of two numbrs.
+///
+/// See [the docs](https://swift.org) for detals.
+func sum(_ a: Int, _ b: Int) -> Int {
+    /* Outer coment /* inner note */ still outer */
+    return a + b // The resul
+}
+/**
+ * Multi-line doc with a `code` span
+ * and a secnd line
+ */
+"##;
+
+    #[test]
+    fn test_get_comments_swift() {
+        let language = Language::from_extension("swift").unwrap();
+        assert!(language.markdown_code);
+
+        assert_round_trip(
+            SWIFT_FIXTURE,
+            language,
+            &[
+                (0, "Compute the `sum` of two numbrs.", CommentType::Single),
+                (2, "See [the docs](https://swift.org) for detals.", CommentType::Single),
+                (4, "Outer coment /* inner note */ still outer", CommentType::Multi),
+                (5, "The resul", CommentType::Single),
+                (8, "Multi-line doc with a `code` span", CommentType::Multi),
+                (9, "and a secnd line", CommentType::Multi),
+            ],
+            &[
+                ("numbrs", "numbers"),
+                ("detals", "details"),
+                ("coment", "comment"),
+                ("resul", "result"),
+                ("secnd", "second"),
+            ],
+        );
+    }
+
+    const CSHARP_FIXTURE: &str = r##"/// <summary>
+/// Adds two numbrs and returns the resul.
+/// </summary>
+/// <param name="a">The frist value</param>
+public int Sum(int a, int b)
+{
+    /* Block coment */
+    return a + b; // Simple additon
+}
+"##;
+
+    #[test]
+    fn test_get_comments_csharp() {
+        let language = Language::from_extension("cs").unwrap();
+        assert!(language.xml_doc_tags);
+
+        assert_round_trip(
+            CSHARP_FIXTURE,
+            language,
+            &[
+                (0, "<summary>", CommentType::Single),
+                (1, "Adds two numbrs and returns the resul.", CommentType::Single),
+                (2, "</summary>", CommentType::Single),
+                (3, "<param name=\"a\">The frist value</param>", CommentType::Single),
+                (6, "Block coment", CommentType::Multi),
+                (7, "Simple additon", CommentType::Single),
+            ],
+            &[
+                ("numbrs", "numbers"),
+                ("resul.", "result."),
+                ("frist", "first"),
+                ("coment", "comment"),
+                ("additon", "addition"),
+            ],
+        );
+    }
+
+    const KOTLIN_FIXTURE: &str = r##"/**
+ * Returns the `sum` of two numbrs.
+ *
+ * @param a the frist value
+ * @return the resul of the addition
+ */
+fun sum(a: Int, b: Int): Int {
+    /* Outer coment /* inner note */ still outer */
+    return a + b // Simple additon
+}
+"##;
+
+    #[test]
+    fn test_get_comments_kotlin() {
+        let language = Language::from_extension("kt").unwrap();
+        assert!(language.nested_ml_comments && language.markdown_code);
+
+        assert_round_trip(
+            KOTLIN_FIXTURE,
+            language,
+            &[
+                (1, "Returns the `sum` of two numbrs.", CommentType::Multi),
+                (3, "@param a the frist value", CommentType::Multi),
+                (4, "@return the resul of the addition", CommentType::Multi),
+                (7, "Outer coment /* inner note */ still outer", CommentType::Multi),
+                (8, "Simple additon", CommentType::Single),
+            ],
+            &[
+                ("numbrs", "numbers"),
+                ("frist", "first"),
+                ("resul ", "result "),
+                ("coment", "comment"),
+                ("additon", "addition"),
+            ],
+        );
+    }
+
+    const SCALA_FIXTURE: &str = r##"/** Parses the `input` into tokns.
+  *
+  * @param input the sorce text
+  * @return the list of tokens
+  */
+def tokenize(input: String): List[Token] = {
+  /* A nested /* blok */ comment */
+  Nil // Not implmented
+}
+"##;
+
+    #[test]
+    fn test_get_comments_scala() {
+        let language = Language::from_extension("scala").unwrap();
+        assert!(language.nested_ml_comments && language.markdown_code);
+
+        // The Scaladoc margin, aligned with the second `*` of `/**`, is kept in the output
+        assert_round_trip(
+            SCALA_FIXTURE,
+            language,
+            &[
+                (0, "Parses the `input` into tokns.", CommentType::Multi),
+                (2, "@param input the sorce text", CommentType::Multi),
+                (3, "@return the list of tokens", CommentType::Multi),
+                (6, "A nested /* blok */ comment", CommentType::Multi),
+                (7, "Not implmented", CommentType::Single),
+            ],
+            &[
+                ("tokns", "tokens"),
+                ("sorce", "source"),
+                ("blok", "block"),
+                ("implmented", "implemented"),
+            ],
+        );
+    }
+
+    const ELIXIR_FIXTURE: &str = r##"defmodule Math do
+  @moduledoc """
+  Helpers for basic arithmetc.
+  """
+
+  @doc """
+  Sums two numbrs.
+
+      iex> Math.sum(1, 2)
+      3
+  """
+  def sum(a, b), do: a + b # The resul
+  def hash, do: "# not a comment"
+end
+"##;
+
+    #[test]
+    fn test_get_comments_elixir() {
+        let language = Language::from_extension("ex").unwrap();
+
+        let mut buffer = Buffer::from_string(ELIXIR_FIXTURE.to_string(), language);
+        let comments = buffer.get_comments().clone();
+        let texts: Vec<(usize, &str, CommentType)> =
+            comments.iter().map(|c| (c.line, c.text.as_str(), c.comment_type)).collect();
+        assert_eq!(
+            texts,
+            vec![
+                (2, "Helpers for basic arithmetc.", CommentType::Multi),
+                (6, "Sums two numbrs.", CommentType::Multi),
+                (11, "The resul", CommentType::Single),
+            ]
+        );
+
+        buffer
+            .replace_comments(&[
+                Comment::new(2, "Helpers for basic arithmetic.".to_string(), CommentType::Multi),
+                Comment::new(6, "Sums two numbers.".to_string(), CommentType::Multi),
+                Comment::new(11, "The result".to_string(), CommentType::Single),
+            ])
+            .unwrap();
+        assert_eq!(
+            buffer.to_string(),
+            ELIXIR_FIXTURE
+                .replace("arithmetc", "arithmetic")
+                .replace("numbrs", "numbers")
+                .replace("resul\n", "result\n")
+        );
+    }
+
+    const ELIXIR_HEREDOC_FIXTURE: &str = r##"defmodule Repo do
+  @query """
+  SELECT naem FROM users # all of them
+  """
+
+  @typedoc ~S"""
+  A usr record.
+  """
+  def all, do: run(@query) # Run the qery
+end
+"##;
+
+    #[test]
+    fn test_get_comments_elixir_heredoc_string() {
+        let language = Language::from_extension("exs").unwrap();
+
+        // The plain heredoc is a string, its lines are never sent, not even the `#` in it
+        let mut buffer = Buffer::from_string(ELIXIR_HEREDOC_FIXTURE.to_string(), language);
+        let comments = buffer.get_comments().clone();
+        let texts: Vec<(usize, &str, CommentType)> =
+            comments.iter().map(|c| (c.line, c.text.as_str(), c.comment_type)).collect();
+        assert_eq!(
+            texts,
+            vec![
+                (6, "A usr record.", CommentType::Multi),
+                (8, "Run the qery", CommentType::Single),
+            ]
+        );
+    }
+
+    const ERLANG_FIXTURE: &str = r##"%%% Module for the greting
+-module(greet).
+%% Say helo
+hello() -> io:format("100% sure~n"). % Print the mesage
+"##;
+
+    #[test]
+    fn test_get_comments_erlang() {
+        let language = Language::from_extension("erl").unwrap();
+
+        let mut buffer = Buffer::from_string(ERLANG_FIXTURE.to_string(), language);
+        let texts: Vec<(usize, &str)> = buffer.get_comments().iter().map(|c| (c.line, c.text.as_str())).collect();
+        assert_eq!(
+            texts,
+            vec![(0, "Module for the greting"), (2, "Say helo"), (3, "Print the mesage")]
+        );
+
+        buffer
+            .replace_comments(&[
+                Comment::new(0, "Module for the greeting".to_string(), CommentType::Single),
+                Comment::new(3, "Print the message".to_string(), CommentType::Single),
+            ])
+            .unwrap();
+        let output = buffer.to_string();
+        assert!(output.starts_with("%%% Module for the greeting\n"));
+        assert!(output.contains("\nhello() -> io:format(\"100% sure~n\"). % Print the message\n"));
+    }
+
+    const VIM_FIXTURE: &str = r##"" Plugin setings
+set number " Show the line nubmers
+let g:greeting = "hello" " The greting
+let s:quote = 'a " b'
+echo "no comment here"
+  " Indented coment with "quotes"
+"##;
+
+    #[test]
+    fn test_get_comments_vim() {
+        let language = Language::from_extension("vim").unwrap();
+
+        let mut buffer = Buffer::from_string(VIM_FIXTURE.to_string(), language);
+        let texts: Vec<(usize, &str)> = buffer.get_comments().iter().map(|c| (c.line, c.text.as_str())).collect();
+        assert_eq!(
+            texts,
+            vec![
+                (0, "Plugin setings"),
+                (1, "Show the line nubmers"),
+                (2, "The greting"),
+                (5, "Indented coment with \"quotes\""),
+            ]
+        );
+
+        buffer
+            .replace_comments(&[
+                Comment::new(1, "Show the line numbers".to_string(), CommentType::Single),
+                Comment::new(2, "The greeting".to_string(), CommentType::Single),
+            ])
+            .unwrap();
+        let output = buffer.to_string();
+        assert!(output.contains("\nset number \" Show the line numbers\nlet g:greeting = \"hello\" \" The greeting\n"));
+        assert!(output.contains("\nlet s:quote = 'a \" b'\necho \"no comment here\"\n"));
+    }
+
+    const MARKDOWN_FIXTURE: &str = r##"# Instalation
+
+Run the comand below:
+
+```bash
+# Instal the crate
+cargo instal neospeller
+```
+
+~~~~
+```
+Nested fense
+~~~~
+
+The `--writte` flag is kept.
+"##;
+
+    #[test]
+    fn test_get_comments_markdown() {
+        let language = Language::from_extension("md").unwrap();
+
+        let mut buffer = Buffer::from_string(MARKDOWN_FIXTURE.to_string(), language);
+        let texts: Vec<(usize, &str)> = buffer.get_comments().iter().map(|c| (c.line, c.text.as_str())).collect();
+        assert_eq!(
+            texts,
+            vec![
+                (0, "# Instalation"),
+                (2, "Run the comand below:"),
+                (14, "The `--writte` flag is kept."),
+            ]
+        );
+
+        buffer
+            .replace_comments(&[
+                Comment::new(0, "# Installation".to_string(), CommentType::Single),
+                Comment::new(2, "Run the command below:".to_string(), CommentType::Single),
+            ])
+            .unwrap();
+
+        let output = buffer.to_string();
+        assert!(output.starts_with("# Installation\n\nRun the command below:\n"));
+        assert!(output.contains("```bash\n# Instal the crate\ncargo instal neospeller\n```\n"));
+        assert!(output.contains("~~~~\n```\nNested fense\n~~~~\n"));
+    }
+
+    const MARKDOWN_INDENTED_FIXTURE: &str = r##"- Top item
+  - nestd item
+    continued line
+
+Example:
+
+    let x = compute(y);
+    x.instal()
+
+Fnal line
+"##;
+
+    #[test]
+    fn test_get_comments_markdown_indentation() {
+        let language = Language::from_extension("md").unwrap();
+
+        // The indented code block is skipped, the indented list lines are not
+        let mut buffer = Buffer::from_string(MARKDOWN_INDENTED_FIXTURE.to_string(), language);
+        let texts: Vec<(usize, &str)> = buffer.get_comments().iter().map(|c| (c.line, c.text.as_str())).collect();
+        assert_eq!(
+            texts,
+            vec![
+                (0, "- Top item"),
+                (1, "  - nestd item"),
+                (2, "    continued line"),
+                (4, "Example:"),
+                (9, "Fnal line"),
+            ]
+        );
+
+        // The model drops the indentation, it is restored
+        buffer
+            .replace_comments(&[
+                Comment::new(1, "- nested item".to_string(), CommentType::Single),
+                Comment::new(9, "Final line".to_string(), CommentType::Single),
+            ])
+            .unwrap();
+        assert_eq!(
+            buffer.to_string(),
+            MARKDOWN_INDENTED_FIXTURE.replace("nestd", "nested").replace("Fnal", "Final")
+        );
+    }
+
+    #[test]
+    fn test_shebang_is_not_a_comment() {
+        let language = Language::from_name("python").unwrap();
+        let input = "#!/usr/bin/env python3\n# Entry point\nx = 5\n";
+
+        let mut buffer = Buffer::from_string(input.to_string(), language);
+        let comments = buffer.get_comments();
+
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].line, 1);
+        assert_eq!(comments[0].text, "Entry point");
+
+        // Only the first line can be a shebang
+        let language = Language::from_name("bash").unwrap();
+        let mut buffer = Buffer::from_string("echo 1\n#!not a shebang\n".to_string(), language);
+        assert_eq!(buffer.get_comments().len(), 1);
+    }
+
+    #[test]
+    fn test_get_comments_python() {
+        let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+
+        let mut buffer = Buffer::from_string(PYTHON_FIXTURE.to_string(), language);
+        let comments = buffer.get_comments();
+
+        assert_eq!(comments.len(), 13);
+
+        assert_eq!(comments[0].line, 1);
+        assert_eq!(comments[0].text, "this is a");
+        assert_eq!(comments[0].comment_type, CommentType::Multi);
+
+        assert_eq!(comments[1].text, "multi-line comment");
+        assert_eq!(comments[1].comment_type, CommentType::Multi);
+        assert_eq!(comments[1].line, 2);
+
+        assert_eq!(comments[2].line, 5);
+        assert_eq!(comments[2].text, "this is a single line comment");
+        assert_eq!(comments[2].comment_type, CommentType::Single);
+
+        assert_eq!(
+            comments[3].text,
+            "Another multi-line comment, but in a single line"
+        );
+        assert_eq!(comments[3].line, 9);
+        assert_eq!(comments[3].comment_type, CommentType::Multi);
+
+        assert_eq!(comments[4].text, "Unestruturated multi-line comment");
+        assert_eq!(comments[4].line, 11);
+        assert_eq!(comments[4].comment_type, CommentType::Multi);
+
+        assert_eq!(comments[5].text, "Another unestruturated multi-line comment");
+        assert_eq!(comments[5].line, 15);
+        assert_eq!(comments[5].comment_type, CommentType::Multi);
+
+        assert_eq!(comments[6].text, "With multiples lines");
+        assert_eq!(comments[6].line, 16);
+        assert_eq!(comments[6].comment_type, CommentType::Multi);
+
+        assert_eq!(
+            comments[7].text,
+            "Print debug information to compare with the visual content in the browser and verify the order."
+        );
+        assert_eq!(comments[8].text, "Profiles online should be in the positions: [7, 57] and [3, 15, 17] according to the get_profiles_display_group_settings function.");
+        assert_eq!(comments[9].text, "If you change the initial online IDs, another filter may capture them first. Check if this occurs.");
+        assert_eq!(
+            comments[10].text,
+            "print(f\"profile_list[{position}]: {profiles_list[position]}\")"
+        );
+        assert_eq!(comments[11].text, "last");
+        assert_eq!(comments[12].text, "comment");
+    }
+
+    #[test]
+    fn test_replace_comments() {
+        let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+
+        let mut buffer = Buffer::from_string(PYTHON_FIXTURE.to_string(), language);
+        let comments = buffer.get_comments();
+
+        let mut new_comments = comments.clone();
+        for comment in new_comments.iter_mut() {
+            comment.text = comment.text.replace('a', "e");
+        }
+
+        buffer.replace_comments(&new_comments).unwrap();
+
+        assert_eq!(buffer.lines[0].trim(), "\"\"\"");
+        assert_eq!(buffer.lines[1].trim(), "this is e");
+        assert_eq!(buffer.lines[2].trim(), "multi-line comment"); // Not change
+        assert_eq!(buffer.lines[3].trim(), "\"\"\"");
+        assert_eq!(buffer.lines[4].trim(), "");
+        assert_eq!(buffer.lines[5].trim(), "# this is e single line comment");
+        assert_eq!(
+            buffer.lines[9].trim(),
+            "\"\"\"Another multi-line comment, but in e single line\"\"\""
+        );
+    }
+
+    const PYTHON_SINGLE_QUOTES_FIXTURE: &str = r##"'''Modul docstring with single quotes'''
+
+def quote():
+    '''
+    Return the """ symbol, it dosn't close this docstring.
+    '''
+    return '"""'
+
+def other():
+    """It's a docstring with ''' insde."""
+"##;
+
+    #[test]
+    fn test_get_comments_python_single_quotes() {
+        let language = Language::from_extension("py").unwrap();
+
+        let mut buffer = Buffer::from_string(PYTHON_SINGLE_QUOTES_FIXTURE.to_string(), language);
+        let texts: Vec<(usize, &str)> = buffer.get_comments().iter().map(|c| (c.line, c.text.as_str())).collect();
+        assert_eq!(
+            texts,
+            vec![
+                (0, "Modul docstring with single quotes"),
+                (4, "Return the \"\"\" symbol, it dosn't close this docstring."),
+                (9, "It's a docstring with ''' insde."),
+            ]
+        );
+
+        buffer
+            .replace_comments(&[
+                Comment::new(0, "Module docstring with single quotes".to_string(), CommentType::Multi),
+                Comment::new(4, "Return the \"\"\" symbol, it doesn't close this docstring.".to_string(), CommentType::Multi),
+                Comment::new(9, "It's a docstring with ''' inside.".to_string(), CommentType::Multi),
+            ])
+            .unwrap();
+        assert_eq!(
+            buffer.to_string(),
+            PYTHON_SINGLE_QUOTES_FIXTURE
+                .replace("Modul", "Module")
+                .replace("dosn't", "doesn't")
+                .replace("insde", "inside")
+        );
+    }
+
+    #[test]
+    fn test_docstring_blank_lines() {
+        let language = Language::from_name("python").unwrap();
+        let input = "def f():\n    \"\"\"Summary line.\n\n    Details of the functon.\n    \n     * \n    \"\"\"\n";
+
+        let mut buffer = Buffer::from_string(input.to_string(), language);
+        let lines: Vec<(usize, &str)> = buffer.get_comments().iter().map(|c| (c.line, c.text.as_str())).collect();
+        assert_eq!(
+            lines,
+            vec![(1, "Summary line."), (3, "Details of the functon."), (5, " *")]
+        );
+
+        // The blank lines, even with trailing whitespace, are kept untouched
+        buffer
+            .replace_comments(&[Comment::new(3, "Details of the function.".to_string(), CommentType::Multi)])
+            .unwrap();
+        assert_eq!(buffer.to_string(), input.replace("functon", "function"));
+
+        // Also from the JSON of the model, where the blank lines are missing
+        let collection = CommentCollection::from_comments(buffer.comments.clone());
+        let json = serde_json::to_string(&collection).unwrap();
+        assert!(!json.contains("\"\""));
+    }
+
+    #[test]
+    fn test_indented_docstring_body() {
+        let language = Language::from_name("python").unwrap();
+        let input = r#"def area(r):
+    """
+    Compute the aera of a circle, e.g.:
+
+        >>> area(1)  # the unit circle
+        3.14
+    """
+    return 3.14 * r * r
+"#;
+
+        let mut buffer = Buffer::from_string(input.to_string(), language);
+        let comments = buffer.get_comments();
+
+        assert_eq!(comments[0].text, "Compute the aera of a circle, e.g.:");
+        assert_eq!(comments[1].text, "    >>> area(1)  # the unit circle");
+        assert_eq!(comments[2].text, "    3.14");
+
+        // The indentation is restored even if the model drops it
+        let new_comments = vec![
+            Comment::new(2, "Compute the area of a circle, e.g.:".to_string(), CommentType::Multi),
+            Comment::new(4, ">>> area(1)  # the unit circle".to_string(), CommentType::Multi),
+        ];
+        buffer.replace_comments(&new_comments).unwrap();
+
+        assert_eq!(buffer.to_string(), input.replace("aera", "area"));
+    }
+
+    #[test]
+    fn test_json_to_comments() {
+        let json_string = r#"{"single_comments": {"1": "A class that represents a HttpRequest"},"multiline_comments": {"122": "Args:","124": "count -> int: The counter of a loop"}}"#;
+        let comments = serde_json::from_str::<CommentCollection>(json_string)
+            .unwrap()
+            .to_comments();
+
+        // Create a map for easier verification
+        let comments_map: HashMap<_, _> = comments
+            .iter()
+            .map(|c| (c.line, (c.text.as_str(), c.comment_type)))
+            .collect();
+
+        assert_eq!(
+            comments_map.get(&1),
+            Some(&("A class that represents a HttpRequest", CommentType::Single))
+        );
+        assert_eq!(comments_map.get(&122), Some(&("Args:", CommentType::Multi)));
+        assert_eq!(
+            comments_map.get(&124),
+            Some(&("count -> int: The counter of a loop", CommentType::Multi))
+        );
+
+        let json_string = r#"{  "single_comments": {    "42": "Another comment with spelling mistakes?"  },  "multiline_comments": {    "4": "Docstring of a function",    "6": "Args:",    "7": "dictarg (dict): A dictionary argument",    "19": "Multiline comment that is not a docstring",    "26": "Alice's Adventures in Wonderland by Lewis Carroll",    "27": "A Project Gutenberg eBook"  }}"#;
+        let comments = serde_json::from_str::<CommentCollection>(json_string)
+            .unwrap()
+            .to_comments();
+
+        let comments_map: HashMap<_, _> = comments
+            .iter()
+            .map(|c| (c.line, (c.text.as_str(), c.comment_type)))
+            .collect();
+
+        assert_eq!(
+            comments_map.get(&42),
+            Some(&("Another comment with spelling mistakes?", CommentType::Single))
         );
         assert_eq!(
             comments_map.get(&4),
@@ -492,16 +1855,19 @@ CONSTANT = 5
         let comments = vec![
             Comment {
                 line: 1,
+                col: 0,
                 text: "A class that represents a HttpRequest".to_string(),
                 comment_type: CommentType::Single,
             },
             Comment {
                 line: 122,
+                col: 0,
                 text: "Args:".to_string(),
                 comment_type: CommentType::Multi,
             },
             Comment {
                 line: 124,
+                col: 0,
                 text: "count -> int: The counter of a loop".to_string(),
                 comment_type: CommentType::Multi,
             },
@@ -542,7 +1908,7 @@ CONSTANT = 5
         assert_eq!(result_text.chars().last().unwrap(), ',');
 
         // Simulate API response
-        let text = r#"{"single_comments":{},"multiline_comments":{"0":"sticky = \"Always focus on the main concepts; I want to understand the material, not just copy and paste\","}}"#;
+        let text = r#"{"single_comments":{"0":"sticky = \"Always focus on the main concepts; I want to understand the material, not just copy and paste\","},"multiline_comments":{}}"#;
 
         let comments_collection = CommentCollection::from_comments(buffer.comments);
         let parsed_comments = serde_json::to_string(&comments_collection).unwrap();
@@ -551,7 +1917,204 @@ CONSTANT = 5
         buffer.json_to_comments(text).unwrap();
 
         // Must keep the comma.
-        assert!(parsed_comments[parsed_comments.len() - 4..].contains(','));
+        assert!(parsed_comments.contains("paste\\\","));
+        assert!(buffer.to_string().starts_with("sticky = \"Always focus"));
         assert_eq!(buffer.to_string().chars().last().unwrap(), ',');
     }
+
+    #[test]
+    fn test_plain_text_paragraph() {
+        let language = Language::from_name("text").unwrap();
+        let text = "First line of the paragraph.\nSecond line # with symbols //\n\n  Indented line\n";
+
+        let mut buffer = Buffer::from_string(text.to_string(), language);
+        let comments = buffer.get_comments();
+
+        let texts: Vec<(usize, &str)> = comments.iter().map(|c| (c.line, c.text.as_str())).collect();
+        assert_eq!(
+            texts,
+            vec![
+                (0, "First line of the paragraph."),
+                (1, "Second line # with symbols //"),
+                (3, "  Indented line"),
+            ]
+        );
+        assert!(comments.iter().all(|c| c.comment_type == CommentType::Single));
+    }
+
+    #[test]
+    fn test_crlf_round_trip() {
+        let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+        let input = "# first comment\r\nx = 5\r\n# second comment";
+
+        let buffer = Buffer::from_string(input.to_string(), language.clone());
+        assert!(buffer.crlf);
+        assert_eq!(buffer.lines[0], "# first comment");
+        assert_eq!(buffer.to_string(), input);
+
+        let buffer = Buffer::from_string(input.replace("\r\n", "\n"), language);
+        assert!(!buffer.crlf);
+        assert_eq!(buffer.to_string(), input.replace("\r\n", "\n"));
+    }
+
+    #[test]
+    fn test_trailing_newline() {
+        let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+
+        let input = "# comment\nx = 5\n";
+        let buffer = Buffer::from_string(input.to_string(), language.clone());
+        assert_eq!(buffer.to_string().as_bytes(), input.as_bytes());
+
+        let input = "# comment\nx = 5";
+        let buffer = Buffer::from_string(input.to_string(), language.clone());
+        assert_eq!(buffer.to_string().as_bytes(), input.as_bytes());
+
+        let input = "# comment\r\nx = 5\r\n";
+        let buffer = Buffer::from_string(input.to_string(), language);
+        assert_eq!(buffer.to_string().as_bytes(), input.as_bytes());
+    }
+
+    #[test]
+    fn test_replace_comments_reordered_subset() {
+        let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+
+        let mut buffer = Buffer::from_string(PYTHON_FIXTURE.to_string(), language);
+        buffer.get_comments();
+
+        // Reordered
+        let new_comments = vec![
+            Comment::new(5, "this is a fixed single line comment".to_string(), CommentType::Single),
+            Comment::new(2, "fixed multi-line comment".to_string(), CommentType::Multi),
+            Comment::new(1, "this is fixed".to_string(), CommentType::Multi),
+        ];
+        buffer.replace_comments(&new_comments).unwrap();
+
+        assert_eq!(buffer.lines[1], "this is fixed");
+        assert_eq!(buffer.lines[2], "fixed multi-line comment");
+        assert_eq!(buffer.lines[5], "# this is a fixed single line comment");
+
+        // Subset, the rest of the comments are untouched
+        let mut buffer = Buffer::from_string(PYTHON_FIXTURE.to_string(), buffer.language);
+        buffer.get_comments();
+
+        let new_comments = vec![Comment::new(
+            9,
+            "Another fixed comment".to_string(),
+            CommentType::Multi,
+        )];
+        buffer.replace_comments(&new_comments).unwrap();
+
+        assert_eq!(buffer.lines[1], "this is a");
+        assert_eq!(buffer.lines[5], "# this is a single line comment");
+        assert_eq!(buffer.lines[9], "\"\"\"Another fixed comment\"\"\"");
+    }
+
+    /// Parse the single line comment of a line
+    fn parse_single(language: &Language, line: &str) -> Comment {
+        Comment::parse_comment(language, &[line.to_string()], 0, CommentType::Single)
+            .unwrap()
+            .comments
+            .remove(0)
+    }
+
+    #[test]
+    fn test_replace_single_comment_after_duplicated_code() {
+        let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+
+        let line = r#"x = "hello world" # hello world"#;
+        let result = replace_single_comment(line, &parse_single(&language, line), "Hello, world!").unwrap();
+
+        assert_eq!(result, r#"x = "hello world" # Hello, world!"#);
+    }
+
+    #[test]
+    fn test_replace_inline_block_comment() {
+        let language = Language::from_name("c").unwrap();
+        let input = "int x = 5; /* initalize */ int y;\nint z; /*trailing coment*/\n";
+
+        let mut buffer = Buffer::from_string(input.to_string(), language);
+        let texts: Vec<String> = buffer.get_comments().iter().map(|c| c.text.clone()).collect();
+        assert_eq!(texts, vec!["initalize", "trailing coment"]);
+
+        buffer
+            .replace_comments(&[
+                Comment::new(0, "initialize".to_string(), CommentType::Multi),
+                Comment::new(1, "trailing comment".to_string(), CommentType::Multi),
+            ])
+            .unwrap();
+        assert_eq!(buffer.to_string(), "int x = 5; /* initialize */ int y;\nint z; /*trailing comment*/\n");
+    }
+
+    #[test]
+    fn test_replace_identical_open_and_close() {
+        let language = Language::from_name("python").unwrap();
+        let mut buffer = Buffer::from_string(r#""""x"""y"""z""""#.to_string(), language);
+        buffer.get_comments();
+
+        buffer
+            .replace_comments(&[Comment::new(0, "X".to_string(), CommentType::Multi)])
+            .unwrap();
+        assert_eq!(buffer.to_string(), r#""""X"""y"""z""""#);
+    }
+
+    #[test]
+    fn test_replace_single_comment_keeps_spacing() {
+        let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+
+        let cases = [
+            ("    x = 5  # coment", " comment", "    x = 5  # comment"),
+            ("    x = 5  #coment", "comment", "    x = 5  #comment"),
+            ("\tx = 5\t#   coment", "comment", "\tx = 5\t#   comment"),
+            ("# coment", "comment", "# comment"),
+        ];
+        for (line, new_comment, expected) in cases {
+            let result = replace_single_comment(line, &parse_single(&language, line), new_comment).unwrap();
+            assert_eq!(result, expected);
+        }
+
+        // Through the whole buffer, the extracted text has no spacing around it
+        let mut buffer = Buffer::from_string("def f():\n    x = 5  # coment\n".to_string(), language);
+        buffer.get_comments();
+        buffer
+            .replace_comments(&[Comment::new(1, "comment".to_string(), CommentType::Single)])
+            .unwrap();
+        assert_eq!(buffer.to_string(), "def f():\n    x = 5  # comment\n");
+    }
+
+    #[test]
+    fn test_unicode_comments() {
+        let language = Language::new("rust", "//", "/*", "*/");
+        let input = "let café = 1; // café ☕ es rico\n/* ñandú 🦤 */\n/*\n東京 🗼\n*/";
+
+        let mut buffer = Buffer::from_string(input.to_string(), language);
+        let comments = buffer.get_comments();
+
+        assert_eq!(comments.len(), 3);
+        assert_eq!(comments[0].text, "café ☕ es rico");
+        assert_eq!(comments[1].text, "ñandú 🦤");
+        assert_eq!(comments[2].text, "東京 🗼");
+
+        let new_comments = vec![
+            Comment::new(0, "café ☕ está rico".to_string(), CommentType::Single),
+            Comment::new(1, "ñandú 🦤 corre".to_string(), CommentType::Multi),
+            Comment::new(3, "東京タワー 🗼".to_string(), CommentType::Multi),
+        ];
+        buffer.replace_comments(&new_comments).unwrap();
+
+        assert_eq!(buffer.lines[0], "let café = 1; // café ☕ está rico");
+        assert_eq!(buffer.lines[1], "/* ñandú 🦤 corre */");
+        assert_eq!(buffer.lines[3], "東京タワー 🗼");
+    }
+
+    #[test]
+    fn test_json_to_comments_fenced() {
+        let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+        let mut buffer = Buffer::from_string("x = 5\n# a coment".to_string(), language);
+        buffer.get_comments();
+
+        let response = "Sure! Here is the corrected JSON:\n```json\n{\"single_comments\": {\"1\": \"a comment\"}, \"multiline_comments\": {}}\n```";
+        buffer.json_to_comments(response).unwrap();
+
+        assert_eq!(buffer.to_string(), "x = 5\n# a comment");
+    }
 }
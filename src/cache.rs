@@ -0,0 +1,87 @@
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// On-disk cache of the corrected comments, so unchanged comments are not sent again
+///
+/// Each entry is a file named after the hash of the comment text and the
+/// context of the check (language, model and prompt), containing the corrected text.
+pub struct Cache {
+    dir: PathBuf,
+    context: String,
+}
+
+impl Cache {
+    /// Create a new [`Cache`]
+    ///
+    /// # Params
+    /// * `dir`: Directory of the cache entries, created on the first write
+    /// * `language`: Language of the comments
+    /// * `model`: Model that checks the comments
+    /// * `prompt`: Instructions sent to the model, a change of prompt invalidates the entries
+    pub fn new(dir: PathBuf, language: &str, model: &str, prompt: &str) -> Self {
+        Self {
+            dir,
+            context: [language, model, prompt].join("\0"),
+        }
+    }
+
+    /// Path of the entry of a comment
+    fn entry(&self, text: &str) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(self.context.as_bytes());
+        hasher.update([0]);
+        hasher.update(text.as_bytes());
+
+        let hash: String = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        self.dir.join(hash)
+    }
+
+    /// Get the corrected text of a comment, `None` if it is not cached
+    pub fn get(&self, text: &str) -> Option<String> {
+        fs::read_to_string(self.entry(text)).ok()
+    }
+
+    /// Store the corrected text of a comment
+    pub fn insert(&self, text: &str, corrected: &str) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.entry(text), corrected)
+    }
+}
+
+/// Default directory of the cache, `$XDG_CACHE_HOME/neospeller` or `~/.cache/neospeller`
+pub fn default_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .map(|dir| dir.join("neospeller"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache() {
+        let dir = env::temp_dir().join(format!("neospeller-cache-unit-{}", std::process::id()));
+        let cache = Cache::new(dir.clone(), "python", "gpt-4o", "prompt");
+
+        assert_eq!(cache.get("A coment"), None);
+        cache.insert("A coment", "A comment").unwrap();
+        assert_eq!(cache.get("A coment").as_deref(), Some("A comment"));
+
+        // The entries depend on the context of the check
+        let other = Cache::new(dir.clone(), "python", "gpt-4o-mini", "prompt");
+        assert_eq!(other.get("A coment"), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Characters that are frequent in code and rare in prose
+const CODE_SYMBOLS: &[char] = &['(', ')', '{', '}', '[', ']', ';', '=', '<', '>', '+', '*', '/', '&', '|', '!'];
+
+/// Minimum share of [`CODE_SYMBOLS`] in the text of a comment that looks like code
+const SYMBOL_DENSITY: f64 = 0.2;
+
+/// Whether the text of a comment looks like code, e.g. commented-out code
+///
+/// It is a heuristic: a call, an assignment, a statement that starts with a keyword
+/// or a high density of symbols, always with balanced brackets. Prose that only
+/// mentions code, e.g. `Call foo() to reset`, is not detected as code.
+///
+/// # Params
+/// * `text`: Text of the comment, without the comment symbol
+///
+/// # Returns
+/// * `true` if the text should not be checked
+pub fn looks_like_code(text: &str) -> bool {
+    let text = text.trim();
+    if text.is_empty() || !balanced_brackets(text) {
+        return false;
+    }
+
+    statement_pattern().is_match(text) || symbol_density(text) >= SYMBOL_DENSITY
+}
+
+/// Pattern of the common statements of code
+fn statement_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(concat!(
+            // A call, e.g. `old_function(arg1, arg2)` or `self.log.debug("x");`
+            r"^[A-Za-z_][\w.:!]*\(.*\)[;,]?$",
+            // An assignment, e.g. `x = 5` or `self.count += 1`
+            r"|^[A-Za-z_][\w.\[\]]*\s*(?:[-+*/%|&^]|<<|>>)?=\s*[^=\s].*$",
+            // A statement that opens a block or ends with a semicolon, e.g. `def main():` or `return x;`
+            r"|^(?:def|fn|pub|let|const|var|return|class|struct|impl|function|if|elif|else|for|while|match)\b.*[:{;]$",
+            // An import, e.g. `import os`, `from typing import List` or `#include <stdio.h>`
+            r"|^(?:import\s+[\w.]+|from\s+[\w.]+\s+import\s+[\w., *]+|#include\s+[<\x22].+[>\x22]|use\s+[\w:{}, *]+;)$",
+        ))
+        .unwrap()
+    })
+}
+
+/// Share of [`CODE_SYMBOLS`] in the non-whitespace characters of a text
+fn symbol_density(text: &str) -> f64 {
+    let (symbols, total) = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .fold((0, 0), |(symbols, total), c| (symbols + usize::from(CODE_SYMBOLS.contains(&c)), total + 1));
+
+    if total == 0 {
+        return 0.0;
+    }
+    symbols as f64 / total as f64
+}
+
+/// Whether the brackets of a text are balanced and properly nested
+fn balanced_brackets(text: &str) -> bool {
+    let mut stack = Vec::new();
+
+    for c in text.chars() {
+        match c {
+            '(' | '[' | '{' => stack.push(c),
+            ')' | ']' | '}' => {
+                let open = match c {
+                    ')' => '(',
+                    ']' => '[',
+                    _ => '{',
+                };
+                if stack.pop() != Some(open) {
+                    return false;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    stack.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_looks_like_code() {
+        let code = [
+            r#"print(f"profile_list[{position}]: {profiles_list[position]}")"#,
+            "old_function(arg1, arg2)",
+            "self.count += 1",
+            "x = compute(5)",
+            "def main():",
+            "return value;",
+            "from typing import List",
+            "#include <stdio.h>",
+            "use std::fs;",
+            "if (a && b) { c(); }",
+        ];
+        for text in code {
+            assert!(looks_like_code(text), "{}", text);
+        }
+
+        let prose = [
+            "Print debug information to compare with the visual content in the browser and verify the order.",
+            "Profiles online should be in the positions: [7, 57] and [3, 15, 17] according to the get_profiles_display_group_settings function.",
+            "If you change the initial online IDs, another filter may capture them first. Check if this occurs.",
+            "Call foo() to reset the counter",
+            "Returns the number of items (zero if empty)",
+            "For each item:",
+            "A smiley :)",
+            "",
+        ];
+        for text in prose {
+            assert!(!looks_like_code(text), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_balanced_brackets() {
+        assert!(balanced_brackets("a(b[c]{d})"));
+        assert!(!balanced_brackets("a(b]"));
+        assert!(!balanced_brackets("(a"));
+    }
+}
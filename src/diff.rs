@@ -0,0 +1,334 @@
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::language::{Comment, CommentType};
+
+/// Build a unified diff between the original and the corrected text
+///
+/// # Params
+/// * `original`: Text before the corrections
+/// * `corrected`: Text after the corrections
+///
+/// # Returns
+/// * The unified diff with line numbers in the hunk headers, empty if nothing changed
+pub fn unified_diff(original: &str, corrected: &str) -> String {
+    TextDiff::from_lines(original, corrected)
+        .unified_diff()
+        .context_radius(1)
+        .header("original", "corrected")
+        .to_string()
+}
+
+/// ANSI escape codes of the colored diff
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const REVERSE: &str = "\x1b[7m";
+const RESET: &str = "\x1b[0m";
+
+/// Build a unified diff colored with ANSI codes, like `git diff`
+///
+/// Removed lines are red and added lines are green, the words that changed
+/// within a line are highlighted.
+///
+/// # Params
+/// * `original`: Text before the corrections
+/// * `corrected`: Text after the corrections
+///
+/// # Returns
+/// * The colored unified diff, empty if nothing changed
+pub fn colored_unified_diff(original: &str, corrected: &str) -> String {
+    let diff = TextDiff::from_lines(original, corrected);
+    let mut output = String::new();
+
+    for (i, hunk) in diff.unified_diff().context_radius(1).iter_hunks().enumerate() {
+        if i == 0 {
+            output.push_str(&format!("{}--- original\n+++ corrected{}\n", BOLD, RESET));
+        }
+        output.push_str(&format!("{}{}{}\n", CYAN, hunk.header(), RESET));
+
+        for op in hunk.ops() {
+            for change in diff.iter_inline_changes(op) {
+                let (sign, color) = match change.tag() {
+                    ChangeTag::Delete => ("-", RED),
+                    ChangeTag::Insert => ("+", GREEN),
+                    ChangeTag::Equal => (" ", ""),
+                };
+
+                output.push_str(color);
+                output.push_str(sign);
+                for (emphasized, value) in change.iter_strings_lossy() {
+                    let value = value.trim_end_matches('\n');
+                    if emphasized {
+                        output.push_str(&format!("{}{}{}{}", REVERSE, value, RESET, color));
+                    } else {
+                        output.push_str(value);
+                    }
+                }
+                if !color.is_empty() {
+                    output.push_str(RESET);
+                }
+                output.push('\n');
+            }
+        }
+    }
+
+    output
+}
+
+/// Build a unified diff, colored or plain
+pub fn render_diff(original: &str, corrected: &str, color: bool) -> String {
+    if color {
+        colored_unified_diff(original, corrected)
+    } else {
+        unified_diff(original, corrected)
+    }
+}
+
+/// Count the lines that differ between the original and the corrected text
+///
+/// # Returns
+/// * The number of original lines that have been corrected
+pub fn changed_lines(original: &str, corrected: &str) -> usize {
+    TextDiff::from_lines(original, corrected)
+        .iter_all_changes()
+        .filter(|change| change.tag() == ChangeTag::Delete)
+        .count()
+}
+
+/// A comment changed by the corrections
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CommentDiff {
+    pub line: usize,
+    pub comment_type: CommentType,
+    pub before: String,
+    pub after: String,
+}
+
+/// A comment changed by the corrections, as a record of the JSON Lines output
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DiffRecord {
+    /// Path of the file, `None` for stdin
+    pub file: Option<String>,
+    pub line: usize,
+    pub before: String,
+    pub after: String,
+}
+
+impl DiffRecord {
+    /// Build the records of the changed comments of a file
+    pub fn from_diffs(file: Option<&str>, diffs: Vec<CommentDiff>) -> Vec<DiffRecord> {
+        diffs
+            .into_iter()
+            .map(|diff| DiffRecord {
+                file: file.map(str::to_string),
+                line: diff.line,
+                before: diff.before,
+                after: diff.after,
+            })
+            .collect()
+    }
+}
+
+/// Compare the original comments with the corrected ones
+///
+/// # Params
+/// * `original`: Comments before the corrections
+/// * `corrected`: Comments after the corrections
+///
+/// # Returns
+/// * A [`CommentDiff`] for each comment that actually changed, ordered by line number
+pub fn diff_comments(original: &[Comment], corrected: &[Comment]) -> Vec<CommentDiff> {
+    let original: HashMap<(usize, CommentType), &str> = original
+        .iter()
+        .map(|c| ((c.line, c.comment_type), c.text.as_str()))
+        .collect();
+
+    let mut diffs: Vec<CommentDiff> = corrected
+        .iter()
+        .filter_map(|c| {
+            let before = original.get(&(c.line, c.comment_type))?;
+            (*before != c.text).then(|| CommentDiff {
+                line: c.line,
+                comment_type: c.comment_type,
+                before: before.to_string(),
+                after: c.text.clone(),
+            })
+        })
+        .collect();
+
+    diffs.sort_by_key(|d| d.line);
+    diffs
+}
+
+/// A change of the text, to apply the corrections in an editor without replacing the whole buffer
+///
+/// The lines are zero based and the columns are byte offsets in the line, the end is exclusive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TextEdit {
+    pub start_line: usize,
+    pub start_col: usize,
+    pub end_line: usize,
+    pub end_col: usize,
+    pub new_text: String,
+}
+
+/// Build the edits that turn the original text into the corrected one
+///
+/// Each corrected line gets an edit from the column of its first comment, so
+/// the code before the comment is never touched.
+///
+/// # Params
+/// * `original`: Text before the corrections
+/// * `corrected`: Text after the corrections, with the same lines as the original
+/// * `comments`: Comments parsed from the original text, with their columns
+///
+/// # Returns
+/// * A [`TextEdit`] for each line that changed, ordered by line number
+pub fn text_edits(original: &str, corrected: &str, comments: &[Comment]) -> Vec<TextEdit> {
+    // Span of the comments of each line, from the first column to the end of the last text
+    let mut spans: BTreeMap<usize, (usize, usize)> = BTreeMap::new();
+    for comment in comments {
+        let end = comment.col + comment.text.len();
+        spans
+            .entry(comment.line)
+            .and_modify(|(start, last)| {
+                *start = (*start).min(comment.col);
+                *last = (*last).max(end);
+            })
+            .or_insert((comment.col, end));
+    }
+
+    let original_lines: Vec<&str> = original.lines().collect();
+    let corrected_lines: Vec<&str> = corrected.lines().collect();
+
+    spans
+        .into_iter()
+        .filter_map(|(line, (start, end))| {
+            let (old, new) = (original_lines.get(line)?, corrected_lines.get(line)?);
+            if old == new || !new.is_char_boundary(start) {
+                return None;
+            }
+
+            // Keep what follows the comments when the correction didn't change it, e.g. a close symbol
+            let rest = old.get(end..).unwrap_or_default();
+            let (end_col, new_text) = match new.strip_suffix(rest) {
+                Some(new_text) if new_text.len() >= start => (end, &new_text[start..]),
+                _ => (old.len(), &new[start..]),
+            };
+
+            Some(TextEdit {
+                start_line: line,
+                start_col: start,
+                end_line: line,
+                end_col,
+                new_text: new_text.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let original = "x = 5\n# a coment\ny = 6\n";
+        let corrected = "x = 5\n# a comment\ny = 6\n";
+
+        let diff = unified_diff(original, corrected);
+        assert!(diff.contains("@@ -1,3 +1,3 @@"));
+        assert!(diff.contains("-# a coment\n"));
+        assert!(diff.contains("+# a comment\n"));
+
+        assert!(unified_diff(original, original).is_empty());
+    }
+
+    #[test]
+    fn test_colored_unified_diff() {
+        let original = "x = 5\n# a coment\ny = 6\n";
+        let corrected = "x = 5\n# a comment\ny = 6\n";
+
+        let plain = render_diff(original, corrected, false);
+        assert!(!plain.contains('\x1b'));
+
+        let colored = render_diff(original, corrected, true);
+        assert!(colored.contains(&format!("{}-# a {}coment{}", RED, REVERSE, RESET)));
+        assert!(colored.contains(&format!("{}+# a {}comment{}", GREEN, REVERSE, RESET)));
+        assert!(colored.contains("@@ -1,3 +1,3 @@"));
+
+        assert!(colored_unified_diff(original, original).is_empty());
+    }
+
+    #[test]
+    fn test_changed_lines() {
+        let original = "# a coment\nx = 5\n# anothr coment\n# fine\n";
+        let corrected = "# a comment\nx = 5\n# another comment\n# fine\n";
+
+        assert_eq!(changed_lines(original, corrected), 2);
+        assert_eq!(changed_lines(original, original), 0);
+    }
+
+    #[test]
+    fn test_diff_comments() {
+        let original = vec![
+            Comment::new(0, "a coment".to_string(), CommentType::Single),
+            Comment::new(2, "Already correct".to_string(), CommentType::Single),
+            Comment::new(4, "docstrng".to_string(), CommentType::Multi),
+        ];
+        let corrected = vec![
+            Comment::new(4, "docstring".to_string(), CommentType::Multi),
+            Comment::new(0, "a comment".to_string(), CommentType::Single),
+            Comment::new(2, "Already correct".to_string(), CommentType::Single),
+        ];
+
+        let diffs = diff_comments(&original, &corrected);
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].line, 0);
+        assert_eq!(diffs[0].before, "a coment");
+        assert_eq!(diffs[0].after, "a comment");
+        assert_eq!(diffs[1].line, 4);
+        assert_eq!(diffs[1].comment_type, CommentType::Multi);
+
+        let json = serde_json::to_string(&diffs[1]).unwrap();
+        assert_eq!(
+            json,
+            r#"{"line":4,"comment_type":"multiline_comments","before":"docstrng","after":"docstring"}"#
+        );
+    }
+
+    #[test]
+    fn test_text_edits() {
+        let original = "x = 5  # a coment  \nint y; /* init */ int z;\nunchanged  # ok\n";
+        let corrected = "x = 5  # a comment\nint y; /* Init */ int z;\nunchanged  # ok\n";
+        let comments = vec![
+            Comment::new(0, "a coment".to_string(), CommentType::Single).with_col(9),
+            Comment::new(1, "init".to_string(), CommentType::Multi).with_col(10),
+            Comment::new(2, "ok".to_string(), CommentType::Single).with_col(13),
+        ];
+
+        let edits = text_edits(original, corrected, &comments);
+        assert_eq!(
+            edits,
+            vec![
+                TextEdit {
+                    start_line: 0,
+                    start_col: 9,
+                    end_line: 0,
+                    end_col: 19,
+                    new_text: "a comment".to_string(),
+                },
+                TextEdit {
+                    start_line: 1,
+                    start_col: 10,
+                    end_line: 1,
+                    end_col: 14,
+                    new_text: "Init".to_string(),
+                },
+            ]
+        );
+    }
+}
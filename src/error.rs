@@ -0,0 +1,79 @@
+use std::fmt::Display;
+
+use crate::language::UnsupportedLanguageError;
+
+/// Error returned by the spell checker
+#[derive(Debug)]
+pub enum NeospellerError {
+    /// The request to the grammar backend failed
+    #[cfg(feature = "grammar")]
+    Network(reqwest::Error),
+    /// The grammar backend answered with an unexpected response
+    ApiResponse(String),
+    /// A JSON couldn't be serialized or parsed
+    JsonParse(serde_json::Error),
+    /// The language is not supported
+    UnsupportedLanguage(UnsupportedLanguageError),
+    /// A corrected comment couldn't be replaced in the source code
+    CommentReplace(String),
+    /// A file couldn't be read or written
+    Io(std::io::Error),
+    /// Invalid or missing configuration, e.g. an API key that is not set
+    Config(String),
+    /// A multi-line comment opens in this line, zero-based, and is never closed
+    UnterminatedComment(usize),
+}
+
+impl Display for NeospellerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            #[cfg(feature = "grammar")]
+            NeospellerError::Network(err) => write!(f, "Network error: {}", err),
+            NeospellerError::ApiResponse(message) => write!(f, "Unexpected API response: {}", message),
+            NeospellerError::JsonParse(err) => write!(f, "Error parsing json string: {}", err),
+            NeospellerError::UnsupportedLanguage(err) => write!(f, "{}", err),
+            NeospellerError::CommentReplace(message) => write!(f, "Cannot replace the comment: {}", message),
+            NeospellerError::Io(err) => write!(f, "{}", err),
+            NeospellerError::Config(message) => write!(f, "{}", message),
+            NeospellerError::UnterminatedComment(line) => write!(f, "Unterminated comment in line {}", line + 1),
+        }
+    }
+}
+
+impl std::error::Error for NeospellerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "grammar")]
+            NeospellerError::Network(err) => Some(err),
+            NeospellerError::JsonParse(err) => Some(err),
+            NeospellerError::UnsupportedLanguage(err) => Some(err),
+            NeospellerError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "grammar")]
+impl From<reqwest::Error> for NeospellerError {
+    fn from(err: reqwest::Error) -> Self {
+        NeospellerError::Network(err)
+    }
+}
+
+impl From<serde_json::Error> for NeospellerError {
+    fn from(err: serde_json::Error) -> Self {
+        NeospellerError::JsonParse(err)
+    }
+}
+
+impl From<UnsupportedLanguageError> for NeospellerError {
+    fn from(err: UnsupportedLanguageError) -> Self {
+        NeospellerError::UnsupportedLanguage(err)
+    }
+}
+
+impl From<std::io::Error> for NeospellerError {
+    fn from(err: std::io::Error) -> Self {
+        NeospellerError::Io(err)
+    }
+}
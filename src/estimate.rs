@@ -0,0 +1,184 @@
+use std::fmt::Display;
+
+use crate::buffer::Buffer;
+use crate::error::NeospellerError;
+use crate::grammar::{GrammarConfig, ProviderKind};
+use crate::language::{CommentCollection, Language};
+
+/// Average number of characters of a token, a rough heuristic for English text and JSON
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Approximate price in USD per million tokens of the known models, as (input, output)
+const PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-5.4-nano", 0.05, 0.40),
+    ("gpt-5.4-mini", 0.25, 2.00),
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4.1", 2.00, 8.00),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1-nano", 0.10, 0.40),
+    ("claude-haiku-4-5", 1.00, 5.00),
+    ("claude-sonnet-4-5", 3.00, 15.00),
+    ("claude-opus-4-1", 15.00, 75.00),
+];
+
+/// Estimate the number of tokens of a text
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Price per million tokens of a model, as (input, output)
+///
+/// # Returns
+/// * The price, zero for local providers, or `None` if the model is unknown
+pub fn price_per_million(config: &GrammarConfig) -> Option<(f64, f64)> {
+    if matches!(config.provider, ProviderKind::Ollama | ProviderKind::Offline) {
+        return Some((0.0, 0.0));
+    }
+
+    let model = config.model_name();
+    PRICES
+        .iter()
+        .find(|(name, _, _)| *name == model)
+        .map(|(_, input, output)| (*input, *output))
+}
+
+/// Estimated tokens and cost of a spell check
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    /// Model that would check the comments
+    pub model: String,
+    /// Number of requests sent to the model
+    pub requests: usize,
+    /// Tokens sent, the system prompt plus the comments of each request
+    pub input_tokens: usize,
+    /// Tokens of the responses, about the size of the sent comments
+    pub output_tokens: usize,
+    /// Price per million tokens, as (input, output), `None` if the model is unknown
+    pub price: Option<(f64, f64)>,
+}
+
+impl Estimate {
+    /// Estimated cost in USD, `None` if the price of the model is unknown
+    pub fn cost(&self) -> Option<f64> {
+        self.price.map(|(input, output)| {
+            (self.input_tokens as f64 * input + self.output_tokens as f64 * output) / 1_000_000.0
+        })
+    }
+
+    /// Add the estimate of another check, e.g. of another file
+    pub fn add(&mut self, other: &Estimate) {
+        self.requests += other.requests;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+    }
+}
+
+impl Display for Estimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "~{} input and ~{} output tokens in {} request(s) to {}",
+            self.input_tokens, self.output_tokens, self.requests, self.model
+        )?;
+
+        match (self.price, self.cost()) {
+            (Some((input, output)), Some(cost)) => write!(
+                f,
+                ", ${:.2}/${:.2} per million tokens, ~${:.4}",
+                input, output, cost
+            ),
+            _ => write!(f, ", unknown price"),
+        }
+    }
+}
+
+/// Estimate the tokens and cost of checking a source code, without calling the model
+///
+/// # Params
+/// * `input`: The source code to check
+/// * `language`: The programming language of the source code
+/// * `config`: The [`GrammarConfig`] of the grammar check
+///
+/// # Returns
+/// * The [`Estimate`] or an error if the prompt cannot be built
+pub fn estimate(input: &str, language: &Language, config: &GrammarConfig) -> Result<Estimate, NeospellerError> {
+    let mut buffer = Buffer::from_string(input.to_string(), language.clone());
+    buffer.get_comments();
+    let comments = buffer
+        .comments
+        .into_iter()
+        .filter(|comment| !config.is_skipped(comment.line, &comment.text))
+        .collect();
+    let collection = CommentCollection::from_comments(comments);
+
+    let prompt_tokens = estimate_tokens(&config.system_prompt(&language.name)?);
+
+    let mut estimate = Estimate {
+        model: config.model_name().to_string(),
+        requests: 0,
+        input_tokens: 0,
+        output_tokens: 0,
+        price: price_per_million(config),
+    };
+
+    for chunk in collection.chunks(config.chunk_size) {
+        let comment_tokens = estimate_tokens(&serde_json::to_string(&chunk)?);
+
+        estimate.requests += 1;
+        estimate.input_tokens += prompt_tokens + comment_tokens;
+        estimate.output_tokens += comment_tokens;
+    }
+
+    Ok(estimate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+    }
+
+    #[test]
+    fn test_price_per_million() {
+        let config = GrammarConfig {
+            model: Some("gpt-4o".to_string()),
+            ..GrammarConfig::default()
+        };
+        assert_eq!(price_per_million(&config), Some((2.50, 10.00)));
+
+        let config = GrammarConfig {
+            model: Some("my-fine-tune".to_string()),
+            ..GrammarConfig::default()
+        };
+        assert_eq!(price_per_million(&config), None);
+
+        let config = GrammarConfig {
+            provider: ProviderKind::Ollama,
+            ..GrammarConfig::default()
+        };
+        assert_eq!(price_per_million(&config), Some((0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_estimate_cost() {
+        let estimate = Estimate {
+            model: "gpt-4o".to_string(),
+            requests: 1,
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            price: Some((2.50, 10.00)),
+        };
+
+        assert_eq!(estimate.cost(), Some(3.5));
+        assert_eq!(
+            estimate.to_string(),
+            "~1000000 input and ~100000 output tokens in 1 request(s) to gpt-4o, $2.50/$10.00 per million tokens, ~$3.5000"
+        );
+    }
+}
@@ -0,0 +1,379 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::error::NeospellerError;
+use crate::glob::Glob;
+use crate::grammar::{GrammarConfig, GrammarProvider};
+use crate::lang_config::{self, LanguageDefinition};
+use crate::language::Language;
+use crate::{check_spelling_report, SpellingReport};
+
+/// Result of the spell check of a file
+pub struct FileReport {
+    pub path: PathBuf,
+    /// Language of the file
+    pub language: Language,
+    /// Original content of the file
+    pub input: String,
+    pub report: SpellingReport,
+}
+
+impl FileReport {
+    /// Whether the corrections changed the file
+    pub fn changed(&self) -> bool {
+        self.input != self.report.output
+    }
+}
+
+/// Default number of files checked concurrently
+pub const DEFAULT_JOBS: usize = 4;
+
+/// Directories that are never walked, they hold dependencies or build artifacts
+const IGNORED_DIRS: &[&str] = &[".git", "target", "node_modules", "dist", ".venv", "__pycache__"];
+
+/// File listing the paths that are never checked, read from the working directory
+pub const IGNORE_FILE: &str = ".neospellerignore";
+
+/// Read the patterns of an ignore file, e.g. [`IGNORE_FILE`]
+///
+/// One [`Glob`] per line, relative to the directory of the file, blank lines
+/// and lines starting with `#` are skipped.
+///
+/// # Returns
+/// * The patterns or an error message if the file cannot be read or a pattern is invalid
+pub fn read_ignore_file(path: &Path) -> Result<Vec<Glob>, String> {
+    let content = fs::read_to_string(path).map_err(|err| format!("Cannot read {}: {}", path.display(), err))?;
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let base = std::path::absolute(dir)
+        .map_err(|err| format!("Cannot read {}: {}", path.display(), err))?;
+
+    let mut globs = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('!') {
+            return Err(format!("{}:{}: negated patterns are not supported", path.display(), i + 1));
+        }
+
+        let glob = Glob::new(line).map_err(|err| format!("{}:{}: {}", path.display(), i + 1, err))?;
+        globs.push(glob.with_base(base.clone()));
+    }
+
+    Ok(globs)
+}
+
+/// Expand the paths of the arguments into the list of files to process
+///
+/// Files are kept as they are, directories are walked recursively skipping the
+/// [`IGNORED_DIRS`] and the excluded paths, picking the files with an extension
+/// of a supported language.
+///
+/// # Params
+/// * `paths`: Files and directories passed by the user
+/// * `language`: Language selected by the user, only its files are picked from directories
+/// * `custom`: Custom languages of `--lang-config`, their extensions are picked too
+/// * `exclude`: Patterns of the paths skipped in the directories, relative to each directory
+///
+/// # Returns
+/// * The files to process, the ones of each directory sorted by path
+pub fn collect_files(
+    paths: &[PathBuf],
+    language: Option<&Language>,
+    custom: &[LanguageDefinition],
+    exclude: &[Glob],
+) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        if path.is_dir() {
+            walk_dir(path, path, language, custom, exclude, &mut files)?;
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    Ok(files)
+}
+
+/// Collect the files of a directory recursively, `root` is the directory passed by the user
+fn walk_dir(
+    root: &Path,
+    dir: &Path,
+    language: Option<&Language>,
+    custom: &[LanguageDefinition],
+    exclude: &[Glob],
+    files: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for path in entries {
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        let is_dir = path.is_dir();
+
+        if is_excluded(root, &path, is_dir, exclude) {
+            continue;
+        }
+
+        if is_dir {
+            if !IGNORED_DIRS.contains(&name) {
+                walk_dir(root, &path, language, custom, exclude, files)?;
+            }
+            continue;
+        }
+
+        let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+        let matches = match (lang_config::language_from_extension(ext, custom), language) {
+            (Some(found), Some(language)) => found.name == language.name,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+
+        if matches {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a path of a walked directory matches some excluded pattern
+fn is_excluded(root: &Path, path: &Path, is_dir: bool, exclude: &[Glob]) -> bool {
+    exclude.iter().any(|glob| match glob.base() {
+        Some(base) => std::path::absolute(path)
+            .is_ok_and(|path| path.strip_prefix(base).is_ok_and(|relative| glob.matches(relative, is_dir))),
+        None => glob.matches(path.strip_prefix(root).unwrap_or(path), is_dir),
+    })
+}
+
+/// Get the language of a file, the provided language wins over the extension
+///
+/// # Params
+/// * `path`: Path of the file
+/// * `language`: Language selected by the user, if any
+/// * `custom`: Custom languages of `--lang-config`, they win over the built-in ones
+///
+/// # Returns
+/// * The [`Language`] or an error if it cannot be inferred from the extension
+pub fn language_for_path(
+    path: &Path,
+    language: Option<&Language>,
+    custom: &[LanguageDefinition],
+) -> Result<Language, String> {
+    if let Some(language) = language {
+        return Ok(language.clone());
+    }
+
+    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    lang_config::language_from_extension(ext, custom).ok_or_else(|| format!("Cannot infer the language of {}", path.display()))
+}
+
+/// Spell check a file without modifying it
+///
+/// # Params
+/// * `path`: Path of the file
+/// * `language`: Language selected by the user, inferred from the extension when `None`
+/// * `provider`: The [`GrammarProvider`] that checks the comments
+/// * `config`: The [`GrammarConfig`] of the grammar check
+///
+/// # Returns
+/// * A [`FileReport`] with the original and the corrected content
+pub fn check_file(
+    path: &Path,
+    language: Option<&Language>,
+    provider: &dyn GrammarProvider,
+    config: &GrammarConfig,
+) -> Result<FileReport, NeospellerError> {
+    let language = language_for_path(path, language, &config.languages).map_err(NeospellerError::Config)?;
+    let input = fs::read_to_string(path)?;
+    let report = check_spelling_report(input.clone(), language.clone(), provider, config)?;
+
+    Ok(FileReport {
+        path: path.to_path_buf(),
+        language,
+        input,
+        report,
+    })
+}
+
+/// Spell check several files concurrently, without modifying them
+///
+/// # Params
+/// * `paths`: Paths of the files
+/// * `language`: Language selected by the user, inferred from the extension when `None`
+/// * `provider`: The [`GrammarProvider`] that checks the comments
+/// * `config`: The [`GrammarConfig`] of the grammar check
+/// * `jobs`: Maximum number of files checked at the same time
+///
+/// # Returns
+/// * The result of each file, in the same order as `paths`
+pub fn check_files(
+    paths: &[PathBuf],
+    language: Option<&Language>,
+    provider: &(dyn GrammarProvider + Sync),
+    config: &GrammarConfig,
+    jobs: usize,
+) -> Vec<Result<FileReport, NeospellerError>> {
+    let mut results = Vec::new();
+    check_files_streaming(paths, language, provider, config, jobs, |index, result| {
+        results.push((index, result))
+    });
+
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Spell check several files concurrently, handing over each result as soon as it is ready
+///
+/// The grammar calls are blocking, so each worker thread waits on its own
+/// request while the others keep going. A failure only affects its own file.
+///
+/// # Params
+/// * `paths`: Paths of the files
+/// * `language`: Language selected by the user, inferred from the extension when `None`
+/// * `provider`: The [`GrammarProvider`] that checks the comments
+/// * `config`: The [`GrammarConfig`] of the grammar check
+/// * `jobs`: Maximum number of files checked at the same time
+/// * `on_result`: Receives the index of the path and its result, in completion order,
+///   one call at a time
+pub fn check_files_streaming<F>(
+    paths: &[PathBuf],
+    language: Option<&Language>,
+    provider: &(dyn GrammarProvider + Sync),
+    config: &GrammarConfig,
+    jobs: usize,
+    on_result: F,
+) where
+    F: FnMut(usize, Result<FileReport, NeospellerError>) + Send,
+{
+    let next = AtomicUsize::new(0);
+    let on_result = Mutex::new(on_result);
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, paths.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(index) else {
+                    break;
+                };
+
+                let result = check_file(path, language, provider, config);
+                (on_result.lock().unwrap_or_else(|e| e.into_inner()))(index, result);
+            });
+        }
+    });
+}
+
+/// Write the corrections of a file back in place, only if something changed
+///
+/// The content is written to a temporary file in the same directory and then
+/// renamed over the original, so a failure never leaves a truncated file.
+pub fn write_file(file: &FileReport) -> io::Result<()> {
+    if !file.changed() {
+        return Ok(());
+    }
+
+    write_atomically(&file.path, &file.report.output)
+}
+
+/// Write a file through a temporary file and a rename, keeping the permissions
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid file name"))?;
+
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(".neospeller.tmp");
+    let tmp = path.with_file_name(tmp_name);
+
+    let result = fs::write(&tmp, contents)
+        .and_then(|_| fs::set_permissions(&tmp, fs::metadata(path)?.permissions()))
+        .and_then(|_| fs::rename(&tmp, path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_for_path() {
+        let rust = Language::from_name("rust").unwrap();
+
+        assert_eq!(language_for_path(Path::new("src/main.py"), None, &[]).unwrap().name, "python");
+        assert_eq!(language_for_path(Path::new("src/main.py"), Some(&rust), &[]).unwrap().name, "rust");
+        assert!(language_for_path(Path::new("Makefile"), None, &[]).is_err());
+    }
+
+    #[test]
+    fn test_collect_files() {
+        let dir = std::env::temp_dir().join(format!("neospeller-collect-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("target")).unwrap();
+        fs::write(dir.join("src/main.rs"), "").unwrap();
+        fs::write(dir.join("src/script.py"), "").unwrap();
+        fs::write(dir.join("src/notes.unknown"), "").unwrap();
+        fs::write(dir.join("target/build.rs"), "").unwrap();
+
+        let rust = Language::from_name("rust").unwrap();
+        let files = collect_files(std::slice::from_ref(&dir), Some(&rust), &[], &[]).unwrap();
+        assert_eq!(files, vec![dir.join("src/main.rs")]);
+
+        let files = collect_files(std::slice::from_ref(&dir), None, &[], &[]).unwrap();
+        assert_eq!(files, vec![dir.join("src/main.rs"), dir.join("src/script.py")]);
+
+        let exclude = [Glob::new("*.py").unwrap()];
+        let files = collect_files(std::slice::from_ref(&dir), None, &[], &exclude).unwrap();
+        assert_eq!(files, vec![dir.join("src/main.rs")]);
+
+        // The extensions of the custom languages are picked too
+        let custom: Vec<LanguageDefinition> =
+            serde_json::from_str(r##"[{"name": "notes", "extensions": ["unknown"], "comment_symbols": ["#"]}]"##).unwrap();
+        let files = collect_files(std::slice::from_ref(&dir), None, &custom, &[]).unwrap();
+        assert_eq!(
+            files,
+            vec![dir.join("src/main.rs"), dir.join("src/notes.unknown"), dir.join("src/script.py")]
+        );
+
+        // The patterns of an ignore file are relative to its directory, not to the walked one
+        fs::write(dir.join(IGNORE_FILE), "# Generated\nsrc/main.rs\n\n").unwrap();
+        let exclude = read_ignore_file(&dir.join(IGNORE_FILE)).unwrap();
+        let files = collect_files(&[dir.join("src")], None, &[], &exclude).unwrap();
+        assert_eq!(files, vec![dir.join("src/script.py")]);
+
+        fs::write(dir.join(IGNORE_FILE), "*.py\n!keep.py\n").unwrap();
+        assert_eq!(
+            read_ignore_file(&dir.join(IGNORE_FILE)).unwrap_err(),
+            format!("{}:2: negated patterns are not supported", dir.join(IGNORE_FILE).display())
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_atomically() {
+        let path = std::env::temp_dir().join(format!("neospeller-write-{}.py", std::process::id()));
+        fs::write(&path, "# a coment\n").unwrap();
+
+        write_atomically(&path, "# a comment\n").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# a comment\n");
+        let tmp = path.with_file_name(format!(".neospeller-write-{}.py.neospeller.tmp", std::process::id()));
+        assert!(!tmp.exists());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
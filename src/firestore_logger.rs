@@ -75,7 +75,8 @@ fn append_to_file(entry: &SpellcheckLog) -> std::io::Result<()> {
         .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "HOME not set"))?;
     let line = serde_json::to_string(entry)?;
     let mut file = OpenOptions::new().append(true).create(true).open(&path)?;
-    writeln!(file, "{}", line)?;
+    // A single write, so concurrent checks never interleave their lines
+    file.write_all(format!("{}\n", line).as_bytes())?;
     Ok(())
 }
 
@@ -0,0 +1,165 @@
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+/// Glob pattern of the paths skipped when walking a directory, e.g. `*.min.js` or `vendor/`
+///
+/// The syntax is the one of `.gitignore`:
+/// * `*` matches anything but `/`, `?` a single character and `[abc]` a set of characters
+/// * `**` matches any number of directories, e.g. `tests/**/fixtures`
+/// * A pattern without `/` matches the name at any depth, e.g. `generated` or `*.pb.rs`
+/// * A pattern with `/` is relative to the walked directory, e.g. `src/generated.rs`
+/// * A trailing `/` only matches directories, e.g. `dist/`
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: String,
+    regex: Regex,
+    dir_only: bool,
+    /// Directory the paths are relative to, the walked one when `None`
+    base: Option<PathBuf>,
+}
+
+impl Glob {
+    /// Compile a glob pattern
+    ///
+    /// # Returns
+    /// * The [`Glob`] or an error message if the pattern is empty or invalid, e.g. `[abc`
+    pub fn new(pattern: &str) -> Result<Glob, String> {
+        let invalid = |reason: &str| format!("Invalid exclude pattern: {}, {}", pattern, reason);
+
+        let trimmed = pattern.trim();
+        let dir_only = trimmed.ends_with('/');
+        let trimmed = trimmed.trim_end_matches('/');
+        if trimmed.is_empty() {
+            return Err(invalid("it is empty"));
+        }
+
+        // Without `/` the name is matched at any depth, with it the path is relative to the root
+        let (anchored, body) = match trimmed.strip_prefix('/') {
+            Some(body) => (true, body),
+            None => (trimmed.contains('/'), trimmed),
+        };
+
+        let mut regex = String::from("^");
+        if !anchored {
+            regex.push_str("(?:.*/)?");
+        }
+        regex.push_str(&glob_to_regex(body).ok_or_else(|| invalid("a `[` is never closed"))?);
+        regex.push('$');
+
+        Ok(Glob {
+            pattern: pattern.to_string(),
+            regex: Regex::new(&regex).map_err(|err| invalid(&err.to_string()))?,
+            dir_only,
+            base: None,
+        })
+    }
+
+    /// Match the paths relative to a directory instead of the walked one,
+    /// e.g. the directory of an ignore file
+    pub fn with_base(mut self, base: PathBuf) -> Glob {
+        self.base = Some(base);
+        self
+    }
+
+    /// Directory the paths are relative to, if it is not the walked one
+    pub fn base(&self) -> Option<&Path> {
+        self.base.as_deref()
+    }
+
+    /// The pattern as it was written
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether the glob matches a path
+    ///
+    /// # Params
+    /// * `path`: Path relative to the walked directory
+    /// * `is_dir`: Whether the path is a directory
+    pub fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        let path: Vec<_> = path.components().map(|c| c.as_os_str().to_string_lossy()).collect();
+        self.regex.is_match(&path.join("/"))
+    }
+}
+
+/// Translate the body of a glob to a regex, `None` if a `[` is never closed
+fn glob_to_regex(glob: &str) -> Option<String> {
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` is zero or more directories, a final `**` is everything below
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                regex.push('[');
+                if chars.peek() == Some(&'!') {
+                    chars.next();
+                    regex.push('^');
+                }
+                loop {
+                    match chars.next()? {
+                        ']' => break,
+                        '\\' => regex.push_str("\\\\"),
+                        c => regex.push(c),
+                    }
+                }
+                regex.push(']');
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    Some(regex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        let glob = |pattern: &str| Glob::new(pattern).unwrap();
+
+        assert!(glob("*.min.js").matches(Path::new("static/app.min.js"), false));
+        assert!(!glob("*.min.js").matches(Path::new("static/app.js"), false));
+        assert!(glob("fixtures").matches(Path::new("tests/fixtures"), true));
+        assert!(glob("src/generated.rs").matches(Path::new("src/generated.rs"), false));
+        assert!(!glob("src/generated.rs").matches(Path::new("lib/src/generated.rs"), false));
+        assert!(glob("/vendor").matches(Path::new("vendor"), true));
+        assert!(!glob("/vendor").matches(Path::new("lib/vendor"), true));
+        assert!(glob("tests/**/*.py").matches(Path::new("tests/a/b/case.py"), false));
+        assert!(glob("tests/**/*.py").matches(Path::new("tests/case.py"), false));
+        assert!(glob("build/**").matches(Path::new("build/out/main.rs"), false));
+        assert!(glob("file?.[ch]").matches(Path::new("file1.c"), false));
+        assert!(!glob("file?.[!ch]").matches(Path::new("file1.c"), false));
+
+        // A trailing slash only matches directories
+        assert!(glob("out/").matches(Path::new("out"), true));
+        assert!(!glob("out/").matches(Path::new("out"), false));
+    }
+
+    #[test]
+    fn test_invalid_glob() {
+        assert!(Glob::new("").is_err());
+        assert!(Glob::new("/").is_err());
+        assert_eq!(
+            Glob::new("[abc").unwrap_err(),
+            "Invalid exclude pattern: [abc, a `[` is never closed"
+        );
+    }
+}
@@ -1,8 +1,23 @@
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::StatusCode;
 
 use serde::Deserialize;
 use serde_json::json;
 use std::env;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+
+pub use crate::buffer::extract_json;
+use crate::buffer::NewlineEof;
+use crate::code::looks_like_code;
+use crate::error::NeospellerError;
+use crate::lang_config::LanguageDefinition;
+use crate::language::CommentCollection;
+use crate::offline::OfflineProvider;
 
 /// OpenAI response format
 
@@ -26,14 +41,544 @@ struct Message {
     content: String,
 }
 
-pub fn check_grammar(
-    json_data: &str,
-    language: &str,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let openai_token = env::var("OPENAI_API_KEY")?;
+/// Error response format, shared by OpenAI, Anthropic and Ollama
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: ApiError,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ApiError {
+    Detailed {
+        message: String,
+        #[serde(rename = "type")]
+        error_type: Option<String>,
+    },
+    Message(String),
+}
+
+/// Anthropic response format
+
+#[derive(Debug, Deserialize)]
+struct AnthropicResponse {
+    content: Vec<ContentBlock>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContentBlock {
+    #[serde(rename = "type")]
+    block_type: String,
+    #[serde(default)]
+    text: String,
+}
+
+/// A backend able to check the grammar of the comments
+pub trait GrammarProvider {
+    /// Check the grammar of the comments
+    ///
+    /// # Params
+    /// * `json_data`: Serialized [`crate::language::CommentCollection`]
+    /// * `language`: Name of the source file's language
+    ///
+    /// # Returns
+    /// * The corrected comments in the same JSON format
+    fn check(&self, json_data: &str, language: &str) -> Result<String, NeospellerError>;
+
+    /// Check the grammar of a typed collection of comments
+    ///
+    /// # Params
+    /// * `comments`: The comments to check
+    /// * `language`: Name of the source file's language
+    ///
+    /// # Returns
+    /// * The corrected comments parsed from the response
+    fn check_comments(
+        &self,
+        comments: &CommentCollection,
+        language: &str,
+    ) -> Result<CommentCollection, NeospellerError> {
+        let json_data = serde_json::to_string(comments)?;
+        log::debug!("Sending {} comment(s), {} bytes", comments.len(), json_data.len());
+
+        let output = self.check(&json_data, language)?;
+        log::debug!("Received a response of {} bytes", output.len());
+
+        Ok(serde_json::from_str(extract_json(&output))?)
+    }
+}
+
+/// Available grammar backends
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ProviderKind {
+    #[default]
+    OpenAi,
+    Anthropic,
+    Azure,
+    Ollama,
+    Offline,
+}
+
+impl ProviderKind {
+    /// Convert a [`str`] to [`ProviderKind`]
+    pub fn from(string: &str) -> Result<ProviderKind, String> {
+        match string.trim().to_lowercase().as_str() {
+            "openai" => Ok(ProviderKind::OpenAi),
+            "anthropic" | "claude" => Ok(ProviderKind::Anthropic),
+            "azure" => Ok(ProviderKind::Azure),
+            "ollama" => Ok(ProviderKind::Ollama),
+            "offline" => Ok(ProviderKind::Offline),
+            _ => Err(format!("Invalid provider: {}", string)),
+        }
+    }
+
+    /// Read the provider from the `NEOSPELLER_PROVIDER` environment variable,
+    /// OpenAI is used when it is not set
+    pub fn from_env() -> Result<ProviderKind, String> {
+        match env::var("NEOSPELLER_PROVIDER") {
+            Ok(name) => ProviderKind::from(&name),
+            Err(_) => Ok(ProviderKind::default()),
+        }
+    }
+}
+
+/// What the model is allowed to correct
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CheckMode {
+    /// Only misspelled words, the wording is never changed
+    Spelling,
+    /// Only the grammar, misspelled words are kept
+    Grammar,
+    /// Both spelling and grammar
+    #[default]
+    Both,
+}
+
+impl CheckMode {
+    /// Convert a [`str`] to [`CheckMode`]
+    pub fn from(string: &str) -> Result<CheckMode, String> {
+        match string.trim().to_lowercase().as_str() {
+            "spelling" => Ok(CheckMode::Spelling),
+            "grammar" => Ok(CheckMode::Grammar),
+            "both" => Ok(CheckMode::Both),
+            _ => Err(format!("Invalid mode: {}", string)),
+        }
+    }
+}
+
+/// Default budget of characters of comments sent in each request
+pub const DEFAULT_CHUNK_SIZE: usize = 4000;
+
+/// Default time limit of each request, the one of the HTTP client
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Delay before the first retry, doubled on each retry
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Configuration of the grammar check
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrammarConfig {
+    /// Backend that checks the comments
+    pub provider: ProviderKind,
+    /// Model name, each provider has its own default
+    pub model: Option<String>,
+    /// Sampling temperature
+    pub temperature: f32,
+    /// Maximum tokens of the response
+    pub max_tokens: u32,
+    /// Budget of characters of comments sent in each request
+    pub chunk_size: usize,
+    /// Word list used by the offline provider, it is required by that provider
+    pub dict: Option<PathBuf>,
+    /// File containing the API key, it takes precedence over the API key environment variable
+    pub api_key_file: Option<PathBuf>,
+    /// Fail instead of warning when the response doesn't match the sent comments,
+    /// or when a multi-line comment is never closed
+    pub strict: bool,
+    /// What the model is allowed to correct
+    pub mode: CheckMode,
+    /// Template that replaces the built-in system prompt, `{language}` is substituted
+    pub prompt_file: Option<PathBuf>,
+    /// Word list, one word per line, of words that are never changed
+    pub ignore_words: Option<PathBuf>,
+    /// Directory of the cache of corrected comments, the cache is disabled when `None`
+    pub cache_dir: Option<PathBuf>,
+    /// Don't send the comments that look like code, e.g. commented-out code
+    pub skip_code: bool,
+    /// Don't send the comments with fewer words, e.g. `TODO`, zero sends every comment
+    pub min_words: usize,
+    /// Only send the comments of these lines, zero-based, every line when `None`
+    pub only_lines: Option<RangeInclusive<usize>>,
+    /// How the end of the corrected text is written
+    pub newline_eof: NewlineEof,
+    /// Time limit of each request to the provider
+    pub timeout: Duration,
+    /// Number of times a request is repeated when it fails temporarily, e.g. a rate limit
+    pub retries: u32,
+    /// Directory where the body of each request and the raw response are written, for debugging
+    pub dump_dir: Option<PathBuf>,
+    /// Replace the smart quotes introduced by the model with straight quotes
+    pub straight_quotes: bool,
+    /// Restore the case of the first letter and of the acronyms of each comment
+    pub keep_case: bool,
+    /// Custom languages of `--lang-config`, they win over the built-in ones
+    pub languages: Vec<LanguageDefinition>,
+}
+
+impl Default for GrammarConfig {
+    fn default() -> Self {
+        Self {
+            provider: ProviderKind::default(),
+            model: None,
+            temperature: 0.5,
+            max_tokens: 2000,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            dict: None,
+            api_key_file: None,
+            strict: false,
+            mode: CheckMode::default(),
+            prompt_file: None,
+            ignore_words: None,
+            cache_dir: None,
+            skip_code: false,
+            min_words: 0,
+            only_lines: None,
+            newline_eof: NewlineEof::default(),
+            timeout: DEFAULT_TIMEOUT,
+            retries: 0,
+            dump_dir: None,
+            straight_quotes: false,
+            keep_case: false,
+            languages: Vec::new(),
+        }
+    }
+}
+
+impl GrammarConfig {
+    /// Read the configuration from the environment variables: `NEOSPELLER_PROVIDER`,
+    /// `NEOSPELLER_MODEL`, `NEOSPELLER_CHUNK_SIZE` and `OPENAI_API_KEY_FILE`
+    pub fn from_env() -> Result<GrammarConfig, String> {
+        let mut config = GrammarConfig {
+            provider: ProviderKind::from_env()?,
+            model: env::var("NEOSPELLER_MODEL").ok(),
+            api_key_file: env::var_os("OPENAI_API_KEY_FILE").map(PathBuf::from),
+            ..GrammarConfig::default()
+        };
+
+        if let Ok(size) = env::var("NEOSPELLER_CHUNK_SIZE") {
+            config.chunk_size = size
+                .parse()
+                .map_err(|_| format!("Invalid chunk size: {}", size))?;
+        }
+
+        Ok(config)
+    }
+
+    /// Parse and validate a temperature, it must be between 0.0 and 2.0
+    pub fn parse_temperature(value: &str) -> Result<f32, String> {
+        let temperature: f32 = value
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid temperature: {}", value))?;
+
+        if !(0.0..=2.0).contains(&temperature) {
+            return Err(format!(
+                "Invalid temperature: {}, it must be between 0.0 and 2.0",
+                value
+            ));
+        }
+
+        Ok(temperature)
+    }
+
+    /// Parse the minimum number of words of the checked comments
+    pub fn parse_min_words(value: &str) -> Result<usize, String> {
+        value
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid min words: {}, it must be a non-negative integer", value))
+    }
 
-    let initial_prompt = format!(
-        r#"I will send you a JSON containing comments from a {} source file. Your task is to check the grammar and ensure that the comments are straightforward, clear, and concise. Respond in the same JSON format, including the line number and the corrected text.
+    /// Parse and validate a range of lines, e.g. `10:25`, one-based and inclusive
+    ///
+    /// # Returns
+    /// * The zero-based range, a start of zero is clamped to the first line,
+    ///   the lines after the end of the file are never matched
+    pub fn parse_line_range(value: &str) -> Result<RangeInclusive<usize>, String> {
+        let invalid = || format!("Invalid line range: {}, it must be START:END (e.g. 10:25)", value);
+
+        let (start, end) = value.trim().split_once(':').ok_or_else(invalid)?;
+        let start: usize = start.trim().parse().map_err(|_| invalid())?;
+        let end: usize = end.trim().parse().map_err(|_| invalid())?;
+
+        if start > end {
+            return Err(format!("Invalid line range: {}, the start is after the end", value));
+        }
+
+        Ok(start.saturating_sub(1)..=end.saturating_sub(1))
+    }
+
+    /// Whether a comment is left out of the check, it is kept unchanged
+    ///
+    /// Each line of a multi-line comment is checked on its own.
+    ///
+    /// # Params
+    /// * `line`: Zero-based line of the comment
+    /// * `text`: Text of the comment
+    pub fn is_skipped(&self, line: usize, text: &str) -> bool {
+        self.only_lines.as_ref().is_some_and(|lines| !lines.contains(&line))
+            || text.split_whitespace().count() < self.min_words
+            || (self.skip_code && looks_like_code(text))
+    }
+
+    /// Parse and validate the maximum tokens, it must be greater than zero
+    pub fn parse_max_tokens(value: &str) -> Result<u32, String> {
+        match value.trim().parse() {
+            Ok(max_tokens) if max_tokens > 0 => Ok(max_tokens),
+            _ => Err(format!(
+                "Invalid max tokens: {}, it must be a positive integer",
+                value
+            )),
+        }
+    }
+
+    /// Parse and validate a timeout in seconds, it must be greater than zero
+    pub fn parse_timeout(value: &str) -> Result<Duration, String> {
+        match value.trim().parse::<f64>() {
+            Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(Duration::from_secs_f64(seconds)),
+            _ => Err(format!("Invalid timeout: {}, it must be a positive number of seconds", value)),
+        }
+    }
+
+    /// Parse the number of retries of a failed request
+    pub fn parse_retries(value: &str) -> Result<u32, String> {
+        value
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid retries: {}, it must be a non-negative integer", value))
+    }
+
+    /// Send a request with the configured timeout, repeating it up to `retries` times
+    /// when it fails temporarily: a timeout, a connection error, a rate limit (429)
+    /// or a server error (5xx)
+    ///
+    /// # Returns
+    /// * The response of the last attempt, the status is checked by the caller
+    fn send(&self, request: RequestBuilder) -> Result<Response, NeospellerError> {
+        let request = request.timeout(self.timeout);
+
+        for attempt in 0..self.retries {
+            let Some(current) = request.try_clone() else {
+                break;
+            };
+
+            match current.send() {
+                Ok(res) if !is_temporary_failure(res.status()) => return Ok(res),
+                Ok(res) => log::warn!("Request failed with {}, retrying ({}/{})", res.status(), attempt + 1, self.retries),
+                Err(err) if err.is_timeout() || err.is_connect() => {
+                    log::warn!("Request failed: {}, retrying ({}/{})", err, attempt + 1, self.retries)
+                }
+                Err(err) => return Err(err.into()),
+            }
+
+            thread::sleep(RETRY_DELAY * 2u32.pow(attempt.min(5)));
+        }
+
+        Ok(request.send()?)
+    }
+
+    /// Send a request, see [`GrammarConfig::send`], and read the body of the response,
+    /// failing when the status is not successful
+    ///
+    /// With `dump_dir`, the request and the raw response are written there before the status is checked.
+    fn fetch(&self, request: RequestBuilder) -> Result<String, NeospellerError> {
+        let dump = match &self.dump_dir {
+            Some(dir) => Some(dump_request(dir, &request)?),
+            None => None,
+        };
+
+        let res = self.send(request)?;
+        let status = res.status();
+        let text = res.text()?;
+
+        if let Some(prefix) = dump {
+            std::fs::write(prefix.with_extension("response.txt"), &text)?;
+        }
+
+        if !status.is_success() {
+            let message = api_error_message(&text).unwrap_or(text);
+            return Err(NeospellerError::ApiResponse(format!("{}: {}", status, message)));
+        }
+
+        Ok(text)
+    }
+
+    /// Model name, or the default model of the provider
+    pub fn model_or<'a>(&'a self, default: &'a str) -> &'a str {
+        self.model.as_deref().unwrap_or(default)
+    }
+
+    /// Name of the model that checks the comments, `offline` for the offline provider
+    pub fn model_name(&self) -> &str {
+        match self.provider {
+            ProviderKind::OpenAi | ProviderKind::Azure => self.model_or(OpenAiProvider::DEFAULT_MODEL),
+            ProviderKind::Anthropic => self.model_or(AnthropicProvider::DEFAULT_MODEL),
+            ProviderKind::Ollama => self.model_or(OllamaProvider::DEFAULT_MODEL),
+            ProviderKind::Offline => "offline",
+        }
+    }
+
+    /// Build the system prompt, from the template of `prompt_file` when provided
+    ///
+    /// # Params
+    /// * `language`: Name of the source file's language, substituted in `{language}`
+    ///
+    /// # Returns
+    /// * The system prompt or an error if the template cannot be read
+    pub fn system_prompt(&self, language: &str) -> Result<String, NeospellerError> {
+        match &self.prompt_file {
+            Some(path) => {
+                let template = std::fs::read_to_string(path).map_err(|err| {
+                    NeospellerError::Config(format!("Cannot read the prompt file {}: {}", path.display(), err))
+                })?;
+                Ok(template.replace("{language}", language))
+            }
+            None => Ok(system_prompt(language, self.mode)),
+        }
+    }
+
+    /// Build the [`GrammarProvider`] selected by the configuration
+    pub fn provider(&self) -> Result<Box<dyn GrammarProvider + Send + Sync>, NeospellerError> {
+        match (&self.provider, &self.dict) {
+            (ProviderKind::OpenAi, _) => Ok(Box::new(OpenAiProvider::new(self.clone()))),
+            (ProviderKind::Anthropic, _) => Ok(Box::new(AnthropicProvider::new(self.clone()))),
+            (ProviderKind::Azure, _) => Ok(Box::new(AzureOpenAiProvider::new(self.clone()))),
+            (ProviderKind::Ollama, _) => Ok(Box::new(OllamaProvider::new(self.clone()))),
+            (ProviderKind::Offline, Some(dict)) => Ok(Box::new(OfflineProvider::from_file(dict)?)),
+            (ProviderKind::Offline, None) => Err(NeospellerError::Config(
+                "The offline provider needs a word list, pass --dict FILE, e.g. /usr/share/dict/words".to_string(),
+            )),
+        }
+    }
+}
+
+/// Shared HTTP client, built once and reused across grammar calls
+/// to keep the connection pool and TLS setup alive
+fn http_client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(Client::new)
+}
+
+/// Whether a request may succeed if it is repeated, after a rate limit or a server error
+fn is_temporary_failure(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Headers that carry the API keys, redacted in the dumped requests
+const SECRET_HEADERS: [&str; 3] = ["authorization", "api-key", "x-api-key"];
+
+/// Write the method, URL, headers and body of a request to a new file of `dir`,
+/// named after the current time, e.g. `20260101T120000-000-0001.request.json`
+///
+/// # Returns
+/// * The path of the dump without its extension, the response is written next to it
+fn dump_request(dir: &Path, request: &RequestBuilder) -> Result<PathBuf, NeospellerError> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let Some(request) = request.try_clone() else {
+        return Err(NeospellerError::Config("Cannot dump a streamed request".to_string()));
+    };
+    let request = request.build()?;
+
+    let headers: serde_json::Map<String, serde_json::Value> = request
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            let value = if SECRET_HEADERS.contains(&name.as_str()) {
+                "[REDACTED]".into()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into()
+            };
+            (name.to_string(), value)
+        })
+        .collect();
+
+    // The body is kept as JSON when it is valid, as text otherwise
+    let body = request.body().and_then(|body| body.as_bytes()).unwrap_or_default();
+    let body = serde_json::from_slice(body)
+        .unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(body).into_owned()));
+
+    std::fs::create_dir_all(dir)?;
+    let prefix = dir.join(format!(
+        "{}-{:04}",
+        chrono::Local::now().format("%Y%m%dT%H%M%S-%3f"),
+        COUNTER.fetch_add(1, Ordering::Relaxed) + 1
+    ));
+
+    let dump = json!({
+        "method": request.method().as_str(),
+        "url": request.url().as_str(),
+        "headers": headers,
+        "body": body,
+    });
+    std::fs::write(prefix.with_extension("request.json"), serde_json::to_string_pretty(&dump)?)?;
+    log::debug!("Request dumped to {}", prefix.display());
+
+    Ok(prefix)
+}
+
+/// Get the explanation of an error response, e.g. an invalid API key or an exceeded quota
+///
+/// # Returns
+/// * The message and type of the error, or `None` if the body is not an error response
+fn api_error_message(body: &str) -> Option<String> {
+    let response: ErrorResponse = serde_json::from_str(body).ok()?;
+
+    match response.error {
+        ApiError::Detailed { message, error_type: Some(error_type) } => {
+            Some(format!("{} ({})", message, error_type))
+        }
+        ApiError::Detailed { message, error_type: None } => Some(message),
+        ApiError::Message(message) => Some(message),
+    }
+}
+
+/// Resolve the API key, reading it from a file when provided, otherwise
+/// from an environment variable
+///
+/// # Params
+/// * `key_file`: File containing the API key, trailing whitespace is trimmed
+/// * `env_var`: Environment variable with the API key, e.g. `OPENAI_API_KEY`
+///
+/// # Returns
+/// * The API key or an error if neither the file nor the variable is available
+pub fn resolve_api_key(
+    key_file: Option<&Path>,
+    env_var: &str,
+) -> Result<String, NeospellerError> {
+    if let Some(path) = key_file {
+        let key = std::fs::read_to_string(path).map_err(|err| {
+            NeospellerError::Config(format!("Cannot read the API key file {}: {}", path.display(), err))
+        })?;
+        return Ok(key.trim_end().to_string());
+    }
+
+    env::var(env_var).map_err(|_| {
+        NeospellerError::Config(format!("The API key is not set, export {} or use --api-key-file", env_var))
+    })
+}
+
+/// Build the system prompt sent to the model
+fn system_prompt(language: &str, mode: CheckMode) -> String {
+    let task = match mode {
+        CheckMode::Spelling => "Your task is to fix only the misspelled words. Leave the wording, punctuation, and structure of the sentences untouched, never rephrase them.",
+        CheckMode::Grammar => "Your task is to check the grammar and ensure that the comments are straightforward, clear, and concise. Do not change the spelling of the words.",
+        CheckMode::Both => "Your task is to check the spelling and grammar and ensure that the comments are straightforward, clear, and concise.",
+    };
+
+    format!(
+        r#"I will send you a JSON containing comments from a {} source file. {} Respond in the same JSON format, including the line number and the corrected text.
 
 - Do not remove or add punctuation at the end of lines unless necessary for clarity
 - Do not remove formatters such as '-' or '*'; preserve the original formatting and change only the text when necessary.
@@ -41,42 +586,404 @@ pub fn check_grammar(
 - Keep quotes if they exist, along with language syntax such as commas, brackets, etc.
 - Do not replace variable names like line_number to line number
 - Do not mix single-line comments with multi-line comments; keep them separate."#,
-        language
-    );
-
-    let url =
-        env::var("OPENAI_API_BASE_URL").unwrap_or_else(|_| "https://api.openai.com".to_string());
-    let client = Client::new();
-
-    let res = client
-        .post(format!("{}/v1/chat/completions", url))
-        .header("Authorization", format!("Bearer {}", openai_token))
-        .header("Content-Type", "application/json")
-        .json(&json!({
-            "model": "gpt-5.4-nano",
-            "messages": [
-                {
-                    "role": "system",
-                    "content": initial_prompt
-                },
-                {
-                    "role": "user",
-                    "content": json_data
-                }
-            ],
-            "max_completion_tokens": 2000,
-            "temperature": 0.5,
-            "response_format": {"type": "json_object"}
-        }))
-        .send()?;
+        language, task
+    )
+}
+
+/// OpenAI chat completions backend
+pub struct OpenAiProvider {
+    config: GrammarConfig,
+}
+
+impl OpenAiProvider {
+    /// Model used when none is configured
+    pub const DEFAULT_MODEL: &'static str = "gpt-5.4-nano";
+
+    /// Create a new [`OpenAiProvider`]
+    pub fn new(config: GrammarConfig) -> Self {
+        Self { config }
+    }
+}
 
-    let response_text = res.text()?;
-    let response: OpenAIResponse = serde_json::from_str(&response_text)?;
+impl GrammarProvider for OpenAiProvider {
+    fn check(&self, json_data: &str, language: &str) -> Result<String, NeospellerError> {
+        let openai_token = resolve_api_key(self.config.api_key_file.as_deref(), "OPENAI_API_KEY")?;
+
+        let url =
+            env::var("OPENAI_API_BASE_URL").unwrap_or_else(|_| "https://api.openai.com".to_string());
+        let text = self.config.fetch(
+            http_client()
+                .post(format!("{}/v1/chat/completions", url))
+                .header("Authorization", format!("Bearer {}", openai_token))
+                .header("Content-Type", "application/json")
+                .json(&chat_completions_body(&self.config, Self::DEFAULT_MODEL, json_data, language)?),
+        )?;
+
+        parse_chat_completions(&text)
+    }
+}
+
+/// Body of a chat completions request, shared by OpenAI and Azure OpenAI
+fn chat_completions_body(
+    config: &GrammarConfig,
+    default_model: &str,
+    json_data: &str,
+    language: &str,
+) -> Result<serde_json::Value, NeospellerError> {
+    Ok(json!({
+        "model": config.model_or(default_model),
+        "messages": [
+            {
+                "role": "system",
+                "content": config.system_prompt(language)?
+            },
+            {
+                "role": "user",
+                "content": json_data
+            }
+        ],
+        "max_completion_tokens": config.max_tokens,
+        "temperature": config.temperature,
+        "response_format": {"type": "json_object"}
+    }))
+}
+
+/// Get the content of the first choice of a chat completions response
+fn parse_chat_completions(response_text: &str) -> Result<String, NeospellerError> {
+    if let Some(message) = api_error_message(response_text) {
+        return Err(NeospellerError::ApiResponse(message));
+    }
+
+    let response: OpenAIResponse = serde_json::from_str(response_text)?;
 
     // Return the content string from the first choice
     if let Some(choice) = response.choices.first() {
-        Ok(choice.message.content.clone().replace("\n", ""))
+        Ok(choice.message.content.clone())
     } else {
-        Err("No choices found in the response".into())
+        Err(NeospellerError::ApiResponse("No choices found in the response".to_string()))
+    }
+}
+
+/// Azure OpenAI backend, it mirrors the OpenAI schema with its own URL and authentication
+pub struct AzureOpenAiProvider {
+    config: GrammarConfig,
+}
+
+impl AzureOpenAiProvider {
+    /// API version used when `AZURE_OPENAI_API_VERSION` is not set
+    pub const DEFAULT_API_VERSION: &'static str = "2024-10-21";
+
+    /// Create a new [`AzureOpenAiProvider`]
+    pub fn new(config: GrammarConfig) -> Self {
+        Self { config }
+    }
+
+    /// Build the chat completions URL of the deployment from the environment variables
+    /// `AZURE_OPENAI_ENDPOINT`, `AZURE_OPENAI_DEPLOYMENT` and `AZURE_OPENAI_API_VERSION`
+    fn url() -> Result<String, NeospellerError> {
+        let endpoint = env::var("AZURE_OPENAI_ENDPOINT").map_err(|_| {
+            NeospellerError::Config("The Azure endpoint is not set, export AZURE_OPENAI_ENDPOINT".to_string())
+        })?;
+        let deployment = env::var("AZURE_OPENAI_DEPLOYMENT").map_err(|_| {
+            NeospellerError::Config("The Azure deployment is not set, export AZURE_OPENAI_DEPLOYMENT".to_string())
+        })?;
+        let api_version =
+            env::var("AZURE_OPENAI_API_VERSION").unwrap_or_else(|_| Self::DEFAULT_API_VERSION.to_string());
+
+        Ok(format!(
+            "{}/openai/deployments/{}/chat/completions?api-version={}",
+            endpoint.trim_end_matches('/'),
+            deployment,
+            api_version
+        ))
+    }
+}
+
+impl GrammarProvider for AzureOpenAiProvider {
+    fn check(&self, json_data: &str, language: &str) -> Result<String, NeospellerError> {
+        let azure_token = resolve_api_key(self.config.api_key_file.as_deref(), "AZURE_OPENAI_API_KEY")?;
+
+        let text = self.config.fetch(
+            http_client()
+                .post(Self::url()?)
+                .header("api-key", azure_token)
+                .header("Content-Type", "application/json")
+                .json(&chat_completions_body(
+                    &self.config,
+                    OpenAiProvider::DEFAULT_MODEL,
+                    json_data,
+                    language,
+                )?),
+        )?;
+
+        parse_chat_completions(&text)
+    }
+}
+
+/// Ollama backend, or any local server compatible with OpenAI that doesn't need an API key
+pub struct OllamaProvider {
+    config: GrammarConfig,
+}
+
+impl OllamaProvider {
+    /// Model used when none is configured
+    pub const DEFAULT_MODEL: &'static str = "llama3.2";
+
+    /// Create a new [`OllamaProvider`]
+    pub fn new(config: GrammarConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl GrammarProvider for OllamaProvider {
+    fn check(&self, json_data: &str, language: &str) -> Result<String, NeospellerError> {
+        let url =
+            env::var("OLLAMA_API_BASE_URL").unwrap_or_else(|_| "http://localhost:11434".to_string());
+        let text = self.config.fetch(
+            http_client()
+                .post(format!("{}/v1/chat/completions", url))
+                .header("Content-Type", "application/json")
+                .json(&chat_completions_body(&self.config, Self::DEFAULT_MODEL, json_data, language)?),
+        )?;
+
+        parse_chat_completions(&text)
+    }
+}
+
+/// Anthropic messages backend
+pub struct AnthropicProvider {
+    config: GrammarConfig,
+}
+
+impl AnthropicProvider {
+    /// Model used when none is configured
+    pub const DEFAULT_MODEL: &'static str = "claude-haiku-4-5";
+
+    /// Create a new [`AnthropicProvider`]
+    pub fn new(config: GrammarConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl GrammarProvider for AnthropicProvider {
+    fn check(&self, json_data: &str, language: &str) -> Result<String, NeospellerError> {
+        let anthropic_token = resolve_api_key(self.config.api_key_file.as_deref(), "ANTHROPIC_API_KEY")?;
+
+        let url = env::var("ANTHROPIC_API_BASE_URL")
+            .unwrap_or_else(|_| "https://api.anthropic.com".to_string());
+        let text = self.config.fetch(
+            http_client()
+                .post(format!("{}/v1/messages", url))
+                .header("x-api-key", anthropic_token)
+                .header("anthropic-version", "2023-06-01")
+                .header("Content-Type", "application/json")
+                .json(&json!({
+                    "model": self.config.model_or(Self::DEFAULT_MODEL),
+                    "system": self.config.system_prompt(language)?,
+                    "messages": [
+                        {
+                            "role": "user",
+                            "content": json_data
+                        }
+                    ],
+                    "max_tokens": self.config.max_tokens,
+                    "temperature": self.config.temperature
+                })),
+        )?;
+
+        if let Some(message) = api_error_message(&text) {
+            return Err(NeospellerError::ApiResponse(message));
+        }
+
+        let response: AnthropicResponse = serde_json::from_str(&text)?;
+
+        // Return the first text block of the message
+        if let Some(block) = response.content.iter().find(|b| b.block_type == "text") {
+            Ok(block.text.clone())
+        } else {
+            Err(NeospellerError::ApiResponse("No text content found in the response".to_string()))
+        }
+    }
+}
+
+/// Check the grammar of the comments with the provider configured
+/// through the environment variables, see [`GrammarConfig::from_env`]
+///
+/// # Returns
+/// * The corrected comments parsed from the response
+pub fn check_grammar(
+    comments: &CommentCollection,
+    language: &str,
+) -> Result<CommentCollection, NeospellerError> {
+    let provider = GrammarConfig::from_env().map_err(NeospellerError::Config)?.provider()?;
+    provider.check_comments(comments, language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_kind_from() {
+        assert_eq!(ProviderKind::from("openai"), Ok(ProviderKind::OpenAi));
+        assert_eq!(ProviderKind::from("Anthropic"), Ok(ProviderKind::Anthropic));
+        assert_eq!(ProviderKind::from("claude"), Ok(ProviderKind::Anthropic));
+        assert_eq!(ProviderKind::from("azure"), Ok(ProviderKind::Azure));
+        assert_eq!(ProviderKind::from("ollama"), Ok(ProviderKind::Ollama));
+        assert_eq!(ProviderKind::from("offline"), Ok(ProviderKind::Offline));
+        assert!(ProviderKind::from("unknown").is_err());
+    }
+
+    #[test]
+    fn test_api_error_message() {
+        let body = r#"{"error": {"message": "Incorrect API key provided", "type": "invalid_request_error", "code": "invalid_api_key"}}"#;
+        assert_eq!(
+            api_error_message(body).as_deref(),
+            Some("Incorrect API key provided (invalid_request_error)")
+        );
+
+        let body = r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}"#;
+        assert_eq!(api_error_message(body).as_deref(), Some("Overloaded (overloaded_error)"));
+
+        let body = r#"{"error": "model 'llama9' not found"}"#;
+        assert_eq!(api_error_message(body).as_deref(), Some("model 'llama9' not found"));
+
+        assert_eq!(api_error_message(r#"{"choices": []}"#), None);
+        assert_eq!(api_error_message("Internal Server Error"), None);
+    }
+
+    #[test]
+    fn test_check_mode() {
+        assert_eq!(CheckMode::from("spelling"), Ok(CheckMode::Spelling));
+        assert_eq!(CheckMode::from("Grammar"), Ok(CheckMode::Grammar));
+        assert_eq!(CheckMode::from("both"), Ok(CheckMode::Both));
+        assert!(CheckMode::from("style").is_err());
+
+        assert!(system_prompt("rust", CheckMode::Spelling).contains("fix only the misspelled words"));
+        assert!(system_prompt("rust", CheckMode::Both).contains("spelling and grammar"));
+    }
+
+    #[test]
+    fn test_prompt_file() {
+        let config = GrammarConfig::default();
+        assert_eq!(config.system_prompt("rust").unwrap(), system_prompt("rust", CheckMode::Both));
+
+        let path = env::temp_dir().join(format!("neospeller-prompt-{}.txt", std::process::id()));
+        std::fs::write(&path, "Fix the {language} comments, \"idempotent\" is a valid word.").unwrap();
+
+        let config = GrammarConfig {
+            prompt_file: Some(path.clone()),
+            ..GrammarConfig::default()
+        };
+        assert_eq!(
+            config.system_prompt("rust").unwrap(),
+            "Fix the rust comments, \"idempotent\" is a valid word."
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(config.system_prompt("rust").is_err());
+    }
+
+    #[test]
+    fn test_offline_provider_needs_a_word_list() {
+        let config = GrammarConfig {
+            provider: ProviderKind::Offline,
+            ..GrammarConfig::default()
+        };
+        let err = config.provider().err().unwrap();
+        assert!(err.to_string().contains("--dict"), "{}", err);
+    }
+
+    #[test]
+    fn test_grammar_config_model() {
+        let config = GrammarConfig::default();
+        assert_eq!(config.model_or(OpenAiProvider::DEFAULT_MODEL), "gpt-5.4-nano");
+
+        let config = GrammarConfig {
+            model: Some("gpt-4o".to_string()),
+            ..GrammarConfig::default()
+        };
+        assert_eq!(config.model_or(OpenAiProvider::DEFAULT_MODEL), "gpt-4o");
+    }
+
+    #[test]
+    fn test_parse_temperature() {
+        assert_eq!(GrammarConfig::parse_temperature("0"), Ok(0.0));
+        assert_eq!(GrammarConfig::parse_temperature("1.5"), Ok(1.5));
+        assert_eq!(GrammarConfig::parse_temperature("2.0"), Ok(2.0));
+        assert!(GrammarConfig::parse_temperature("2.1").is_err());
+        assert!(GrammarConfig::parse_temperature("-0.5").is_err());
+        assert!(GrammarConfig::parse_temperature("hot").is_err());
+    }
+
+    #[test]
+    fn test_parse_max_tokens() {
+        assert_eq!(GrammarConfig::parse_max_tokens("4000"), Ok(4000));
+        assert!(GrammarConfig::parse_max_tokens("0").is_err());
+        assert!(GrammarConfig::parse_max_tokens("-1").is_err());
+        assert!(GrammarConfig::parse_max_tokens("many").is_err());
+    }
+
+    #[test]
+    fn test_is_skipped() {
+        let config = GrammarConfig::default();
+        assert!(!config.is_skipped(0, "TODO"));
+        assert!(!config.is_skipped(0, "old_function(arg1, arg2)"));
+
+        let config = GrammarConfig {
+            min_words: 2,
+            skip_code: true,
+            ..GrammarConfig::default()
+        };
+        assert!(config.is_skipped(0, "TODO"));
+        assert!(config.is_skipped(0, ""));
+        assert!(config.is_skipped(0, "old_function(arg1, arg2)"));
+        assert!(!config.is_skipped(0, "Fix this"));
+        assert!(GrammarConfig::parse_min_words("x").is_err());
+
+        let config = GrammarConfig {
+            only_lines: Some(GrammarConfig::parse_line_range("2:3").unwrap()),
+            ..GrammarConfig::default()
+        };
+        assert!(config.is_skipped(0, "Fix this"));
+        assert!(!config.is_skipped(1, "Fix this"));
+        assert!(!config.is_skipped(2, "Fix this"));
+        assert!(config.is_skipped(3, "Fix this"));
+    }
+
+    #[test]
+    fn test_parse_line_range() {
+        assert_eq!(GrammarConfig::parse_line_range("10:25"), Ok(9..=24));
+        assert_eq!(GrammarConfig::parse_line_range("0:1"), Ok(0..=0));
+        assert_eq!(GrammarConfig::parse_line_range("5:5"), Ok(4..=4));
+        assert_eq!(
+            GrammarConfig::parse_line_range("25:10"),
+            Err("Invalid line range: 25:10, the start is after the end".to_string())
+        );
+        assert!(GrammarConfig::parse_line_range("10").is_err());
+        assert!(GrammarConfig::parse_line_range("a:b").is_err());
+    }
+
+    #[test]
+    fn test_resolve_api_key() {
+        let env_var = "NEOSPELLER_TEST_RESOLVE_API_KEY";
+
+        // Neither the file nor the variable
+        env::remove_var(env_var);
+        assert!(resolve_api_key(None, env_var).is_err());
+
+        // Environment variable
+        env::set_var(env_var, "env_key");
+        assert_eq!(resolve_api_key(None, env_var).unwrap(), "env_key");
+
+        // The file takes precedence
+        let path = env::temp_dir().join(format!("neospeller-api-key-{}", std::process::id()));
+        std::fs::write(&path, "file_key\n").unwrap();
+        assert_eq!(resolve_api_key(Some(&path), env_var).unwrap(), "file_key");
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(resolve_api_key(Some(&path), env_var).is_err());
+
+        env::remove_var(env_var);
     }
 }
@@ -0,0 +1,215 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use crate::language::{Language, UnsupportedLanguageError};
+
+/// Definition of a custom language, e.g. in `languages.json`, only JSON is accepted:
+///
+/// ```json
+/// {
+///   "languages": [
+///     {
+///       "name": "zig",
+///       "extensions": ["zig"],
+///       "comment_symbols": ["//"]
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LanguageDefinition {
+    pub name: String,
+    /// Short names accepted in place of the name
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// File extensions of the language, without the leading dot
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// Single line comment symbols
+    #[serde(default)]
+    pub comment_symbols: Vec<String>,
+    /// Multi-line comment symbols as `[open, close]` pairs
+    #[serde(default)]
+    pub block_symbols: Vec<(String, String)>,
+    /// Whether multi-line comments can be nested
+    #[serde(default)]
+    pub nested: bool,
+}
+
+#[derive(Deserialize)]
+struct LanguagesConfig {
+    languages: Vec<LanguageDefinition>,
+}
+
+impl LanguageDefinition {
+    /// Build the [`Language`] of the definition
+    pub fn to_language(&self) -> Language {
+        let mut language = Language::new(&self.name, "", "", "");
+        language.aliases = self.aliases.clone();
+        language.comment_symbols = self.comment_symbols.clone();
+        language.ml_comment_symbols = self.block_symbols.clone();
+        language.nested_ml_comments = self.nested;
+        language
+    }
+
+    /// Lowercase the names and check that the language has some comment symbol
+    fn normalize(mut self) -> Result<LanguageDefinition, String> {
+        self.name = self.name.trim().to_lowercase();
+        if self.name.is_empty() {
+            return Err("Invalid language: the name is empty".to_string());
+        }
+
+        self.comment_symbols.retain(|symbol| !symbol.is_empty());
+        self.block_symbols.retain(|(open, close)| !open.is_empty() && !close.is_empty());
+        if self.comment_symbols.is_empty() && self.block_symbols.is_empty() {
+            return Err(format!("Invalid language: {} has no comment symbols", self.name));
+        }
+
+        for alias in &mut self.aliases {
+            *alias = alias.trim().to_lowercase();
+        }
+        for ext in &mut self.extensions {
+            *ext = ext.trim().trim_start_matches('.').to_lowercase();
+        }
+
+        Ok(self)
+    }
+}
+
+/// Read the custom languages of a config file
+///
+/// # Params
+/// * `path`: JSON file with a `languages` list of [`LanguageDefinition`]
+///
+/// # Returns
+/// * The definitions or an error message
+pub fn load(path: &Path) -> Result<Vec<LanguageDefinition>, String> {
+    let content =
+        fs::read_to_string(path).map_err(|err| format!("Cannot read the languages of {}: {}", path.display(), err))?;
+    let config: LanguagesConfig = serde_json::from_str(&content)
+        .map_err(|err| format!("Invalid languages config {}: {}", path.display(), err))?;
+
+    config.languages.into_iter().map(LanguageDefinition::normalize).collect()
+}
+
+/// Load the languages of `--lang-config`, or of the default config when it exists
+///
+/// # Params
+/// * `path`: The config passed by the user, the default one is read when `None`
+///
+/// # Returns
+/// * The definitions, empty when there is no config, or an error message
+pub fn load_languages(path: Option<&Path>) -> Result<Vec<LanguageDefinition>, String> {
+    match path {
+        Some(path) => load(path),
+        None => match default_path().filter(|path| path.is_file()) {
+            Some(path) => load(&path),
+            None => Ok(Vec::new()),
+        },
+    }
+}
+
+/// Find a language by its name or an alias, a custom language wins over a built-in one
+///
+/// # Params
+/// * `name`: Name or alias of the language, case-insensitive
+/// * `custom`: The custom languages, see [`load_languages`]
+///
+/// # Returns
+/// * The [`Language`] or an [`UnsupportedLanguageError`] listing the supported languages
+pub fn language_from_name(name: &str, custom: &[LanguageDefinition]) -> Result<Language, UnsupportedLanguageError> {
+    let lowered = name.trim().to_lowercase();
+
+    match custom.iter().find(|c| c.name == lowered || c.aliases.contains(&lowered)) {
+        Some(definition) => Ok(definition.to_language()),
+        None => Language::from_name(name).map_err(|mut err| {
+            err.supported.extend(custom.iter().map(|c| c.name.clone()));
+            err
+        }),
+    }
+}
+
+/// Find a language by a file extension, a custom language wins over a built-in one
+///
+/// # Params
+/// * `ext`: File extension, with or without the leading dot
+/// * `custom`: The custom languages, see [`load_languages`]
+///
+/// # Returns
+/// * The [`Language`] or `None` if the extension is unknown
+pub fn language_from_extension(ext: &str, custom: &[LanguageDefinition]) -> Option<Language> {
+    let lowered = ext.trim().trim_start_matches('.').to_lowercase();
+
+    custom
+        .iter()
+        .find(|definition| definition.extensions.contains(&lowered))
+        .map(LanguageDefinition::to_language)
+        .or_else(|| Language::from_extension(ext))
+}
+
+/// Default config of the custom languages,
+/// `$XDG_CONFIG_HOME/neospeller/languages.json` or `~/.config/neospeller/languages.json`
+pub fn default_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("neospeller").join("languages.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract_comments;
+    use crate::language::CommentType;
+
+    #[test]
+    fn test_load_languages() {
+        let path = env::temp_dir().join(format!("neospeller-languages-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"{"languages": [{"name": "Zig", "aliases": ["ZZ"], "extensions": [".zig"],
+                "comment_symbols": ["//"], "block_symbols": [["/+", "+/"]], "nested": true}]}"#,
+        )
+        .unwrap();
+
+        let custom = load_languages(Some(&path)).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let zig = language_from_name("zig", &custom).unwrap();
+        assert_eq!(zig.comment_symbols, vec!["//"]);
+        assert!(zig.nested_ml_comments);
+        assert_eq!(language_from_name("zz", &custom).unwrap().name, "zig");
+        assert_eq!(language_from_extension("ZIG", &custom).unwrap().name, "zig");
+
+        let comments = extract_comments("const x = 5; // The answr\n/+ A block +/\n", &zig);
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments[0].text, "The answr");
+        assert_eq!(comments[1].comment_type, CommentType::Multi);
+
+        // The built-in languages are still supported, and only know the custom ones when passed
+        assert_eq!(language_from_name("python", &custom).unwrap().name, "python");
+        assert_eq!(language_from_extension("py", &custom).unwrap().name, "python");
+        assert!(Language::from_name("zig").is_err());
+        assert!(language_from_extension("zig", &[]).is_none());
+
+        let err = language_from_name("cobol", &custom).unwrap_err();
+        assert!(err.supported.contains(&"zig".to_string()));
+    }
+
+    #[test]
+    fn test_load_invalid_languages() {
+        let path = env::temp_dir().join(format!("neospeller-languages-invalid-{}.json", std::process::id()));
+
+        fs::write(&path, r#"{"languages": [{"name": "nosymbols"}]}"#).unwrap();
+        assert_eq!(load(&path).unwrap_err(), "Invalid language: nosymbols has no comment symbols");
+
+        fs::write(&path, "not json").unwrap();
+        assert!(load(&path).unwrap_err().starts_with("Invalid languages config"));
+
+        fs::remove_file(&path).unwrap();
+        assert!(load(&path).unwrap_err().starts_with("Cannot read the languages of"));
+        assert!(load_languages(Some(&path)).is_err());
+    }
+}
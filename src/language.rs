@@ -1,9 +1,10 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt::Display;
 
 /// Main type that represents single line comment
 /// or multiline comment
-#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
 pub enum CommentType {
     #[serde(rename = "single_comments")]
     Single,
@@ -36,22 +37,62 @@ impl CommentType {
 pub struct ParseState {
     pub comments: Vec<Comment>,
     pub lines_parsed: usize,
+    /// Whether a multi-line comment is never closed, e.g. in a truncated file
+    pub unterminated: bool,
 }
 
 /// Main structure that represents a comment
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Comment {
     pub line: usize,
+    /// Byte offset of the text in the line
+    pub col: usize,
     pub text: String,
     pub comment_type: CommentType,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommentCollection {
+    #[serde(deserialize_with = "deserialize_comments")]
     single_comments: HashMap<usize, String>,
+    #[serde(deserialize_with = "deserialize_comments")]
     multiline_comments: HashMap<usize, String>,
 }
 
+/// Deserialize the comments of a response, tolerating the mistakes of the model
+///
+/// A number or a boolean is coerced to its text, e.g. `{"3": 42}`, and an entry
+/// that cannot be coerced, e.g. a nested object or a key that is not a line number,
+/// is skipped instead of failing the whole response.
+fn deserialize_comments<'de, D>(deserializer: D) -> Result<HashMap<usize, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let entries: HashMap<String, serde_json::Value> = HashMap::deserialize(deserializer)?;
+    let mut comments = HashMap::new();
+
+    for (key, value) in entries {
+        let text = match value {
+            serde_json::Value::String(text) => text,
+            serde_json::Value::Number(number) => number.to_string(),
+            serde_json::Value::Bool(value) => value.to_string(),
+            value => {
+                log::warn!("Skipping the comment of line {}, not a text: {}", key, value);
+                continue;
+            }
+        };
+
+        match key.trim().parse() {
+            Ok(line) => {
+                comments.insert(line, text);
+            }
+            Err(_) => log::warn!("Skipping the comment of an invalid line: {}", key),
+        }
+    }
+
+    Ok(comments)
+}
+
 impl CommentCollection {
     pub fn from_comments(comments: Vec<Comment>) -> Self {
         let mut single_comments = HashMap::new();
@@ -70,11 +111,115 @@ impl CommentCollection {
         }
     }
 
+    /// Map of the comments of the provided type
+    fn comments_of(&self, comment_type: CommentType) -> &HashMap<usize, String> {
+        match comment_type {
+            CommentType::Single => &self.single_comments,
+            CommentType::Multi => &self.multiline_comments,
+        }
+    }
+
+    /// Mutable map of the comments of the provided type
+    fn comments_of_mut(&mut self, comment_type: CommentType) -> &mut HashMap<usize, String> {
+        match comment_type {
+            CommentType::Single => &mut self.single_comments,
+            CommentType::Multi => &mut self.multiline_comments,
+        }
+    }
+
+    /// Get the text of the comment in the provided line
+    pub fn get(&self, line: usize, comment_type: CommentType) -> Option<&String> {
+        self.comments_of(comment_type).get(&line)
+    }
+
+    /// Insert a comment, returns the previous text of the line if any
+    pub fn insert(&mut self, line: usize, comment_type: CommentType, text: String) -> Option<String> {
+        self.comments_of_mut(comment_type).insert(line, text)
+    }
+
+    /// Remove the comment of the provided line, returns its text if any
+    pub fn remove(&mut self, line: usize, comment_type: CommentType) -> Option<String> {
+        self.comments_of_mut(comment_type).remove(&line)
+    }
+
+    /// Line numbers and types of the comments, ordered by line number
+    pub fn keys(&self) -> Vec<(usize, CommentType)> {
+        let mut keys: Vec<(usize, CommentType)> = self
+            .single_comments
+            .keys()
+            .map(|line| (*line, CommentType::Single))
+            .chain(self.multiline_comments.keys().map(|line| (*line, CommentType::Multi)))
+            .collect();
+
+        keys.sort_by_key(|(line, _)| *line);
+        keys
+    }
+
+    /// Number of comments in the collection
+    pub fn len(&self) -> usize {
+        self.single_comments.len() + self.multiline_comments.len()
+    }
+
+    /// Whether the collection has no comments
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Split the collection into batches ordered by line number
+    ///
+    /// # Params
+    /// * `max_chars`: Approximate budget of characters for each batch, a comment
+    ///   longer than the budget gets its own batch
+    ///
+    /// # Returns
+    /// * The batches, each one keeping the original line numbers
+    pub fn chunks(&self, max_chars: usize) -> Vec<CommentCollection> {
+        let mut comments = self.to_comments();
+        comments.sort_by_key(|c| c.line);
+
+        let mut chunks = Vec::new();
+        let mut current = Vec::new();
+        let mut size = 0;
+
+        for comment in comments {
+            // Text plus the line key and the JSON syntax around it
+            let comment_size = comment.text.len() + 16;
+
+            if !current.is_empty() && size + comment_size > max_chars {
+                chunks.push(CommentCollection::from_comments(std::mem::take(&mut current)));
+                size = 0;
+            }
+
+            size += comment_size;
+            current.push(comment);
+        }
+
+        if !current.is_empty() {
+            chunks.push(CommentCollection::from_comments(current));
+        }
+
+        chunks
+    }
+
+    /// Merge the comments of another collection into this one
+    pub fn merge(&mut self, other: CommentCollection) {
+        self.single_comments.extend(other.single_comments);
+        self.multiline_comments.extend(other.multiline_comments);
+    }
+
+    /// Mutable access to the text of every comment in the collection
+    pub fn texts_mut(&mut self) -> impl Iterator<Item = &mut String> {
+        self.single_comments
+            .values_mut()
+            .chain(self.multiline_comments.values_mut())
+    }
+
     pub fn to_comments(&self) -> Vec<Comment> {
         let mut comments: Vec<Comment> = vec![];
         for (line, text) in self.single_comments.iter() {
             comments.push(Comment {
                 line: *line,
+                col: 0,
                 text: text.to_string(),
                 comment_type: CommentType::Single,
             });
@@ -83,6 +228,7 @@ impl CommentCollection {
         for (line, text) in self.multiline_comments.iter() {
             comments.push(Comment {
                 line: *line,
+                col: 0,
                 text: text.to_string(),
                 comment_type: CommentType::Multi,
             });
@@ -97,11 +243,18 @@ impl Comment {
     pub fn new(line: usize, text: String, comment_type: CommentType) -> Comment {
         Comment {
             line,
+            col: 0,
             text,
             comment_type,
         }
     }
 
+    /// Set the byte offset of the text in the line
+    pub fn with_col(mut self, col: usize) -> Comment {
+        self.col = col;
+        self
+    }
+
     /// Retrieve comments from provided text
     ///
     /// # Params
@@ -121,6 +274,7 @@ impl Comment {
     ) -> Result<ParseState, &'static str> {
         let mut comments = Vec::new();
         let mut lines_parsed = 0;
+        let mut unterminated = false;
 
         if lines.is_empty() {
             return Err("Empty input");
@@ -137,6 +291,7 @@ impl Comment {
                 if let Some(parse_state) = parse_multi_line_comment(language, lines, start_line) {
                     comments.extend(parse_state.comments);
                     lines_parsed = parse_state.lines_parsed;
+                    unterminated = parse_state.unterminated;
                 }
             }
         }
@@ -144,6 +299,7 @@ impl Comment {
         Ok(ParseState {
             comments,
             lines_parsed,
+            unterminated,
         })
     }
 }
@@ -158,22 +314,237 @@ impl Comment {
 /// # Returns
 /// * [`Comment`] instance if comment has been parsed or `None`
 fn parse_single_line_comment(language: &Language, line: &str, line_number: usize) -> Option<Comment> {
-    if let Some(pos) = line.find(&language.comment_symbol) {
-        let comment_text = line[pos + language.comment_symbol.len()..].trim();
-
-        // Ensure that the quantity of quotes is not odd,
-        // that could indicate that the symbol is enclosed in quotes
-        let before = &line[..pos];
-        let quotes = before.chars().filter(|&c| c == '"' || c == '\'').count();
-
-        if !comment_text.is_empty() && quotes % 2 == 0 {
-            return Some(Comment::new(
-                line_number,
-                comment_text.to_string(),
-                CommentType::Single,
-            ));
+    // Take the first occurrence of the symbol that is not enclosed in quotes,
+    // e.g. in `fmt.Println("http://x") // print` the first `//` is part of a string
+    let (pos, symbol) = language.find_comment_symbol(line)?;
+
+    // Slice with `get` to never panic on a non char boundary
+    let comment_text = strip_doc_marker(symbol, line.get(pos + symbol.len()..)?).trim();
+
+    if !comment_text.is_empty() {
+        return Some(
+            Comment::new(line_number, comment_text.to_string(), CommentType::Single)
+                .with_col(offset_in(line, comment_text)),
+        );
+    }
+    None
+}
+
+/// Doc comment markers that can follow a comment symbol, e.g. `///`, `//!`, `/**` and `(**`
+const DOC_MARKERS: &[(&str, &[char])] = &[("//", &['/', '!']), ("/*", &['*', '!']), ("(*", &['*'])];
+
+/// Strip the doc comment marker that follows a comment symbol, if any
+///
+/// # Params
+/// * `symbol`: Comment symbol that precedes the text
+/// * `text`: Text after the comment symbol
+///
+/// # Returns
+/// * The text without the doc marker, e.g. `* Item` for `/// * Item` or `Header` for `;;; Header`
+fn strip_doc_marker<'a>(symbol: &str, text: &'a str) -> &'a str {
+    // Lisp and Erlang comments repeat the symbol by convention, e.g. `;;;` and `%%`
+    if symbol == ";" || symbol == "%" {
+        return text.trim_start_matches(symbol);
+    }
+
+    let Some((_, markers)) = DOC_MARKERS.iter().find(|(s, _)| *s == symbol) else {
+        return text;
+    };
+
+    // `/**/` and `(**)` are empty comments, not doc comments
+    if text.starts_with("*/") || text.starts_with("*)") {
+        return text;
+    }
+
+    match text.strip_prefix(*markers) {
+        // Doxygen member comments document the previous member, e.g. `//!< Sum`
+        Some(stripped) => stripped.strip_prefix('<').unwrap_or(stripped),
+        None => text,
+    }
+}
+
+/// Strip the asterisk that aligns the lines of a block comment, e.g. ` * text`
+///
+/// # Params
+/// * `text`: Line of the comment body
+///
+/// # Returns
+/// * The text after the asterisk and one space, or `text` if there is no margin
+fn strip_margin(text: &str) -> &str {
+    match text.trim_start().strip_prefix('*') {
+        // The close symbol, not a margin
+        Some(rest) if rest.starts_with('/') => text,
+        Some(rest) => rest.strip_prefix(' ').unwrap_or(rest),
+        None => text,
+    }
+}
+
+/// Find the first occurrence of `symbol` in `line` that is not enclosed in quotes
+///
+/// The line is scanned tracking the string literals: a string ends with the same
+/// quote that opened it, and quotes escaped with a backslash are ignored, e.g.
+/// `"he said \"hi\" # not a comment"` or `"it's # not a comment"`.
+///
+/// # Params
+/// * `line`: Line to search
+/// * `symbol`: Symbol to find
+///
+/// # Returns
+/// * The byte position of the symbol or `None`
+fn find_unquoted(line: &str, symbol: &str) -> Option<usize> {
+    let mut active_quote: Option<char> = None;
+    let mut escaped = false;
+
+    for (pos, c) in line.char_indices() {
+        if let Some(quote) = active_quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == quote {
+                active_quote = None;
+            }
+            continue;
+        }
+
+        // Check the symbol first, it can start with a quote, e.g. `"""` in Python
+        if line[pos..].starts_with(symbol) {
+            return Some(pos);
+        }
+
+        if c == '"' || c == '\'' {
+            active_quote = Some(c);
+        }
+    }
+
+    None
+}
+
+/// Find the `"` that begins a Vimscript comment
+///
+/// A `"` begins a comment at the start of a line, or after whitespace when no other
+/// `"` follows it in the line, otherwise it opens a string, e.g. `echo "hi" " Greet`.
+/// Single quoted strings never contain a comment, e.g. `let s = 'a " b'`.
+///
+/// # Params
+/// * `line`: Line to search
+///
+/// # Returns
+/// * The byte position of the comment symbol or `None`
+fn find_vim_comment(line: &str) -> Option<usize> {
+    let indent = leading_whitespace(line).len();
+    if line[indent..].starts_with('"') {
+        return Some(indent);
+    }
+
+    let mut active_quote: Option<char> = None;
+    let mut escaped = false;
+
+    for (pos, c) in line.char_indices() {
+        if let Some(quote) = active_quote {
+            // Only the double quoted strings have escape sequences, `''` in a single
+            // quoted string closes and reopens it, which gives the same result
+            if escaped {
+                escaped = false;
+            } else if c == '\\' && quote == '"' {
+                escaped = true;
+            } else if c == quote {
+                active_quote = None;
+            }
+            continue;
+        }
+
+        match c {
+            '"' if line[..pos].ends_with(char::is_whitespace) && !line[pos + 1..].contains('"') => return Some(pos),
+            '"' | '\'' => active_quote = Some(c),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Find the earliest unquoted occurrence of any of the provided symbols
+///
+/// When two symbols start at the same position the longest one wins,
+/// e.g. `--[[` is preferred over `--`.
+///
+/// # Params
+/// * `line`: Line to search
+/// * `symbols`: Candidate symbols
+///
+/// # Returns
+/// * The byte position and the matched symbol or `None`
+fn find_first_unquoted<'a>(
+    line: &str,
+    symbols: impl Iterator<Item = &'a str>,
+) -> Option<(usize, &'a str)> {
+    symbols
+        .filter(|symbol| !symbol.is_empty())
+        .filter_map(|symbol| find_unquoted(line, symbol).map(|pos| (pos, symbol)))
+        .min_by_key(|&(pos, symbol)| (pos, std::cmp::Reverse(symbol.len())))
+}
+
+/// Byte offset of a slice of the line, e.g. the text of a comment
+fn offset_in(line: &str, text: &str) -> usize {
+    text.as_ptr() as usize - line.as_ptr() as usize
+}
+
+/// Leading whitespace of a line
+pub fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// Remove up to `indent` leading whitespace characters from a line
+fn strip_indent(line: &str, indent: usize) -> &str {
+    let mut start = 0;
+    for (i, c) in line.char_indices().take(indent) {
+        if !c.is_whitespace() {
+            break;
+        }
+        start = i + c.len_utf8();
+    }
+
+    &line[start..]
+}
+
+/// Find the close symbol that ends a multi-line comment
+///
+/// For languages with nested comments every open symbol increases the depth and
+/// every close symbol decreases it, the comment only ends when the depth reaches zero.
+///
+/// # Params
+/// * `language`: [`Language`] instance of the text's language
+/// * `text`: Text inside the comment to search
+/// * `open`: Open symbol of the comment
+/// * `close`: Close symbol of the comment
+/// * `depth`: Current depth of nesting, updated with the symbols found in `text`
+///
+/// # Returns
+/// * The byte position of the close symbol that ends the comment or `None`
+fn find_ml_close(language: &Language, text: &str, open: &str, close: &str, depth: &mut usize) -> Option<usize> {
+    if !language.nested_ml_comments {
+        return text.find(close);
+    }
+
+    let mut pos = 0;
+    while pos < text.len() {
+        let rest = &text[pos..];
+
+        if rest.starts_with(close) {
+            *depth -= 1;
+            if *depth == 0 {
+                return Some(pos);
+            }
+            pos += close.len();
+        } else if rest.starts_with(open) {
+            *depth += 1;
+            pos += open.len();
+        } else {
+            pos += rest.chars().next().map_or(1, char::len_utf8);
         }
     }
+
     None
 }
 
@@ -191,20 +562,49 @@ fn parse_multi_line_comment(language: &Language, lines: &[String], start_line: u
     let comment_type = CommentType::Multi;
 
     let first_line = &lines[0];
-    if let Some(start_pos) = first_line.find(&language.ml_comment_symbol) {
+
+    if let Some((start_pos, open)) = language.find_ml_open(first_line) {
+        // The close symbol must belong to the same pair as the open symbol
+        let close = language.ml_comment_close(open)?;
+
         let mut lines_parsed = 1; // Always parse almost one line
-        let mut text = first_line[start_pos + language.ml_comment_symbol.len()..].trim();
+        let mut after_open = first_line.get(start_pos + open.len()..)?;
+        if language.ml_comments_at_line_start {
+            // The command word is part of the marker, e.g. `head1` in `=head1 NAME`
+            after_open = after_open.trim_start_matches(char::is_alphanumeric);
+        }
+        let mut text = strip_doc_marker(open, after_open).trim();
+
+        // C-family blocks usually align each line with an asterisk, e.g. `/**` and ` * text`,
+        // it is detected from the doc marker or from the margin of the first continuation line
+        let is_doc_block = !after_open.starts_with("*/") && after_open.starts_with(['*', '!']);
+        let next_has_margin = lines
+            .get(1)
+            .is_some_and(|line| strip_margin(line).len() != line.len() || line.trim() == "*");
+        let has_margin = open == "/*" && (is_doc_block || next_has_margin);
+
+        // Depth of nested comments, only tracked if the language allows nesting
+        let mut depth = 1;
+
+        // A block that is a string, e.g. a heredoc that is not a doc, is parsed to skip its lines
+        let is_comment = language.is_ml_comment_at(first_line, start_pos);
 
         // Handle single-line multi-line comment for example in `python`:
         // """Single line comment in Python using multi-line symbol"""
-        if let Some(end_pos) = text.find(&language.ml_comment_symbol_close) {
+        // The close is searched after the end of the open symbol, so when both symbols are
+        // identical the open one is never taken as the close, e.g. `"""x"""y"""z"""` is `x`
+        if let Some(end_pos) = find_ml_close(language, text, open, close, &mut depth) {
             text = text[..end_pos].trim();
-            if !text.is_empty() {
-                comments.push(Comment::new(start_line, text.to_string(), comment_type));
+            if !text.is_empty() && is_comment {
+                comments.push(
+                    Comment::new(start_line, text.to_string(), comment_type)
+                        .with_col(offset_in(first_line, text)),
+                );
             }
             return Some(ParseState {
                 comments,
                 lines_parsed,
+                unterminated: false,
             });
         }
 
@@ -214,59 +614,393 @@ fn parse_multi_line_comment(language: &Language, lines: &[String], start_line: u
             // In case of begin with symbol but has line breaks, like:
             // """Comment in multi-line
             // using symbol in same line"""
-            comments.push(Comment::new(start_line, text.to_string(), comment_type));
+            comments.push(
+                Comment::new(start_line, text.to_string(), comment_type)
+                    .with_col(offset_in(first_line, text)),
+            );
         }
 
+        // Indentation of the comment itself, the extra indentation of the body is
+        // kept because it can be meaningful, e.g. a code sample in a docstring
+        let base_indent = leading_whitespace(first_line).chars().count();
+        let mut closed = false;
+        let mut in_doctest = false;
+
         for (i, line) in lines[1..].iter().enumerate() {
             lines_parsed += 1;
-            let text = line.trim().to_string();
+            let mut text = strip_indent(line, base_indent).trim_end();
+            if has_margin {
+                text = strip_margin(text);
+            }
+            let col = offset_in(line, text);
+            let text = text.to_string();
+
+            if let Some(prompt) = &language.doctest_prompt {
+                in_doctest = (in_doctest || text.trim_start().starts_with(prompt.as_str())) && !text.trim().is_empty();
+            }
 
             // Last line
-            if let Some(end_pos) = text.find(&language.ml_comment_symbol_close) {
-                let text = text[..end_pos].trim().to_string();
+            if let Some(end_pos) = find_ml_close(language, &text, open, close, &mut depth) {
+                let text = text[..end_pos].trim_end().to_string();
                 if !text.is_empty() {
-                    comments.push(Comment::new(start_line + i + 1, text, comment_type));
+                    comments.push(Comment::new(start_line + i + 1, text, comment_type).with_col(col));
                 }
+                closed = true;
                 break;
             }
 
-            comments.push(Comment::new(start_line + i + 1, text, comment_type));
+            // Blank lines are not sent to the model, they are kept as they are in the text
+            if !text.is_empty() && !in_doctest {
+                comments.push(Comment::new(start_line + i + 1, text, comment_type).with_col(col));
+            }
+        }
+
+        // The comment is never closed, e.g. a truncated file, only the open line is taken
+        // as a comment so the code below it is not sent to the model
+        if !closed {
+            comments.retain(|comment| is_comment && comment.line == start_line);
+            return Some(ParseState {
+                comments,
+                lines_parsed: 1,
+                unterminated: true,
+            });
+        }
+
+        if !is_comment {
+            comments.clear();
         }
 
         return Some(ParseState {
             comments,
             lines_parsed,
+            unterminated: false,
         });
     }
     // If the opening or closing symbol is not found returns None
     None
 }
 
+/// How the comment symbols are told apart from the string literals
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuoteRule {
+    /// A symbol enclosed in `"` or `'` quotes is part of a string
+    #[default]
+    Standard,
+    /// Vimscript, where `"` is both the comment symbol and the string delimiter
+    Vim,
+}
+
 /// Language parameters
+#[derive(Debug, Clone)]
 pub struct Language {
     pub name: String,
-    pub comment_symbol: String,
-    pub ml_comment_symbol: String,
-    pub ml_comment_symbol_close: String,
+    /// Short names accepted in place of the name, e.g. `py` for Python
+    pub aliases: Vec<String>,
+    /// Single line comment symbols, e.g. `#` and `//` for PHP
+    pub comment_symbols: Vec<String>,
+    /// Multi-line comment symbols as `(open, close)` pairs
+    pub ml_comment_symbols: Vec<(String, String)>,
+    /// Whether multi-line comments can be nested, e.g. `{- outer {- inner -} -}` in Haskell
+    pub nested_ml_comments: bool,
+    /// Whether every non-empty line is a comment, e.g. plain text
+    pub whole_line_is_comment: bool,
+    /// Whether the text has Markdown code that is never checked, the inline spans, and
+    /// the fenced blocks when every line is a comment
+    pub markdown_code: bool,
+    /// Whether the doc comments have XML tags that are never checked, e.g. `<summary>` in C#
+    pub xml_doc_tags: bool,
+    /// How the comment symbols are told apart from the string literals
+    pub quote_rule: QuoteRule,
+    /// Whether multi-line comments only open at the beginning of a line, followed by
+    /// a command word that is part of the marker, e.g. `=pod` or `=head1` in Perl
+    pub ml_comments_at_line_start: bool,
+    /// Attributes that a multi-line block must follow to be a comment, the other blocks are
+    /// strings that are skipped, e.g. `@doc """` in Elixir, every block is a comment when empty
+    pub ml_comment_prefixes: Vec<String>,
+    /// Prompt of the doctests of the doc blocks, e.g. `iex>`, a doctest is code up to a blank line
+    pub doctest_prompt: Option<String>,
 }
 
 impl Language {
+    /// Create a new [`Language`] with one single line symbol and one multi-line pair,
+    /// an empty symbol means that the language doesn't have that comment form
+    pub fn new(
+        name: &str,
+        comment_symbol: &str,
+        ml_comment_symbol: &str,
+        ml_comment_symbol_close: &str,
+    ) -> Language {
+        let mut comment_symbols = Vec::new();
+        if !comment_symbol.is_empty() {
+            comment_symbols.push(comment_symbol.to_string());
+        }
+
+        let mut ml_comment_symbols = Vec::new();
+        if !ml_comment_symbol.is_empty() {
+            ml_comment_symbols.push((
+                ml_comment_symbol.to_string(),
+                ml_comment_symbol_close.to_string(),
+            ));
+        }
+
+        Language {
+            name: name.to_string(),
+            aliases: Vec::new(),
+            comment_symbols,
+            ml_comment_symbols,
+            nested_ml_comments: false,
+            whole_line_is_comment: false,
+            markdown_code: false,
+            xml_doc_tags: false,
+            quote_rule: QuoteRule::default(),
+            ml_comments_at_line_start: false,
+            ml_comment_prefixes: Vec::new(),
+            doctest_prompt: None,
+        }
+    }
+
+    /// Add short names accepted in place of the name
+    pub fn with_aliases(mut self, aliases: &[&str]) -> Language {
+        self.aliases.extend(aliases.iter().map(|alias| alias.to_string()));
+        self
+    }
+
+    /// Treat every non-empty line as a single line comment
+    pub fn with_whole_line_comments(mut self) -> Language {
+        self.whole_line_is_comment = true;
+        self
+    }
+
+    /// Tell the comment symbols apart from the string literals with a specific rule
+    pub fn with_quote_rule(mut self, quote_rule: QuoteRule) -> Language {
+        self.quote_rule = quote_rule;
+        self
+    }
+
+    /// Skip the Markdown code, fenced blocks and inline spans
+    pub fn with_markdown_code(mut self) -> Language {
+        self.markdown_code = true;
+        self
+    }
+
+    /// Skip the XML tags of the doc comments, e.g. `<param name="value">`
+    pub fn with_xml_doc_tags(mut self) -> Language {
+        self.xml_doc_tags = true;
+        self
+    }
+
+    /// Only take the multi-line blocks that follow one of these attributes as comments
+    pub fn with_ml_comment_prefixes(mut self, prefixes: &[&str]) -> Language {
+        self.ml_comment_prefixes = prefixes.iter().map(|prefix| prefix.to_string()).collect();
+        self
+    }
+
+    /// Skip the doctests of the doc blocks, the lines from the prompt up to a blank line
+    pub fn with_doctest_prompt(mut self, prompt: &str) -> Language {
+        self.doctest_prompt = Some(prompt.to_string());
+        self
+    }
+
+    /// Whether a multi-line block opened at `pos` is a comment, see [`Language::ml_comment_prefixes`]
+    ///
+    /// A sigil between the attribute and the block is allowed, e.g. `@doc ~S"""`.
+    pub fn is_ml_comment_at(&self, line: &str, pos: usize) -> bool {
+        if self.ml_comment_prefixes.is_empty() {
+            return true;
+        }
+
+        let before = line.get(..pos).unwrap_or_default().trim();
+        let before = before.strip_suffix("~S").or_else(|| before.strip_suffix("~s")).unwrap_or(before);
+        self.ml_comment_prefixes.iter().any(|prefix| prefix == before.trim_end())
+    }
+
+    /// Only open the multi-line comments at the beginning of a line
+    pub fn with_ml_comments_at_line_start(mut self) -> Language {
+        self.ml_comments_at_line_start = true;
+        self
+    }
+
+    /// Allow the multi-line comments to be nested
+    pub fn with_nested_ml_comments(mut self) -> Language {
+        self.nested_ml_comments = true;
+        self
+    }
+
+    /// Add an alternative single line comment symbol
+    pub fn with_comment_symbol(mut self, symbol: &str) -> Language {
+        self.comment_symbols.push(symbol.to_string());
+        self
+    }
+
+    /// Add an alternative multi-line comment symbols pair
+    pub fn with_ml_comment_symbols(mut self, open: &str, close: &str) -> Language {
+        self.ml_comment_symbols.push((open.to_string(), close.to_string()));
+        self
+    }
+
+    /// Find a supported [`Language`] by its name or an alias, case-insensitive
+    ///
+    /// # Params
+    /// * `name`: Name or alias of the language, e.g. `python` or `py`
+    ///
+    /// # Returns
+    /// * The [`Language`] or an [`UnsupportedLanguageError`] listing the supported languages
+    pub fn from_name(name: &str) -> Result<Language, UnsupportedLanguageError> {
+        let lowered = name.trim().to_lowercase();
+        let supported_languages = init_supported_languages();
+
+        let supported: Vec<String> = supported_languages
+            .languages
+            .iter()
+            .map(|l| l.name.clone())
+            .collect();
+
+        supported_languages
+            .languages
+            .into_iter()
+            .find(|l| l.name == lowered || l.aliases.contains(&lowered))
+            .ok_or(UnsupportedLanguageError {
+                name: name.to_string(),
+                supported,
+            })
+    }
+
+    /// Find a supported [`Language`] by a file extension, e.g. `rs` or `.py`
+    ///
+    /// # Params
+    /// * `ext`: File extension, with or without the leading dot
+    ///
+    /// # Returns
+    /// * The [`Language`] or `None` if the extension is unknown
+    pub fn from_extension(ext: &str) -> Option<Language> {
+        let ext = ext.trim().trim_start_matches('.').to_lowercase();
+
+        let name = match ext.as_str() {
+            "py" | "pyi" => "python",
+            "js" | "mjs" | "cjs" | "jsx" | "ts" | "tsx" | "mts" | "cts" => "javascript",
+            "rs" => "rust",
+            "css" => "css",
+            "lua" => "lua",
+            "c" | "h" => "c",
+            "sh" | "bash" => "bash",
+            "go" => "go",
+            "rb" => "ruby",
+            "hs" | "lhs" => "haskell",
+            "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => "cpp",
+            "html" | "htm" => "html",
+            "sql" => "sql",
+            "yaml" | "yml" => "yaml",
+            "toml" => "toml",
+            "pl" | "pm" | "pod" => "perl",
+            "clj" | "cljs" | "cljc" | "edn" => "clojure",
+            "ps1" | "psm1" | "psd1" => "powershell",
+            "ml" | "mli" => "ocaml",
+            "fs" | "fsi" | "fsx" => "fsharp",
+            "lisp" | "lsp" | "cl" | "el" | "scm" | "ss" => "lisp",
+            "vim" => "vim",
+            "ex" | "exs" => "elixir",
+            "swift" => "swift",
+            "cs" => "csharp",
+            "kt" | "kts" => "kotlin",
+            "scala" | "sc" => "scala",
+            "groovy" | "gvy" | "gradle" => "groovy",
+            "erl" | "hrl" => "erlang",
+            "f90" | "f95" | "f03" | "f08" => "fortran",
+            "asm" | "s" | "nasm" => "asm",
+            "txt" => "text",
+            "md" | "markdown" => "markdown",
+            _ => return None,
+        };
+
+        Language::from_name(name).ok()
+    }
+
+    /// Find the first single line comment symbol that is not enclosed in quotes
+    ///
+    /// # Returns
+    /// * The byte position and the matched symbol or `None`
+    pub fn find_comment_symbol(&self, line: &str) -> Option<(usize, &str)> {
+        match self.quote_rule {
+            QuoteRule::Standard => find_first_unquoted(line, self.comment_symbols.iter().map(String::as_str)),
+            QuoteRule::Vim => find_vim_comment(line).zip(self.comment_symbols.first().map(String::as_str)),
+        }
+    }
+
+    /// Find the open symbol of a multi-line comment that is not enclosed in quotes
+    ///
+    /// # Returns
+    /// * The byte position and the matched symbol or `None`
+    pub fn find_ml_open(&self, line: &str) -> Option<(usize, &str)> {
+        let open_symbols = self.ml_comment_symbols.iter().map(|(open, _)| open.as_str());
+
+        if !self.ml_comments_at_line_start {
+            return find_first_unquoted(line, open_symbols);
+        }
+
+        // A command word must follow the symbol, e.g. `=pod`, so `= 5;` is not a comment
+        open_symbols
+            .filter(|symbol| !symbol.is_empty())
+            .find(|symbol| {
+                line.strip_prefix(*symbol)
+                    .is_some_and(|rest| symbol.ends_with(char::is_alphanumeric) || rest.starts_with(char::is_alphabetic))
+            })
+            .map(|symbol| (0, symbol))
+    }
+
+    /// Whether the line is a shebang, e.g. `#!/usr/bin/env python3`,
+    /// only for languages where `#` starts a comment
+    pub fn is_shebang(&self, line: &str) -> bool {
+        line.starts_with("#!") && self.comment_symbols.iter().any(|symbol| symbol == "#")
+    }
+
+    /// Get the close symbol that matches the provided multi-line open symbol
+    pub fn ml_comment_close(&self, open: &str) -> Option<&str> {
+        self.ml_comment_symbols
+            .iter()
+            .find(|(o, _)| o == open)
+            .map(|(_, close)| close.as_str())
+    }
+
     /// Get comment type depending on symbol
     /// by default returns single line comment
     pub fn get_comment_type(&self, line: &str) -> CommentType {
-        // First check for multi-line comment
-        if let Some(ml_pos) = line.find(&self.ml_comment_symbol) {
-            // Make sure it's not inside a string
-            let before = &line[..ml_pos];
-            let quotes = before.chars().filter(|&c| c == '"' || c == '\'').count();
-            if quotes % 2 == 0 {
-                return CommentType::Multi;
-            }
+        // Languages without single line comments, e.g. HTML
+        if self.comment_symbols.is_empty() && !self.ml_comment_symbols.is_empty() {
+            return CommentType::Multi;
+        }
+
+        // The first comment token outside the strings wins, e.g. the `/*` in `x // a /* b */`
+        // is part of the line comment, on a tie the block comment wins, e.g. `--[[` over `--`
+        match (self.find_ml_open(line), self.find_comment_symbol(line)) {
+            (Some((ml_pos, _)), Some((pos, _))) if pos < ml_pos => CommentType::Single,
+            (Some(_), _) => CommentType::Multi,
+            (None, _) => CommentType::Single,
         }
-        CommentType::Single
     }
 }
 
+/// Error returned when a language is not supported
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsupportedLanguageError {
+    /// The attempted language name
+    pub name: String,
+    /// Names of the supported languages
+    pub supported: Vec<String>,
+}
+
+impl Display for UnsupportedLanguageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Language not supported: {}. Supported languages: {}",
+            self.name,
+            self.supported.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedLanguageError {}
+
 /// Languages parameters configuration
 pub struct SupportedLanguages {
     pub languages: Vec<Language>,
@@ -276,75 +1010,119 @@ pub struct SupportedLanguages {
 pub fn init_supported_languages() -> SupportedLanguages {
     let mut languages = Vec::new();
 
-    let python = Language {
-        name: "python".to_string(),
-        comment_symbol: "#".to_string(),
-        ml_comment_symbol: "\"\"\"".to_string(),
-        ml_comment_symbol_close: "\"\"\"".to_string(),
-    };
+    let python = Language::new("python", "#", "\"\"\"", "\"\"\"")
+        .with_ml_comment_symbols("'''", "'''")
+        .with_aliases(&["py"]);
 
-    let javascript = Language {
-        name: "javascript".to_string(),
-        comment_symbol: "//".to_string(),
-        ml_comment_symbol: "/*".to_string(),
-        ml_comment_symbol_close: "*/".to_string(),
-    };
+    // TypeScript shares the comment syntax of JavaScript
+    let javascript =
+        Language::new("javascript", "//", "/*", "*/").with_aliases(&["js", "ts", "typescript"]);
 
-    let rust = Language {
-        name: "rust".to_string(),
-        comment_symbol: "//".to_string(),
-        ml_comment_symbol: "/*".to_string(),
-        ml_comment_symbol_close: "*/".to_string(),
-    };
+    let rust = Language::new("rust", "//", "/*", "*/")
+        .with_nested_ml_comments()
+        .with_aliases(&["rs"]);
 
-    let css = Language {
-        name: "css".to_string(),
-        comment_symbol: "//".to_string(),
-        ml_comment_symbol: "/*".to_string(),
-        ml_comment_symbol_close: "*/".to_string(),
-    };
+    let css = Language::new("css", "//", "/*", "*/");
 
-    let lua = Language {
-        name: "lua".to_string(),
-        comment_symbol: "--".to_string(),
-        ml_comment_symbol: "--[[".to_string(),
-        ml_comment_symbol_close: "]]".to_string(),
-    };
+    let lua = Language::new("lua", "--", "--[[", "]]");
 
-    let c = Language {
-        name: "c".to_string(),
-        comment_symbol: "//".to_string(),
-        ml_comment_symbol: "/*".to_string(),
-        ml_comment_symbol_close: "*/".to_string(),
-    };
+    let c = Language::new("c", "//", "/*", "*/");
 
-    let bash = Language {
-        name: "bash".to_string(),
-        comment_symbol: "#".to_string(),
-        ml_comment_symbol: ": '".to_string(),
-        ml_comment_symbol_close: "'".to_string(),
-    };
+    let cpp = Language::new("cpp", "//", "/*", "*/").with_aliases(&["c++"]);
 
-    let go = Language {
-        name: "go".to_string(),
-        comment_symbol: "//".to_string(),
-        ml_comment_symbol: "/*".to_string(),
-        ml_comment_symbol_close: "*/".to_string(),
-    };
+    let bash = Language::new("bash", "#", ": '", "'").with_aliases(&["sh", "shell"]);
 
-    let ruby = Language {
-        name: "ruby".to_string(),
-        comment_symbol: "#".to_string(),
-        ml_comment_symbol: "=begin".to_string(),
-        ml_comment_symbol_close: "=end".to_string(),
-    };
+    let go = Language::new("go", "//", "/*", "*/").with_aliases(&["golang"]);
 
-    let text = Language {
-        name: "text".to_string(),
-        comment_symbol: "".to_string(),
-        ml_comment_symbol: "".to_string(),
-        ml_comment_symbol_close: "".to_string(),
-    };
+    let ruby = Language::new("ruby", "#", "=begin", "=end").with_aliases(&["rb"]);
+
+    let haskell = Language::new("haskell", "--", "{-", "-}")
+        .with_nested_ml_comments()
+        .with_aliases(&["hs"]);
+
+    let html = Language::new("html", "", "<!--", "-->");
+
+    let sql = Language::new("sql", "--", "/*", "*/");
+
+    // `#` is an operator in other dialects, e.g. PostgreSQL, so only MySQL uses it as a comment
+    let mysql = Language::new("mysql", "--", "/*", "*/").with_comment_symbol("#");
+
+    let yaml = Language::new("yaml", "#", "", "").with_aliases(&["yml"]);
+
+    let toml = Language::new("toml", "#", "", "");
+
+    // POD blocks open with any command at the beginning of a line, e.g. `=pod` or `=head1`
+    let perl = Language::new("perl", "#", "=", "=cut")
+        .with_ml_comments_at_line_start()
+        .with_aliases(&["pl"]);
+
+    let clojure = Language::new("clojure", ";", "", "").with_aliases(&["clj"]);
+
+    let lisp = Language::new("lisp", ";", "", "").with_aliases(&["scheme", "elisp"]);
+
+    let powershell = Language::new("powershell", "#", "<#", "#>").with_aliases(&["ps1", "pwsh"]);
+
+    // OCaml has no line comments, only nested `(* *)` blocks
+    let ocaml = Language::new("ocaml", "", "(*", "*)").with_nested_ml_comments();
+
+    let fsharp = Language::new("fsharp", "//", "(*", "*)")
+        .with_nested_ml_comments()
+        .with_aliases(&["f#"]);
+
+    // Free form Fortran, the fixed form `C` comments of `.f` files are not supported
+    let fortran = Language::new("fortran", "!", "", "").with_aliases(&["f90"]);
+
+    // `;` for NASM and MASM, `#` for GAS
+    let asm = Language::new("asm", ";", "", "")
+        .with_comment_symbol("#")
+        .with_aliases(&["assembly", "nasm", "gas"]);
+
+    // `"` is both the comment symbol and the string delimiter
+    let vim = Language::new("vim", "\"", "", "")
+        .with_quote_rule(QuoteRule::Vim)
+        .with_aliases(&["vimscript", "viml"]);
+
+    // The doc comments are Markdown, the inline code is never checked
+    let swift = Language::new("swift", "//", "/*", "*/")
+        .with_nested_ml_comments()
+        .with_markdown_code();
+
+    // The `///` doc comments are XML, the tags are never checked
+    let csharp = Language::new("csharp", "//", "/*", "*/")
+        .with_xml_doc_tags()
+        .with_aliases(&["cs", "c#"]);
+
+    // KDoc and Scaladoc are Markdown, the `@param` and `@return` tags are kept as they are
+    let kotlin = Language::new("kotlin", "//", "/*", "*/")
+        .with_nested_ml_comments()
+        .with_markdown_code()
+        .with_aliases(&["kt", "kts"]);
+
+    let scala = Language::new("scala", "//", "/*", "*/")
+        .with_nested_ml_comments()
+        .with_markdown_code();
+
+    // Groovydoc is HTML like Javadoc, the blocks are not nested
+    let groovy = Language::new("groovy", "//", "/*", "*/").with_aliases(&["gradle"]);
+
+    // Only the `@moduledoc`, `@doc` and `@typedoc` heredocs are the documentation, the other
+    // heredocs are strings, e.g. a SQL query, and the `iex>` doctests are code
+    let elixir = Language::new("elixir", "#", "\"\"\"", "\"\"\"")
+        .with_ml_comment_prefixes(&["@moduledoc", "@doc", "@typedoc"])
+        .with_doctest_prompt("iex>")
+        .with_aliases(&["ex", "exs"]);
+
+    let erlang = Language::new("erlang", "%", "", "").with_aliases(&["erl"]);
+
+    let text = Language::new("text", "", "", "")
+        .with_whole_line_comments()
+        .with_aliases(&["txt"]);
+
+    // Markdown is prose, except for the code
+    let markdown = Language::new("markdown", "", "", "")
+        .with_whole_line_comments()
+        .with_markdown_code()
+        .with_aliases(&["md"]);
 
     languages.push(python);
     languages.push(javascript);
@@ -352,10 +1130,34 @@ pub fn init_supported_languages() -> SupportedLanguages {
     languages.push(css);
     languages.push(lua);
     languages.push(c);
+    languages.push(cpp);
     languages.push(bash);
     languages.push(go);
     languages.push(ruby);
+    languages.push(haskell);
+    languages.push(html);
+    languages.push(sql);
+    languages.push(mysql);
+    languages.push(yaml);
+    languages.push(toml);
+    languages.push(perl);
+    languages.push(clojure);
+    languages.push(lisp);
+    languages.push(powershell);
+    languages.push(ocaml);
+    languages.push(fsharp);
+    languages.push(fortran);
+    languages.push(asm);
+    languages.push(vim);
+    languages.push(swift);
+    languages.push(csharp);
+    languages.push(kotlin);
+    languages.push(scala);
+    languages.push(groovy);
+    languages.push(elixir);
+    languages.push(erlang);
     languages.push(text);
+    languages.push(markdown);
 
     SupportedLanguages { languages }
 }
@@ -363,19 +1165,299 @@ pub fn init_supported_languages() -> SupportedLanguages {
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[test]
+    fn test_comment_clone_eq() {
+        let comment = Comment::new(3, "A comment".to_string(), CommentType::Single);
+
+        let mut cloned = comment.clone();
+        assert_eq!(cloned, comment);
+
+        cloned.text = "Another comment".to_string();
+        assert_ne!(cloned, comment);
+        assert_ne!(Comment::new(3, "A comment".to_string(), CommentType::Multi), comment);
+    }
+
     #[test]
     fn test_get_comment_type() {
-        let language = Language {
-            name: "rust".to_string(),
-            comment_symbol: "//".to_string(),
-            ml_comment_symbol: "/*".to_string(),
-            ml_comment_symbol_close: "*/".to_string(),
-        };
+        let language = Language::new("rust", "//", "/*", "*/");
 
         let single_line = "let x = 5; // this is a comment";
         let multi_line = "/* this is a\nmulti-line comment */";
 
         assert_eq!(language.get_comment_type(single_line), CommentType::Single);
         assert_eq!(language.get_comment_type(multi_line), CommentType::Multi);
+
+        // The first comment token outside the strings wins
+        assert_eq!(language.get_comment_type("let x = 5; // a /* b */"), CommentType::Single);
+        assert_eq!(language.get_comment_type("let x = 5; // unclosed /* block"), CommentType::Single);
+        assert_eq!(language.get_comment_type("let a = 2 /* */ ; // c"), CommentType::Multi);
+        assert_eq!(language.get_comment_type(r#"let url = "a/*b"; // real comment"#), CommentType::Single);
+        assert_eq!(language.get_comment_type(r#"let url = "a//b"; /* real comment */"#), CommentType::Multi);
+
+        // On a tie the block comment wins
+        let lua = Language::from_name("lua").unwrap();
+        assert_eq!(lua.get_comment_type("x = 1 --[[ block ]]"), CommentType::Multi);
+        assert_eq!(lua.get_comment_type("x = 1 -- line [[ not a block ]]"), CommentType::Single);
+
+        // A block symbol in a line comment doesn't open a block that swallows the next lines
+        let lines: Vec<String> = ["x(); // see /* here", "y();", "// next */"].iter().map(|l| l.to_string()).collect();
+        let parse_state =
+            Comment::parse_comment(&language, &lines, 0, language.get_comment_type(&lines[0])).unwrap();
+        assert_eq!(parse_state.lines_parsed, 1);
+        assert_eq!(parse_state.comments[0].text, "see /* here");
+    }
+
+    #[test]
+    fn test_multiple_comment_symbols() {
+        let php = Language::new("php", "//", "/*", "*/").with_comment_symbol("#");
+
+        let comment = parse_single_line_comment(&php, "$x = 5; # hash comment", 0).unwrap();
+        assert_eq!(comment.text, "hash comment");
+
+        let comment = parse_single_line_comment(&php, "$x = 5; // slash comment # not a symbol", 0).unwrap();
+        assert_eq!(comment.text, "slash comment # not a symbol");
+    }
+
+    #[test]
+    fn test_symbols_inside_strings() {
+        let python = Language::from_name("python").unwrap();
+        let javascript = Language::from_name("javascript").unwrap();
+
+        // Escaped quotes don't end the string
+        assert!(parse_single_line_comment(&python, r#"x = "he said \"hi\" #not a comment""#, 0).is_none());
+        let comment = parse_single_line_comment(&python, r#"x = "he said \"hi\"" # real comment"#, 0).unwrap();
+        assert_eq!(comment.text, "real comment");
+
+        // Apostrophes inside a string of the other quote are not quotes
+        let comment = parse_single_line_comment(&javascript, r#"msg = "it's // here" // real comment"#, 0).unwrap();
+        assert_eq!(comment.text, "real comment");
+        let comment = parse_single_line_comment(&python, r##"s = 'say "#"' # it's fine"##, 0).unwrap();
+        assert_eq!(comment.text, "it's fine");
+
+        assert_eq!(python.get_comment_type(r#"s = "it's \"\"\" quoted""#), CommentType::Single);
+        assert_eq!(python.get_comment_type(r#"x = 5 """doc""""#), CommentType::Multi);
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let rust = Language::new("rust", "//", "/*", "*/").with_nested_ml_comments();
+
+        let comment = parse_single_line_comment(&rust, "/// Outer doc comment", 0).unwrap();
+        assert_eq!(comment.text, "Outer doc comment");
+
+        let comment = parse_single_line_comment(&rust, "//! Inner doc comment", 0).unwrap();
+        assert_eq!(comment.text, "Inner doc comment");
+
+        let lines = vec!["/** Block doc comment */".to_string()];
+        let parse_state = Comment::parse_comment(&rust, &lines, 0, CommentType::Multi).unwrap();
+        assert_eq!(parse_state.comments[0].text, "Block doc comment");
+
+        // Other symbols keep their text untouched
+        let python = Language::new("python", "#", "\"\"\"", "\"\"\"");
+        let comment = parse_single_line_comment(&python, "# ! Important", 0).unwrap();
+        assert_eq!(comment.text, "! Important");
+    }
+
+    #[test]
+    fn test_nested_ml_comments() {
+        let rust = Language::from_name("rust").unwrap();
+        assert!(rust.nested_ml_comments);
+
+        let lines = vec!["/* outer /* inner */ outer */".to_string(), "x = 5".to_string()];
+        let parse_state = Comment::parse_comment(&rust, &lines, 0, CommentType::Multi).unwrap();
+        assert_eq!(parse_state.lines_parsed, 1);
+        assert_eq!(parse_state.comments.len(), 1);
+        assert_eq!(parse_state.comments[0].text, "outer /* inner */ outer");
+
+        let lines: Vec<String> = ["/* outer", "/* inner */", "outer */", "let x = 5;"]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        let parse_state = Comment::parse_comment(&rust, &lines, 0, CommentType::Multi).unwrap();
+        assert_eq!(parse_state.lines_parsed, 3);
+        assert_eq!(parse_state.comments.len(), 3);
+        assert_eq!(parse_state.comments[2].text, "outer");
+
+        // Without nesting the comment ends at the first close symbol
+        let c = Language::from_name("c").unwrap();
+        let parse_state = Comment::parse_comment(&c, &lines, 0, CommentType::Multi).unwrap();
+        assert_eq!(parse_state.lines_parsed, 2);
+    }
+
+    #[test]
+    fn test_python_single_quotes_docstring() {
+        let python = init_supported_languages()
+            .languages
+            .into_iter()
+            .find(|l| l.name == "python")
+            .unwrap();
+
+        let lines: Vec<String> = ["'''", "Docstring with single quotes", "'''", "x = 5"]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+
+        assert_eq!(python.get_comment_type(&lines[0]), CommentType::Multi);
+
+        let parse_state = Comment::parse_comment(&python, &lines, 0, CommentType::Multi).unwrap();
+        assert_eq!(parse_state.lines_parsed, 3);
+        assert_eq!(parse_state.comments.len(), 1);
+        assert_eq!(parse_state.comments[0].text, "Docstring with single quotes");
+        assert_eq!(parse_state.comments[0].line, 1);
+    }
+
+    #[test]
+    fn test_python_identical_open_and_close() {
+        let python = Language::from_name("python").unwrap();
+
+        // The close is searched after the end of the open symbol, never at the open symbol itself
+        let lines = vec![r#""""x"""y"""z""""#.to_string(), "w = 1".to_string()];
+        assert_eq!(python.get_comment_type(&lines[0]), CommentType::Multi);
+
+        let parse_state = Comment::parse_comment(&python, &lines, 0, CommentType::Multi).unwrap();
+        assert_eq!(parse_state.lines_parsed, 1);
+        assert_eq!(parse_state.comments.len(), 1);
+        assert_eq!((parse_state.comments[0].col, parse_state.comments[0].text.as_str()), (3, "x"));
+
+        let lines = vec![r#"""" text """ more""" text"#.to_string()];
+        let parse_state = Comment::parse_comment(&python, &lines, 0, CommentType::Multi).unwrap();
+        assert_eq!(parse_state.lines_parsed, 1);
+        assert_eq!((parse_state.comments[0].col, parse_state.comments[0].text.as_str()), (4, "text"));
+
+        // An empty docstring has no text
+        let lines = vec![r#""""""""#.to_string()];
+        let parse_state = Comment::parse_comment(&python, &lines, 0, CommentType::Multi).unwrap();
+        assert_eq!(parse_state.lines_parsed, 1);
+        assert!(parse_state.comments.is_empty());
+    }
+
+    #[test]
+    fn test_comment_columns() {
+        let lines: Vec<String> = [
+            "let s = \"// no\"; // Inline note",
+            "int x = 5; /* init */ int y;",
+            "    /*",
+            "     * Body",
+            "     */",
+            "/// Doc café",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+
+        let rust = Language::from_name("rust").unwrap();
+        let col_of = |start: usize, comment_type: CommentType| {
+            let state = Comment::parse_comment(&rust, &lines[start..], start, comment_type).unwrap();
+            state
+                .comments
+                .iter()
+                .map(|c| (c.line, c.col, c.text.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(col_of(0, CommentType::Single), vec![(0, 20, "Inline note".to_string())]);
+        assert_eq!(col_of(1, CommentType::Multi), vec![(1, 14, "init".to_string())]);
+        assert_eq!(col_of(2, CommentType::Multi), vec![(3, 7, "Body".to_string())]);
+        assert_eq!(col_of(5, CommentType::Single), vec![(5, 4, "Doc café".to_string())]);
+
+        // The column points to the text in the line
+        assert!(lines[0][20..].starts_with("Inline note"));
+        assert!(lines[3][7..].starts_with("Body"));
+    }
+
+    #[test]
+    fn test_from_name() {
+        let language = Language::from_name("rust").unwrap();
+        assert_eq!(language.name, "rust");
+
+        let language = Language::from_name("Python").unwrap();
+        assert_eq!(language.name, "python");
+
+        let aliases = [
+            ("py", "python"),
+            ("rs", "rust"),
+            ("js", "javascript"),
+            ("ts", "javascript"),
+            ("sh", "bash"),
+            ("RB", "ruby"),
+            ("hs", "haskell"),
+            ("c++", "cpp"),
+            ("yml", "yaml"),
+            ("pwsh", "powershell"),
+            ("scheme", "lisp"),
+            ("c#", "csharp"),
+            ("kt", "kotlin"),
+            ("gradle", "groovy"),
+        ];
+        for (alias, name) in aliases {
+            assert_eq!(Language::from_name(alias).unwrap().name, name, "alias {}", alias);
+        }
+
+        let err = Language::from_name("cobol").unwrap_err();
+        assert_eq!(err.name, "cobol");
+        assert!(err.supported.contains(&"python".to_string()));
+        assert!(err.to_string().contains("cobol"));
+    }
+
+    #[test]
+    fn test_from_extension() {
+        assert_eq!(Language::from_extension("rs").unwrap().name, "rust");
+        assert_eq!(Language::from_extension(".py").unwrap().name, "python");
+        assert_eq!(Language::from_extension("mjs").unwrap().name, "javascript");
+        assert_eq!(Language::from_extension("ts").unwrap().name, "javascript");
+        assert_eq!(Language::from_extension("tsx").unwrap().name, "javascript");
+        assert_eq!(Language::from_extension("mts").unwrap().name, "javascript");
+        assert_eq!(Language::from_extension("cts").unwrap().name, "javascript");
+        assert_eq!(Language::from_extension("h").unwrap().name, "c");
+        assert_eq!(Language::from_extension("SH").unwrap().name, "bash");
+        assert_eq!(Language::from_extension("exs").unwrap().name, "elixir");
+        assert_eq!(Language::from_extension("hrl").unwrap().name, "erlang");
+        assert_eq!(Language::from_extension("cs").unwrap().name, "csharp");
+        assert_eq!(Language::from_extension("kts").unwrap().name, "kotlin");
+        assert_eq!(Language::from_extension("sc").unwrap().name, "scala");
+        assert_eq!(Language::from_extension("gradle").unwrap().name, "groovy");
+        assert!(Language::from_extension("xyz").is_none());
+    }
+
+    #[test]
+    fn test_deserialize_malformed_comments() {
+        let json = r#"{
+            "single_comments": {"1": "A comment", "2": 42, "3": {"text": "nested"}, "x": "No line", "4": null},
+            "multiline_comments": {"7": "A docstring", "8": ["a", "list"]}
+        }"#;
+        let collection: CommentCollection = serde_json::from_str(json).unwrap();
+
+        assert_eq!(collection.get(1, CommentType::Single).unwrap(), "A comment");
+        assert_eq!(collection.get(2, CommentType::Single).unwrap(), "42");
+        assert_eq!(collection.get(7, CommentType::Multi).unwrap(), "A docstring");
+        // The entries that cannot be coerced are skipped, the valid ones are kept
+        assert_eq!(collection.keys(), vec![(1, CommentType::Single), (2, CommentType::Single), (7, CommentType::Multi)]);
+    }
+
+    #[test]
+    fn test_comment_collection_chunks() {
+        let comments = (0..30)
+            .map(|i| {
+                let comment_type = if i % 2 == 0 { CommentType::Single } else { CommentType::Multi };
+                Comment::new(i, format!("comment number {}", i), comment_type)
+            })
+            .collect();
+        let collection = CommentCollection::from_comments(comments);
+
+        let chunks = collection.chunks(100);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| !c.is_empty()));
+
+        let mut merged = CommentCollection::from_comments(vec![]);
+        for chunk in chunks {
+            merged.merge(chunk);
+        }
+
+        assert_eq!(merged.len(), 30);
+        assert_eq!(merged.single_comments, collection.single_comments);
+        assert_eq!(merged.multiline_comments, collection.multiline_comments);
+
+        // A single batch when everything fits
+        assert_eq!(collection.chunks(usize::MAX).len(), 1);
     }
 }
@@ -1,72 +1,512 @@
+#[cfg(feature = "grammar")]
+pub mod args;
 pub mod buffer;
+#[cfg(feature = "grammar")]
+pub mod cache;
+pub mod code;
+pub mod diff;
+pub mod error;
+#[cfg(feature = "grammar")]
+pub mod estimate;
+#[cfg(feature = "grammar")]
+pub mod files;
+#[cfg(feature = "grammar")]
 pub mod firestore_logger;
+pub mod glob;
+#[cfg(feature = "grammar")]
 pub mod grammar;
+pub mod lang_config;
 pub mod language;
+pub mod mask;
+#[cfg(feature = "grammar")]
+pub mod offline;
+pub mod progress;
+pub mod review;
+#[cfg(feature = "grammar")]
+pub mod stats;
 
-use language::{init_supported_languages, Language, CommentCollection};
+#[cfg(feature = "grammar")]
+pub use args::{handle_args, parse_args, usage, version, Args, ArgsError, ColorChoice, OutputFormat};
+
+use diff::{diff_comments, CommentDiff};
+use error::NeospellerError;
+use language::{Comment, CommentCollection, Language};
 use buffer::{Buffer, sort_comments_by_line_number};
+#[cfg(feature = "grammar")]
+use diff::{text_edits, TextEdit};
+#[cfg(feature = "grammar")]
+use language::CommentType;
+#[cfg(feature = "grammar")]
+use cache::Cache;
+#[cfg(feature = "grammar")]
+use grammar::{GrammarConfig, GrammarProvider};
+#[cfg(feature = "grammar")]
+use mask::{inline_code_pattern, keep_case, straighten_quotes, url_pattern, word_list_pattern, xml_tag_pattern, Masker};
+
+
+/// Extract the comments of a source code, without calling any grammar backend
+///
+/// # Arguments
+///
+/// * `input` - The source code
+/// * `language` - The programming language of the source code
+///
+/// # Returns
+///
+/// * The comments ordered by line number
+///
+/// # Example
+///
+/// ```
+/// use neospeller::extract_comments;
+/// use neospeller::language::{CommentType, Language};
+///
+/// let language = Language::from_name("python").unwrap();
+/// let comments = extract_comments("x = 5  # The answr\n\"\"\"Docstring\"\"\"\n", &language);
+///
+/// assert_eq!(comments.len(), 2);
+/// assert_eq!(comments[0].line, 0);
+/// assert_eq!(comments[0].text, "The answr");
+/// assert_eq!(comments[1].comment_type, CommentType::Multi);
+/// ```
+pub fn extract_comments(input: &str, language: &Language) -> Vec<Comment> {
+    let mut buffer = Buffer::from_string(input.to_string(), language.clone());
+    buffer.get_comments();
+    sort_comments_by_line_number(buffer.comments)
+}
+
+/// Replace the comments of a source code with their corrections, without calling any grammar backend
+///
+/// With [`extract_comments`], it lets a caller check the comments with its own backend,
+/// e.g. an editor compiled to WebAssembly without the `grammar` feature.
+///
+/// # Arguments
+///
+/// * `input` - The source code
+/// * `language` - The programming language of the source code
+/// * `corrections` - The corrected comments by line, the comments that are not in it are kept
+///
+/// # Returns
+///
+/// * The corrected source code, or an error if a line has no such comment
+///
+/// # Example
+///
+/// ```
+/// use neospeller::apply_corrections;
+/// use neospeller::language::{CommentCollection, Language};
+///
+/// let language = Language::from_name("python").unwrap();
+/// let corrections: CommentCollection =
+///     serde_json::from_str(r#"{"single_comments": {"0": "The answer"}, "multiline_comments": {}}"#).unwrap();
+///
+/// let output = apply_corrections("x = 5  # The answr\n# Anothr\n", &language, &corrections).unwrap();
+/// assert_eq!(output, "x = 5  # The answer\n# Anothr\n");
+/// ```
+pub fn apply_corrections(
+    input: &str,
+    language: &Language,
+    corrections: &CommentCollection,
+) -> Result<String, NeospellerError> {
+    let mut buffer = Buffer::from_string(input.to_string(), language.clone());
+    buffer.get_comments();
+    buffer.collection_to_comments(corrections)?;
+    Ok(buffer.to_string())
+}
+
+/// Main entry point for the spell checker
+/// 
+/// # Arguments
+/// 
+/// * `input` - The source code to check
+/// * `language` - The programming language of the source code
+/// 
+/// # Returns
+/// 
+/// * The corrected source code
+#[cfg(feature = "grammar")]
+pub fn check_spelling(input: String, language: Language) -> Result<String, NeospellerError> {
+    check_spelling_with_config(input, language, &GrammarConfig::from_env().map_err(NeospellerError::Config)?)
+}
 
-use std::env;
+/// Spell checker using the provided configuration
+///
+/// # Arguments
+///
+/// * `input` - The source code to check
+/// * `language` - The programming language of the source code
+/// * `config` - The [`GrammarConfig`] of the grammar check
+///
+/// # Returns
+///
+/// * The corrected source code
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use neospeller::check_spelling_with_config;
+/// use neospeller::grammar::{GrammarConfig, ProviderKind};
+/// use neospeller::language::Language;
+///
+/// let dict = std::env::temp_dir().join("neospeller-doc-words.txt");
+/// std::fs::write(&dict, "a\ncomment\n").unwrap();
+///
+/// let config = GrammarConfig {
+///     provider: ProviderKind::Offline,
+///     dict: Some(dict),
+///     timeout: Duration::from_secs(60),
+///     retries: 3,
+///     ..GrammarConfig::default()
+/// };
+/// let language = Language::from_name("python").unwrap();
+///
+/// let output = check_spelling_with_config("# A coment\n".to_string(), language, &config).unwrap();
+/// assert_eq!(output, "# A comment\n");
+/// ```
+#[cfg(feature = "grammar")]
+pub fn check_spelling_with_config(
+    input: String,
+    language: Language,
+    config: &GrammarConfig,
+) -> Result<String, NeospellerError> {
+    let provider = config.provider()?;
+    check_spelling_report(input, language, provider.as_ref(), config).map(|report| report.output)
+}
 
-/// Handle the CLI args
-pub fn handle_args() -> Result<Language, &'static str> {
-    let mut args = env::args();
+/// Spell checker using the provided grammar backend
+///
+/// # Arguments
+///
+/// * `input` - The source code to check
+/// * `language` - The programming language of the source code
+/// * `provider` - The [`GrammarProvider`] that checks the comments
+///
+/// # Returns
+///
+/// * The corrected source code
+#[cfg(feature = "grammar")]
+pub fn check_spelling_with_provider(
+    input: String,
+    language: Language,
+    provider: &dyn GrammarProvider,
+) -> Result<String, NeospellerError> {
+    let config = GrammarConfig::from_env().map_err(NeospellerError::Config)?;
+    check_spelling_report(input, language, provider, &config).map(|report| report.output)
+}
 
-    if args.len() < 2 {
-        eprintln!("The --lang attribute is required. (e.g. --lang python)");
-        return Err("Language not found");
+/// Spell checker that also returns the edits applied to the source code
+///
+/// The edits let an editor apply the corrections in place, without diffing the whole buffer.
+///
+/// # Arguments
+///
+/// * `input` - The source code to check
+/// * `language` - The programming language of the source code
+///
+/// # Returns
+///
+/// * The corrected source code and a [`TextEdit`] for each corrected line
+#[cfg(feature = "grammar")]
+pub fn check_spelling_with_edits(input: String, language: Language) -> Result<(String, Vec<TextEdit>), NeospellerError> {
+    let config = GrammarConfig::from_env().map_err(NeospellerError::Config)?;
+    let provider = config.provider()?;
+    let report = check_spelling_report(input.clone(), language, provider.as_ref(), &config)?;
+
+    let edits = text_edits(&input, &report.output, &report.original);
+    Ok((report.output, edits))
+}
+
+/// Minimum similarity, between 0 and 1, of the sent and the returned text of a comment
+/// to take the returned one as its correction
+#[cfg(feature = "grammar")]
+const LINE_SIMILARITY: f32 = 0.5;
+
+/// Validate that the model preserved the line numbers of the comments
+///
+/// The comments whose line drifted, e.g. numbered from one or from the start of the
+/// batch, are moved back to the nearest unused line of the same type with a similar
+/// text. The rest of the missing comments fall back to the original text and the
+/// unknown comments are discarded. Every mismatch is reported as a warning, or as an
+/// error when `strict` is set.
+///
+/// # Arguments
+///
+/// * `sent` - Comments sent to the model
+/// * `received` - Comments returned by the model
+/// * `strict` - Fail when the line numbers differ
+#[cfg(feature = "grammar")]
+fn validate_lines(
+    sent: &CommentCollection,
+    received: &mut CommentCollection,
+    strict: bool,
+) -> Result<(), String> {
+    let sent_keys = sent.keys();
+    let received_keys = received.keys();
+
+    let missing: Vec<_> = sent_keys.iter().filter(|k| !received_keys.contains(k)).collect();
+    let unknown: Vec<_> = received_keys.iter().filter(|k| !sent_keys.contains(k)).collect();
+
+    if missing.is_empty() && unknown.is_empty() {
+        return Ok(());
     }
 
-    let mut language: Option<Language> = None;
+    let lines = |keys: &[&(usize, CommentType)]| {
+        keys.iter()
+            .map(|(line, _)| (line + 1).to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let message = format!(
+        "The model response doesn't match the sent comments (missing lines: [{}], unknown lines: [{}])",
+        lines(&missing),
+        lines(&unknown)
+    );
 
-    while let Some(arg) = args.next() {
-        if arg == "--lang" {
-            let supported_languages = init_supported_languages();
-            let lang = args.next().expect("Language not found (e.g. python)");
-            let lang = lang.trim().to_lowercase();
+    if strict {
+        return Err(message);
+    }
+    log::warn!("{}, restoring the lines", message);
 
-            language = supported_languages.languages.into_iter().find(|l| l.name == lang);
+    *received = reconcile_lines(sent, received);
+    Ok(())
+}
 
-            break;
+/// Match the comments returned by the model to the sent ones
+///
+/// Each returned comment is matched to a sent comment of the same type with a
+/// similar text, the most similar pairs first and then the nearest lines, e.g. a
+/// correction returned for the next line is moved back to its line. The returned
+/// comments without a match stay in their line if it was sent, and the sent lines
+/// without a match keep their original text.
+///
+/// # Returns
+/// * The returned comments, with the keys of the sent comments
+#[cfg(feature = "grammar")]
+fn reconcile_lines(sent: &CommentCollection, received: &CommentCollection) -> CommentCollection {
+    let mut pairs = Vec::new();
+    for (line, comment_type) in received.keys() {
+        let Some(text) = received.get(line, comment_type) else {
+            continue;
+        };
+        for (to, sent_type) in sent.keys() {
+            let Some(original) = sent.get(to, sent_type).filter(|_| sent_type == comment_type) else {
+                continue;
+            };
+            let similarity = similar::TextDiff::from_chars(original.as_str(), text.as_str()).ratio();
+            if similarity >= LINE_SIMILARITY {
+                pairs.push((similarity, to.abs_diff(line), line, to, comment_type));
+            }
         }
     }
+    pairs.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
 
-    if language.is_none() {
-        return Err("Error: Language not supported or not specified.");
+    let mut reconciled = CommentCollection::from_comments(Vec::new());
+    let mut matched = Vec::new();
+    for (_, _, line, to, comment_type) in pairs {
+        if matched.contains(&(line, comment_type)) || reconciled.get(to, comment_type).is_some() {
+            continue;
+        }
+        if let Some(text) = received.get(line, comment_type) {
+            if to != line {
+                log::warn!("Moving the comment returned for line {} to line {}", line + 1, to + 1);
+            }
+            reconciled.insert(to, comment_type, text.clone());
+            matched.push((line, comment_type));
+        }
     }
 
-    Ok(language.unwrap())
+    // A comment rewritten beyond recognition, e.g. in the grammar mode, stays in its line
+    for (line, comment_type) in received.keys() {
+        if matched.contains(&(line, comment_type)) {
+            continue;
+        }
+        match received.get(line, comment_type) {
+            Some(text) if sent.get(line, comment_type).is_some() && reconciled.get(line, comment_type).is_none() => {
+                reconciled.insert(line, comment_type, text.clone());
+            }
+            _ => log::warn!("Discarding the comment returned for line {}", line + 1),
+        }
+    }
+
+    for (line, comment_type) in sent.keys() {
+        if let (None, Some(text)) = (reconciled.get(line, comment_type), sent.get(line, comment_type)) {
+            reconciled.insert(line, comment_type, text.clone());
+        }
+    }
+
+    reconciled
 }
 
-/// Main entry point for the spell checker
-/// 
+/// Result of a spell check, with the comments before and after the corrections
+#[derive(Debug)]
+pub struct SpellingReport {
+    /// The corrected source code
+    pub output: String,
+    /// Comments extracted from the source code
+    pub original: Vec<Comment>,
+    /// Comments returned by the grammar backend
+    pub corrected: Vec<Comment>,
+}
+
+impl SpellingReport {
+    /// Comments that have been changed by the corrections
+    pub fn diffs(&self) -> Vec<CommentDiff> {
+        diff_comments(&self.original, &self.corrected)
+    }
+}
+
+/// Spell checker that reports the comments before and after the corrections
+///
 /// # Arguments
-/// 
+///
 /// * `input` - The source code to check
 /// * `language` - The programming language of the source code
-/// 
+/// * `provider` - The [`GrammarProvider`] that checks the comments
+/// * `config` - The [`GrammarConfig`] of the grammar check
+///
 /// # Returns
-/// 
-/// * The corrected source code
-pub fn check_spelling(input: String, language: Language) -> Result<String, Box<dyn std::error::Error>> {
+///
+/// * A [`SpellingReport`] with the corrected source code and comments
+#[cfg(feature = "grammar")]
+pub fn check_spelling_report(
+    input: String,
+    language: Language,
+    provider: &dyn GrammarProvider,
+    config: &GrammarConfig,
+) -> Result<SpellingReport, NeospellerError> {
     let language_name = language.name.clone();
     let original = input.clone();
 
     let mut buffer = Buffer::from_string(input, language);
     buffer.get_comments();
-    let comments_collection = CommentCollection::from_comments(buffer.comments);
-    let parsed_comments = serde_json::to_string(&comments_collection)?;
 
-    let output = grammar::check_grammar(&parsed_comments, &language_name)?;
+    // Only the open line of an unterminated comment is checked, the rest is taken as code
+    if let Some(line) = buffer.unterminated_comments.first() {
+        if config.strict {
+            return Err(NeospellerError::UnterminatedComment(*line));
+        }
+        log::warn!("Unterminated comment in line {}, only its first line is checked", line + 1);
+    }
+
+    let mut comments_collection = CommentCollection::from_comments(buffer.comments.clone());
+    log::debug!("{} comment(s) extracted", comments_collection.len());
 
-    buffer.comments = comments_collection.to_comments();
-    buffer.comments = sort_comments_by_line_number(buffer.comments);
+    // The parsed comments keep their columns, they are needed to replace them
+    buffer.comments = sort_comments_by_line_number(std::mem::take(&mut buffer.comments));
+    let original_comments = buffer.comments.clone();
+
+    let word_list = match &config.ignore_words {
+        Some(path) => std::fs::read_to_string(path).map_err(|err| {
+            NeospellerError::Config(format!("Cannot read the ignore words file {}: {}", path.display(), err))
+        })?,
+        None => String::new(),
+    };
+
+    // Commented-out code, trivial comments and the comments outside `--only-lines` are
+    // left as they are, e.g. the model would "fix" the identifiers of the code
+    for (line, comment_type) in comments_collection.keys() {
+        if comments_collection.get(line, comment_type).is_some_and(|text| config.is_skipped(line, text)) {
+            comments_collection.remove(line, comment_type);
+            log::debug!("Skipping the comment in line {}", line + 1);
+        }
+    }
+
+    // Only the comments that are not cached are sent to the model
+    let mut corrected_collection = CommentCollection::from_comments(Vec::new());
+    let cache = match &config.cache_dir {
+        Some(dir) => {
+            let prompt = config.system_prompt(&language_name)? + &word_list;
+            Some(Cache::new(dir.clone(), &language_name, config.model_name(), &prompt))
+        }
+        None => None,
+    };
+    if let Some(cache) = &cache {
+        for (line, comment_type) in comments_collection.keys() {
+            let cached = comments_collection.get(line, comment_type).and_then(|text| cache.get(text));
+            if let Some(text) = cached {
+                comments_collection.remove(line, comment_type);
+                corrected_collection.insert(line, comment_type, text);
+            }
+        }
+        log::debug!("{} comment(s) found in the cache", corrected_collection.len());
+    }
+    let sent_collection = comments_collection.clone();
+
+    // Protect URLs, the ignored words, the Markdown code and the XML tags from being "corrected" by the model
+    let ignored_words = word_list_pattern(&word_list);
+
+    let mut masker = Masker::new();
+    for text in comments_collection.texts_mut() {
+        if buffer.language.markdown_code {
+            *text = masker.mask(text, inline_code_pattern(), "CODE");
+        }
+        if buffer.language.xml_doc_tags {
+            *text = masker.mask(text, xml_tag_pattern(), "TAG");
+        }
+        *text = masker.mask(text, url_pattern(), "URL");
+        if let Some(pattern) = &ignored_words {
+            *text = masker.mask(text, pattern, "WORD");
+        }
+    }
+
+    // Send the comments in batches to avoid truncated responses
+    let mut fresh_collection = CommentCollection::from_comments(Vec::new());
+    for chunk in comments_collection.chunks(config.chunk_size) {
+        let mut corrected_chunk = provider.check_comments(&chunk, &language_name)?;
+        validate_lines(&chunk, &mut corrected_chunk, config.strict).map_err(NeospellerError::ApiResponse)?;
+        fresh_collection.merge(corrected_chunk);
+    }
+
+    for text in fresh_collection.texts_mut() {
+        *text = masker.unmask(text);
+    }
+
+    if let Some(cache) = &cache {
+        for (line, comment_type) in fresh_collection.keys() {
+            if let (Some(text), Some(corrected)) = (
+                sent_collection.get(line, comment_type),
+                fresh_collection.get(line, comment_type),
+            ) {
+                if let Err(err) = cache.insert(text, corrected) {
+                    log::warn!("Cannot write the cache: {}", err);
+                }
+            }
+        }
+    }
+    corrected_collection.merge(fresh_collection);
+
+    // The model may turn the straight quotes into smart ones, e.g. `don’t`, or change the case
+    // of the first letter against the house style, the cached corrections too
+    if config.straight_quotes || config.keep_case {
+        let originals = CommentCollection::from_comments(original_comments.clone());
+        for (line, comment_type) in corrected_collection.keys() {
+            if let (Some(original), Some(corrected)) =
+                (originals.get(line, comment_type), corrected_collection.get(line, comment_type))
+            {
+                let mut text = corrected.clone();
+                if config.straight_quotes {
+                    text = straighten_quotes(original, &text);
+                }
+                if config.keep_case {
+                    text = keep_case(original, &text);
+                }
+                corrected_collection.insert(line, comment_type, text);
+            }
+        }
+    }
 
-    buffer.json_to_comments(&output)?;
+    buffer.collection_to_comments(&corrected_collection)?;
+    buffer.normalize_eof(config.newline_eof);
 
     let corrected = buffer.to_string();
 
     firestore_logger::spool(original, corrected.clone());
 
-    Ok(corrected)
+    Ok(SpellingReport {
+        output: corrected,
+        original: original_comments,
+        corrected: buffer.comments,
+    })
 }
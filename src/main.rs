@@ -1,23 +1,324 @@
-use std::io::{self, Read};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::Path;
 
-use neospeller::check_spelling;
+use neospeller::{check_spelling_report, extract_comments, Args, ArgsError, OutputFormat, SpellingReport};
+use neospeller::diff::{changed_lines, render_diff, DiffRecord};
+use neospeller::error::NeospellerError;
+use neospeller::estimate::{estimate, Estimate};
+use neospeller::files::{self, collect_files, language_for_path, write_file, FileReport};
+use neospeller::grammar::GrammarProvider;
+use neospeller::language::Language;
+use neospeller::progress::Progress;
+use neospeller::review::{prompt_decision, review};
+use neospeller::stats::Stats;
 
 fn main() {
-    let language = neospeller::handle_args().unwrap_or_else(|err| {
+    let args = neospeller::handle_args().unwrap_or_else(|err| match err {
+        ArgsError::Help | ArgsError::Version => {
+            println!("{}", err);
+            std::process::exit(0);
+        }
+        err => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    });
+
+    init_logger(args.verbose, args.quiet);
+
+    if args.estimate {
+        print_estimate(&args);
+        return;
+    }
+
+    if args.stats {
+        print_stats(&args);
+        return;
+    }
+
+    let provider = args.config.provider().unwrap_or_else(|err| {
         eprintln!("{}", err);
         std::process::exit(1);
     });
 
+    let needs_corrections = if args.paths.is_empty() {
+        check_stdin(&args, provider.as_ref())
+    } else {
+        check_files(&args, provider.as_ref())
+    };
+
+    if args.check {
+        if needs_corrections {
+            std::process::exit(1);
+        }
+        if !args.quiet {
+            eprintln!("All comments are correct");
+        }
+    }
+
+    // The files have been fixed, but they must be reviewed, e.g. re-staged in a pre-commit hook
+    if args.fix && needs_corrections {
+        std::process::exit(1);
+    }
+}
+
+/// Log to stderr, at debug level with `--verbose`, only the errors with `--quiet`,
+/// otherwise as set by `NEOSPELLER_LOG`, the warnings by default
+fn init_logger(verbose: bool, quiet: bool) {
+    let mut builder = env_logger::Builder::from_env(env_logger::Env::new().filter_or("NEOSPELLER_LOG", "warn"));
+    if verbose {
+        builder.filter_level(log::LevelFilter::Debug);
+    } else if quiet {
+        builder.filter_level(log::LevelFilter::Error);
+    }
+    builder.format_timestamp(None).init();
+}
+
+/// Read the source code to check, from stdin or from the files of the arguments
+///
+/// # Returns
+/// * The label of each input, `None` for stdin, with its content and language
+fn read_inputs(args: &Args) -> Vec<(Option<String>, String, Language)> {
+    if args.paths.is_empty() {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        });
+        let language = args.language.clone().expect("Language is required when reading stdin");
+        vec![(None, input, language)]
+    } else {
+        let paths = collect_files(&args.paths, args.language.as_ref(), &args.config.languages, &args.exclude).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        });
+        paths
+            .iter()
+            .map(|path| {
+                let language = language_for_path(path, args.language.as_ref(), &args.config.languages)?;
+                let input = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+                Ok((Some(path.display().to_string()), input, language))
+            })
+            .collect::<Result<_, String>>()
+            .unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            })
+    }
+}
+
+/// Print the estimated tokens and cost of the check to stderr, without calling the model
+fn print_estimate(args: &Args) {
+    let mut total: Option<Estimate> = None;
+    for (label, input, language) in &read_inputs(args) {
+        let file_estimate = estimate(input, language, &args.config).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        });
+
+        if let Some(label) = label {
+            eprintln!("{}: {}", label, file_estimate);
+        }
+
+        match &mut total {
+            Some(total) => total.add(&file_estimate),
+            None => total = Some(file_estimate),
+        }
+    }
+
+    if let Some(total) = total {
+        eprintln!("neospeller: {}", total);
+    }
+}
+
+/// Print the counts of the comments to stdout, without calling the model
+fn print_stats(args: &Args) {
+    let inputs = read_inputs(args);
+
+    let mut total = Stats::default();
+    for (label, input, language) in &inputs {
+        let stats = Stats::from_comments(&extract_comments(input, language));
+
+        match label {
+            Some(label) => println!("{}: {}", label, stats),
+            None => println!("{}", stats),
+        }
+        total.add(&stats);
+    }
+
+    if inputs.len() > 1 {
+        println!("total: {}", total);
+    }
+}
+
+/// Check the source code read from stdin, returns whether it needs corrections
+fn check_stdin(args: &Args, provider: &dyn GrammarProvider) -> bool {
     let mut input = String::new();
     io::stdin().read_to_string(&mut input).unwrap_or_else(|err| {
         eprintln!("{}", err);
         std::process::exit(1);
     });
 
-    let output = check_spelling(input, language).unwrap_or_else(|err| {
+    let language = args.language.clone().expect("Language is required when reading stdin");
+    let report = check_spelling_report(input.clone(), language, provider, &args.config)
+        .unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        });
+
+    print_report(args, &input, &report, None)
+}
+
+/// Check every file of the arguments, returns whether any file needs corrections
+///
+/// Each file is processed independently, a failure is reported and the rest of
+/// the files are still checked. A summary is printed at the end.
+///
+/// The files are reported in the order of the arguments, except for the JSON Lines
+/// output that streams each file as soon as it is checked. A status line shows the
+/// progress on a terminal, unless `--quiet` or `--interactive` is set.
+fn check_files(args: &Args, provider: &(dyn GrammarProvider + Sync)) -> bool {
+    let paths = collect_files(&args.paths, args.language.as_ref(), &args.config.languages, &args.exclude).unwrap_or_else(|err| {
         eprintln!("{}", err);
         std::process::exit(1);
     });
 
-    print!("{}", output);
+    let mut needs_corrections = false;
+    let mut corrected = 0;
+    let mut failed = 0;
+
+    let mut handle_result = |path: &Path, result: Result<FileReport, NeospellerError>| {
+        let result = result.and_then(|mut file| {
+            if args.write && !args.check && !args.dry_run {
+                if args.interactive {
+                    file.report.output = review_file(&file)?;
+                }
+                write_file(&file)?;
+                Ok(file.changed())
+            } else {
+                let label = path.display().to_string();
+                Ok(print_report(args, &file.input, &file.report, Some(&label)))
+            }
+        });
+
+        match result {
+            Ok(changed) => {
+                if changed {
+                    corrected += 1;
+                }
+                needs_corrections |= changed;
+            }
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                failed += 1;
+            }
+        }
+    };
+
+    let mut progress = Progress::stderr(paths.len(), args.quiet || args.interactive);
+    progress.draw();
+
+    if args.format == OutputFormat::Jsonl {
+        // Each file is printed as a whole as soon as it is checked, so its records stay grouped
+        files::check_files_streaming(
+            &paths,
+            args.language.as_ref(),
+            provider,
+            &args.config,
+            args.jobs,
+            |index, result| {
+                progress.clear();
+                handle_result(&paths[index], result);
+                progress.tick();
+            },
+        );
+        progress.clear();
+    } else {
+        // The files are checked concurrently, then reported in a stable order
+        let mut results = Vec::new();
+        files::check_files_streaming(
+            &paths,
+            args.language.as_ref(),
+            provider,
+            &args.config,
+            args.jobs,
+            |index, result| {
+                results.push((index, result));
+                progress.tick();
+            },
+        );
+        progress.clear();
+
+        results.sort_by_key(|(index, _)| *index);
+        for (index, result) in results {
+            handle_result(&paths[index], result);
+        }
+    }
+
+    if !args.quiet {
+        eprintln!(
+            "neospeller: {} file(s) processed, {} with corrections, {} failed",
+            paths.len(),
+            corrected,
+            failed
+        );
+    }
+
+    if failed > 0 {
+        std::process::exit(1);
+    }
+
+    needs_corrections
+}
+
+/// Ask for each correction of a file on the terminal
+///
+/// # Returns
+/// * The content of the file with the accepted corrections
+fn review_file(file: &FileReport) -> Result<String, NeospellerError> {
+    let label = file.path.display().to_string();
+
+    review(&file.input, file.language.clone(), &file.report, |diff| {
+        Ok(prompt_decision(diff, &label, &mut io::stdin().lock(), &mut io::stderr())?)
+    })
+}
+
+/// Print the result of a check in the selected mode, returns whether corrections are needed
+fn print_report(args: &Args, input: &str, report: &SpellingReport, label: Option<&str>) -> bool {
+    let output = &report.output;
+    let changed = changed_lines(input, output);
+    let prefix = label.map(|l| format!("{}: ", l)).unwrap_or_default();
+
+    if args.check {
+        if changed > 0 {
+            eprintln!("{}{} comment line(s) need corrections", prefix, changed);
+        }
+    } else if args.dry_run {
+        let color = args.color.enabled(io::stdout().is_terminal());
+        print!("{}", render_diff(input, output, color));
+    } else if args.format == OutputFormat::Json {
+        let diffs = serde_json::to_string_pretty(&report.diffs()).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        });
+        println!("{}", diffs);
+    } else if args.format == OutputFormat::Jsonl {
+        print_records(&DiffRecord::from_diffs(label, report.diffs())).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        });
+    } else {
+        print!("{}", output);
+    }
+
+    changed > 0
+}
+
+/// Print the records of a file as JSON Lines, flushed so they can be consumed right away
+fn print_records(records: &[DiffRecord]) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    for record in records {
+        writeln!(stdout, "{}", serde_json::to_string(record)?)?;
+    }
+    stdout.flush()
 }
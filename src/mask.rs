@@ -0,0 +1,235 @@
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Replace fragments of the comments with stable placeholders before sending
+/// them to the model, and restore them afterward, so the model never alters them
+#[derive(Debug, Default)]
+pub struct Masker {
+    masked: Vec<String>,
+}
+
+impl Masker {
+    /// Create a new [`Masker`]
+    pub fn new() -> Self {
+        Self { masked: Vec::new() }
+    }
+
+    /// Replace every match of `pattern` in `text` with a placeholder
+    ///
+    /// # Params
+    /// * `text`: Text to mask
+    /// * `pattern`: Fragments to protect
+    /// * `label`: Kind of fragment used in the placeholder, e.g. `URL`
+    ///
+    /// # Returns
+    /// * The masked text
+    pub fn mask(&mut self, text: &str, pattern: &Regex, label: &str) -> String {
+        pattern
+            .replace_all(text, |caps: &regex::Captures| {
+                self.masked.push(caps[0].to_string());
+                format!("__{}_{}__", label, self.masked.len() - 1)
+            })
+            .into_owned()
+    }
+
+    /// Restore the original fragments of a masked text
+    pub fn unmask(&self, text: &str) -> String {
+        placeholder_pattern()
+            .replace_all(text, |caps: &regex::Captures| {
+                caps[1]
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| self.masked.get(i))
+                    .cloned()
+                    .unwrap_or_else(|| caps[0].to_string())
+            })
+            .into_owned()
+    }
+}
+
+/// Pattern of the placeholders generated by [`Masker::mask`]
+fn placeholder_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"__[A-Z]+_(\d+)__").unwrap())
+}
+
+/// Pattern of URL-like tokens, trailing punctuation is not part of the URL
+pub fn url_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r#"(?:https?://|www\.)[^\s<>"'`]*[^\s<>"'`.,;:!?)\]]"#).unwrap()
+    })
+}
+
+/// Pattern of Markdown inline code spans, e.g. `` `Vec<u8>` `` or ``` ``a ` b`` ```
+pub fn inline_code_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"``[^`](?:[^`]|`[^`])*``|`[^`]+`").unwrap())
+}
+
+/// Pattern of XML tags, e.g. `<summary>`, `</param>` or `<see cref="List{T}"/>`
+pub fn xml_tag_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r#"</?[A-Za-z][\w:.-]*(?:\s+[^<>]*)?/?>"#).unwrap())
+}
+
+/// Pattern matching any word of a word list, one word per line, case-insensitive
+///
+/// # Returns
+/// * The pattern, or `None` if the list has no words
+pub fn word_list_pattern(word_list: &str) -> Option<Regex> {
+    let words: Vec<String> = word_list
+        .lines()
+        .map(str::trim)
+        .filter(|w| !w.is_empty())
+        .map(regex::escape)
+        .collect();
+
+    if words.is_empty() {
+        return None;
+    }
+
+    Regex::new(&format!(r"(?i)\b(?:{})\b", words.join("|"))).ok()
+}
+
+/// Smart quotes and their ASCII equivalents
+const SMART_QUOTES: [(char, char); 8] = [
+    ('\u{2018}', '\''),
+    ('\u{2019}', '\''),
+    ('\u{201A}', '\''),
+    ('\u{201B}', '\''),
+    ('\u{201C}', '"'),
+    ('\u{201D}', '"'),
+    ('\u{201E}', '"'),
+    ('\u{201F}', '"'),
+];
+
+/// Replace the smart quotes introduced by the model with straight quotes,
+/// e.g. `don’t` becomes `don't`
+///
+/// # Params
+/// * `original`: Text sent to the model, its smart quotes are intentional and kept
+/// * `corrected`: Text returned by the model
+pub fn straighten_quotes(original: &str, corrected: &str) -> String {
+    corrected
+        .chars()
+        .map(|c| match SMART_QUOTES.iter().find(|(smart, _)| *smart == c) {
+            Some((smart, straight)) if !original.contains(*smart) => *straight,
+            _ => c,
+        })
+        .collect()
+}
+
+/// Restore the case of the first letter and of the acronyms of the original text,
+/// e.g. `fix the HTTP client` is kept when the model answers `Fix the http client`
+///
+/// # Params
+/// * `original`: Text sent to the model
+/// * `corrected`: Text returned by the model
+pub fn keep_case(original: &str, corrected: &str) -> String {
+    let mut text = corrected.to_string();
+
+    // Acronyms of two or more capital letters
+    for word in original.split(|c: char| !c.is_alphanumeric()) {
+        let is_acronym = word.chars().filter(|c| c.is_alphabetic()).count() >= 2
+            && word.chars().all(|c| !c.is_lowercase());
+        if is_acronym {
+            if let Ok(pattern) = Regex::new(&format!(r"(?i)\b{}\b", regex::escape(word))) {
+                text = pattern.replace_all(&text, regex::NoExpand(word)).into_owned();
+            }
+        }
+    }
+
+    // The case of the first letter, when both texts start with a letter
+    let offset = text.len() - text.trim_start().len();
+    match (original.trim_start().chars().next(), text[offset..].chars().next()) {
+        (Some(first), Some(letter)) if first.is_alphabetic() && letter.is_alphabetic() => {
+            let restored: String = if first.is_uppercase() {
+                letter.to_uppercase().collect()
+            } else {
+                letter.to_lowercase().collect()
+            };
+            text.replace_range(offset..offset + letter.len_utf8(), &restored);
+            text
+        }
+        _ => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_urls() {
+        let mut masker = Masker::new();
+        let text = "See https://example.com/docs?a=1, or www.rust-lang.org.";
+
+        let masked = masker.mask(text, url_pattern(), "URL");
+        assert_eq!(masked, "See __URL_0__, or __URL_1__.");
+        assert_eq!(masker.unmask(&masked), text);
+    }
+
+    #[test]
+    fn test_unmask_unknown_placeholder() {
+        let masker = Masker::new();
+        assert_eq!(masker.unmask("Keep __URL_3__ as is"), "Keep __URL_3__ as is");
+    }
+
+    #[test]
+    fn test_mask_inline_code() {
+        let mut masker = Masker::new();
+        let text = "Call `recieve()` with ``a ` b`` as the argumnet";
+
+        let masked = masker.mask(text, inline_code_pattern(), "CODE");
+        assert_eq!(masked, "Call __CODE_0__ with __CODE_1__ as the argumnet");
+        assert_eq!(masker.unmask(&masked), text);
+    }
+
+    #[test]
+    fn test_mask_xml_tags() {
+        let mut masker = Masker::new();
+        let text = "<param name=\"count\">The numbr of <see cref=\"Item\"/> values, 1 < 2</param>";
+
+        let masked = masker.mask(text, xml_tag_pattern(), "TAG");
+        assert_eq!(masked, "__TAG_0__The numbr of __TAG_1__ values, 1 < 2__TAG_2__");
+        assert_eq!(masker.unmask(&masked), text);
+    }
+
+    #[test]
+    fn test_mask_word_list() {
+        let pattern = word_list_pattern("serde\n\nidempotent\n").unwrap();
+        let mut masker = Masker::new();
+        let text = "Idempotent parse with serde, not serdes";
+
+        let masked = masker.mask(text, &pattern, "WORD");
+        assert_eq!(masked, "__WORD_0__ parse with __WORD_1__, not serdes");
+        assert_eq!(masker.unmask(&masked), text);
+
+        assert!(word_list_pattern("\n  \n").is_none());
+    }
+
+    #[test]
+    fn test_keep_case() {
+        assert_eq!(keep_case("retry the reqest", "Retry the request"), "retry the request");
+        assert_eq!(keep_case("  Retry the reqest", "  retry the request"), "  Retry the request");
+        assert_eq!(keep_case("send the HTTP reqest", "Send the http request"), "send the HTTP request");
+
+        // A comment that starts with a symbol is left as it is
+        assert_eq!(keep_case("`retry` the reqest", "`Retry` the request"), "`Retry` the request");
+    }
+
+    #[test]
+    fn test_straighten_quotes() {
+        assert_eq!(
+            straighten_quotes("Dont use the \"old\" API", "Don\u{2019}t use the \u{201C}old\u{201D} API"),
+            "Don't use the \"old\" API"
+        );
+
+        // The smart quotes of the original text are intentional, the other Unicode is never changed
+        assert_eq!(
+            straighten_quotes("The \u{201C}café\u{201D} — see it", "The \u{201C}café\u{201D} — see it\u{2019}s"),
+            "The \u{201C}café\u{201D} — see it's"
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::error::NeospellerError;
+use crate::grammar::GrammarProvider;
+use crate::language::CommentCollection;
+
+/// Offline backend that corrects misspelled words using a word list
+///
+/// This backend never touches the network, which makes it suitable for
+/// air-gapped environments. It only fixes spelling: a word that is not in
+/// the word list is replaced by the nearest word by edit distance when there
+/// is only one, grammar and wording are never changed. The word list must be
+/// a complete one, e.g. `/usr/share/dict/words`, the correct words missing
+/// from it would be "corrected".
+pub struct OfflineProvider {
+    words: HashSet<String>,
+}
+
+impl OfflineProvider {
+    /// Create a new [`OfflineProvider`] from a word list, one word per line
+    pub fn new(word_list: &str) -> Self {
+        let words = word_list
+            .lines()
+            .map(|w| w.trim().to_lowercase())
+            .filter(|w| !w.is_empty())
+            .collect();
+
+        Self { words }
+    }
+
+    /// Create a new [`OfflineProvider`] reading the word list from a file
+    pub fn from_file(path: &Path) -> std::io::Result<Self> {
+        let word_list = std::fs::read_to_string(path)?;
+        Ok(Self::new(&word_list))
+    }
+
+    /// Correct the misspelled words of a text, keeping everything else untouched
+    ///
+    /// # Params
+    /// * `text`: Text to correct
+    ///
+    /// # Returns
+    /// * The corrected text
+    pub fn correct_text(&self, text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut word = String::new();
+
+        for c in text.chars() {
+            if c.is_alphanumeric() || c == '_' || c == '\'' {
+                word.push(c);
+            } else {
+                result.push_str(&self.correct_word(&word));
+                word.clear();
+                result.push(c);
+            }
+        }
+        result.push_str(&self.correct_word(&word));
+
+        result
+    }
+
+    /// Correct a single word with the nearest dictionary suggestion
+    ///
+    /// Identifiers (digits, underscores, camelCase), words without a close
+    /// enough suggestion and words with several nearest suggestions, e.g.
+    /// `cas` for `car` and `cat`, are returned unchanged.
+    fn correct_word(&self, word: &str) -> String {
+        let is_identifier = word.chars().any(|c| c.is_ascii_digit() || c == '_')
+            || word.chars().skip(1).any(char::is_uppercase);
+
+        if word.is_empty() || is_identifier {
+            return word.to_string();
+        }
+
+        let lowered = word.to_lowercase();
+        if self.words.contains(&lowered) || self.words.contains(lowered.trim_matches('\'')) {
+            return word.to_string();
+        }
+
+        // Short words have too many neighbours, be stricter with them
+        let max_distance = if lowered.chars().count() <= 4 { 1 } else { 2 };
+
+        let candidates: Vec<(usize, &String)> = self
+            .words
+            .iter()
+            .map(|candidate| (edit_distance(&lowered, candidate), candidate))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+
+        // An ambiguous word is kept, picking one of the suggestions would be a guess
+        let Some(nearest) = candidates.iter().map(|(distance, _)| *distance).min() else {
+            return word.to_string();
+        };
+        let mut nearest = candidates.iter().filter(|(distance, _)| *distance == nearest);
+        match (nearest.next(), nearest.next()) {
+            (Some((_, candidate)), None) => match_case(word, candidate),
+            _ => word.to_string(),
+        }
+    }
+}
+
+impl GrammarProvider for OfflineProvider {
+    fn check(&self, json_data: &str, _language: &str) -> Result<String, NeospellerError> {
+        let collection: CommentCollection = serde_json::from_str(json_data)?;
+
+        let mut comments = collection.to_comments();
+        for comment in comments.iter_mut() {
+            comment.text = self.correct_text(&comment.text);
+        }
+
+        let collection = CommentCollection::from_comments(comments);
+        Ok(serde_json::to_string(&collection)?)
+    }
+}
+
+/// Levenshtein distance between two words
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            current[j + 1] = (previous[j] + cost)
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// Apply the capitalization of the original word to the suggestion
+fn match_case(original: &str, suggestion: &str) -> String {
+    if original.chars().all(char::is_uppercase) {
+        return suggestion.to_uppercase();
+    }
+
+    let mut chars = suggestion.chars();
+    match (original.chars().next(), chars.next()) {
+        (Some(first), Some(s)) if first.is_uppercase() => s.to_uppercase().chain(chars).collect(),
+        _ => suggestion.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DICTIONARY: &str = "read\ninput\nfrom\nstandard\nthe\nprocess\ntext\n";
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("standart", "standard"), 1);
+        assert_eq!(edit_distance("proccess", "process"), 1);
+        assert_eq!(edit_distance("text", "text"), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_correct_text() {
+        let provider = OfflineProvider::new(DICTIONARY);
+
+        assert_eq!(
+            provider.correct_text("Read inpt from standart input"),
+            "Read input from standard input"
+        );
+        assert_eq!(provider.correct_text("Proccess the text."), "Process the text.");
+
+        // Unknown words without a close suggestion and identifiers are kept
+        assert_eq!(provider.correct_text("Read xyzzy line_numbr"), "Read xyzzy line_numbr");
+    }
+
+    #[test]
+    fn test_correct_prose_is_unchanged() {
+        let provider = OfflineProvider::new(
+            "the\nuser\nusers\nwant\nfaster\nafter\nbuild\nbuilds\nreturns\nparse\nparsed\ntoken\ntokens\n",
+        );
+
+        assert_eq!(provider.correct_text("The users want faster builds"), "The users want faster builds");
+        assert_eq!(provider.correct_text("Returns the parsed tokens"), "Returns the parsed tokens");
+    }
+
+    #[test]
+    fn test_ambiguous_word_is_unchanged() {
+        let provider = OfflineProvider::new("car\ncat\ncomment\n");
+
+        assert_eq!(provider.correct_text("A cas"), "A cas");
+        assert_eq!(provider.correct_text("A coment"), "A comment");
+    }
+
+    #[test]
+    fn test_check_comments() {
+        let provider = OfflineProvider::new(DICTIONARY);
+        let json = r#"{"single_comments":{"3":"Read inpt"},"multiline_comments":{"7":"proccess text"}}"#;
+
+        let output = provider.check(json, "python").unwrap();
+        let comments = serde_json::from_str::<CommentCollection>(&output)
+            .unwrap()
+            .to_comments();
+
+        let texts: HashSet<_> = comments.iter().map(|c| (c.line, c.text.as_str())).collect();
+        assert!(texts.contains(&(3, "Read input")));
+        assert!(texts.contains(&(7, "process text")));
+    }
+}
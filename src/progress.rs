@@ -0,0 +1,77 @@
+use std::io::{self, IsTerminal, Write};
+
+/// Status line of a run over several files, rewritten in place as the files are checked
+///
+/// It is only shown on a terminal, so the logs of a CI job or a redirected stderr stay clean.
+pub struct Progress<W: Write> {
+    writer: W,
+    total: usize,
+    done: usize,
+    enabled: bool,
+}
+
+impl Progress<io::Stderr> {
+    /// Progress on stderr, shown for more than one file when stderr is a terminal and `quiet` is not set
+    pub fn stderr(total: usize, quiet: bool) -> Self {
+        Progress::new(io::stderr(), total, !quiet && total > 1 && io::stderr().is_terminal())
+    }
+}
+
+impl<W: Write> Progress<W> {
+    /// Create a new [`Progress`], nothing is written when it is not `enabled`
+    pub fn new(writer: W, total: usize, enabled: bool) -> Self {
+        Self {
+            writer,
+            total,
+            done: 0,
+            enabled,
+        }
+    }
+
+    /// Count a checked file and show the status line
+    pub fn tick(&mut self) {
+        self.done += 1;
+        self.draw();
+    }
+
+    /// Show the status line, e.g. before the first file is checked
+    pub fn draw(&mut self) {
+        if self.enabled {
+            let _ = write!(self.writer, "\r\x1b[Kneospeller: {}/{} file(s) checked...", self.done, self.total);
+            let _ = self.writer.flush();
+        }
+    }
+
+    /// Erase the status line, e.g. before printing other messages or at the end
+    pub fn clear(&mut self) {
+        if self.enabled {
+            let _ = write!(self.writer, "\r\x1b[K");
+            let _ = self.writer.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress() {
+        let mut output = Vec::new();
+        let mut progress = Progress::new(&mut output, 2, true);
+        progress.draw();
+        progress.tick();
+        progress.clear();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\r\x1b[Kneospeller: 0/2 file(s) checked...\r\x1b[Kneospeller: 1/2 file(s) checked...\r\x1b[K"
+        );
+
+        let mut output = Vec::new();
+        let mut progress = Progress::new(&mut output, 2, false);
+        progress.draw();
+        progress.tick();
+        progress.clear();
+        assert!(output.is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+use std::io::{self, BufRead, Write};
+
+use crate::buffer::Buffer;
+use crate::diff::CommentDiff;
+use crate::error::NeospellerError;
+use crate::language::{Comment, Language};
+use crate::SpellingReport;
+
+/// Decision of the user about a proposed correction
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    /// Apply the correction
+    Accept,
+    /// Keep the original comment
+    Reject,
+    /// Replace the comment with a text written by the user
+    Edit(String),
+}
+
+/// Apply only the corrections accepted by a decision function
+///
+/// # Params
+/// * `input`: The original source code
+/// * `language`: The programming language of the source code
+/// * `report`: The [`SpellingReport`] of the check of `input`
+/// * `decide`: Called with each changed comment, in line order
+///
+/// # Returns
+/// * The source code with the accepted and edited corrections, the rejected comments keep their original text
+pub fn review<F>(input: &str, language: Language, report: &SpellingReport, mut decide: F) -> Result<String, NeospellerError>
+where
+    F: FnMut(&CommentDiff) -> Result<Decision, NeospellerError>,
+{
+    let mut buffer = Buffer::from_string(input.to_string(), language);
+    buffer.get_comments();
+
+    let mut comments = Vec::new();
+    for diff in report.diffs() {
+        let text = match decide(&diff)? {
+            Decision::Accept => diff.after,
+            Decision::Reject => continue,
+            Decision::Edit(text) => text,
+        };
+        comments.push(Comment::new(diff.line, text, diff.comment_type));
+    }
+
+    buffer.replace_comments(&comments)?;
+    Ok(buffer.to_string())
+}
+
+/// Ask the user about a correction, until a valid answer is given
+///
+/// # Params
+/// * `diff`: The changed comment
+/// * `label`: Name of the file of the comment
+/// * `input`: Where the answers are read, e.g. stdin
+/// * `output`: Where the prompts are written, e.g. stderr
+///
+/// # Returns
+/// * The [`Decision`], a closed input rejects the correction
+pub fn prompt_decision(
+    diff: &CommentDiff,
+    label: &str,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<Decision> {
+    writeln!(output, "{}:{}\n- {}\n+ {}", label, diff.line + 1, diff.before, diff.after)?;
+
+    loop {
+        write!(output, "Apply this correction [y,n,e]? ")?;
+        output.flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(Decision::Reject);
+        }
+
+        match answer.trim() {
+            "y" | "Y" => return Ok(Decision::Accept),
+            "n" | "N" => return Ok(Decision::Reject),
+            "e" | "E" => {
+                write!(output, "New text: ")?;
+                output.flush()?;
+
+                let mut text = String::new();
+                input.read_line(&mut text)?;
+                return Ok(Decision::Edit(text.trim_end_matches(['\r', '\n']).to_string()));
+            }
+            _ => writeln!(output, "y - apply, n - keep the original, e - edit the text")?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::CommentType;
+
+    fn report(input: &str, output: &str, language: &Language) -> SpellingReport {
+        SpellingReport {
+            output: output.to_string(),
+            original: crate::extract_comments(input, language),
+            corrected: crate::extract_comments(output, language),
+        }
+    }
+
+    #[test]
+    fn test_review() {
+        let language = Language::from_name("python").unwrap();
+        let input = "# A coment\nx = 5  # The answr\n\"\"\"Docstrng\"\"\"\n";
+        let output = "# A comment\nx = 5  # The answer\n\"\"\"Docstring\"\"\"\n";
+        let report = report(input, output, &language);
+
+        let reviewed = review(input, language, &report, |diff| {
+            Ok(match diff.line {
+                0 => Decision::Reject,
+                1 => Decision::Accept,
+                _ => Decision::Edit("A docstring".to_string()),
+            })
+        })
+        .unwrap();
+
+        // The rejected correction keeps the original comment
+        assert_eq!(reviewed, "# A coment\nx = 5  # The answer\n\"\"\"A docstring\"\"\"\n");
+    }
+
+    #[test]
+    fn test_prompt_decision() {
+        let diff = CommentDiff {
+            line: 0,
+            comment_type: CommentType::Single,
+            before: "A coment".to_string(),
+            after: "A comment".to_string(),
+        };
+        let decide = |answers: &str| {
+            let mut output = Vec::new();
+            let decision = prompt_decision(&diff, "main.py", &mut answers.as_bytes(), &mut output).unwrap();
+            (decision, String::from_utf8(output).unwrap())
+        };
+
+        let (decision, output) = decide("y\n");
+        assert_eq!(decision, Decision::Accept);
+        assert!(output.starts_with("main.py:1\n- A coment\n+ A comment\n"));
+
+        assert_eq!(decide("maybe\nn\n").0, Decision::Reject);
+        assert_eq!(decide("e\nA new comment\n").0, Decision::Edit("A new comment".to_string()));
+        assert_eq!(decide("").0, Decision::Reject);
+    }
+}
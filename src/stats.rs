@@ -0,0 +1,85 @@
+use std::fmt::Display;
+
+use crate::estimate::estimate_tokens;
+use crate::language::{Comment, CommentType};
+
+/// Counts of the comments of a source code, computed without calling the model
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    /// Number of single line comments
+    pub single: usize,
+    /// Number of lines of the multi-line comments
+    pub multi: usize,
+    /// Words of every comment
+    pub words: usize,
+    /// Estimated tokens of the text of every comment
+    pub tokens: usize,
+}
+
+impl Stats {
+    /// Count the comments, e.g. the ones returned by [`crate::extract_comments`]
+    pub fn from_comments(comments: &[Comment]) -> Stats {
+        let mut stats = Stats::default();
+
+        for comment in comments {
+            match comment.comment_type {
+                CommentType::Single => stats.single += 1,
+                CommentType::Multi => stats.multi += 1,
+            }
+            stats.words += comment.text.split_whitespace().count();
+            stats.tokens += estimate_tokens(&comment.text);
+        }
+
+        stats
+    }
+
+    /// Add the counts of another source code, e.g. of another file
+    pub fn add(&mut self, other: &Stats) {
+        self.single += other.single;
+        self.multi += other.multi;
+        self.words += other.words;
+        self.tokens += other.tokens;
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} single line comment(s), {} multi-line comment line(s), {} word(s), ~{} token(s)",
+            self.single, self.multi, self.words, self.tokens
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract_comments;
+    use crate::language::Language;
+
+    #[test]
+    fn test_stats() {
+        let language = Language::from_name("python").unwrap();
+        let input = "# Read the input\nx = 5  # The answr\n\"\"\"\nA docstring\nin two lines\n\"\"\"\n";
+
+        let stats = Stats::from_comments(&extract_comments(input, &language));
+        assert_eq!(
+            stats,
+            Stats {
+                single: 2,
+                multi: 2,
+                words: 10,
+                tokens: 13,
+            }
+        );
+        assert_eq!(
+            stats.to_string(),
+            "2 single line comment(s), 2 multi-line comment line(s), 10 word(s), ~13 token(s)"
+        );
+
+        let mut total = stats.clone();
+        total.add(&stats);
+        assert_eq!(total.words, 20);
+    }
+}
@@ -1,46 +1,91 @@
-use neospeller::language::Language;
+#![cfg(feature = "grammar")]
+
+use neospeller::error::NeospellerError;
+use neospeller::grammar::{CheckMode, GrammarConfig, GrammarProvider, ProviderKind};
+use neospeller::language::{Comment, CommentCollection, CommentType, Language};
 use serde_json::json;
 use std::env;
+use std::io::Write;
+use std::sync::Mutex;
 
-#[test]
-fn test_complete_spellcheck_workflow() {
-    // Start mock server
-    let mut server = mockito::Server::new();
+/// Words of the test comments, the word list of the offline provider
+const WORDS: &str = "a\nanother\ncomment\nfunction\nline\nthe\n";
 
-    // Create mock API response that simulates OpenAI's response
-    let mock_response = json!({
+/// Path of a new temporary file or directory, unique per test and per run,
+/// e.g. `temp_path("words.txt")`
+fn temp_path(name: &str) -> std::path::PathBuf {
+    static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    env::temp_dir().join(format!("neospeller-{}-{}-{}-{}", std::process::id(), nanos, count, name))
+}
+
+/// Write [`WORDS`] to a new temporary file, for `--dict`
+fn dict_file() -> std::path::PathBuf {
+    let path = temp_path("words.txt");
+    std::fs::write(&path, WORDS).unwrap();
+    path
+}
+
+/// The mock server URL is shared through environment variables,
+/// so tests that depend on it must not run concurrently
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Body of a chat completion of OpenAI, also the one of Ollama and Azure
+fn chat_completion(content: &str) -> String {
+    json!({
         "choices": [{
-            "message": {
-                "role": "assistant",
-                "content": json!({
-                    "single_comments": {
-                        "16": "Read input from standard input (stdin)",
-                        "19": "Process text for send to an API",
-                        "22": "Print the modified text to standard output (stdout)"
-                    },
-                    "multiline_comments": {
-                            "2": "Demo script for reading and writing text using standard input and output.",
-                            "3": "This script simulates processing text and displaying a modified result.",
-                            "10": "Simulates text processing by reversing the input.",
-                            "11": "This is just a placeholder for demonstration purposes."
-                        }
-                }).to_string()
-            },
-        "index": 0
+            "message": {"role": "assistant", "content": content},
+            "index": 0
         }],
-    });
+    })
+    .to_string()
+}
+
+/// Body of a chat completion that answers with the corrected comments
+fn chat_completion_body(single: serde_json::Value, multi: serde_json::Value) -> String {
+    chat_completion(&json!({"single_comments": single, "multiline_comments": multi}).to_string())
+}
 
-    // Set mock server URL as environment variable
+/// Point the OpenAI provider to the mock server and mock a successful chat completion,
+/// the caller sets the body and creates the mock
+fn mock_openai(server: &mut mockito::Server) -> mockito::Mock {
     env::set_var("OPENAI_API_KEY", "test_key");
     env::set_var("OPENAI_API_BASE_URL", server.url());
 
-    // Setup mock endpoint
-    let mock = server
+    server
         .mock("POST", "/v1/chat/completions")
         .with_status(200)
         .with_header("Content-Type", "application/json")
-        .with_body(mock_response.to_string())
-        .create();
+}
+
+#[test]
+fn test_complete_spellcheck_workflow() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    // Start mock server
+    let mut server = mockito::Server::new();
+
+    // Create mock API response that simulates OpenAI's response
+    let mock_response = chat_completion_body(
+        json!({
+            "16": "Read input from standard input (stdin)",
+            "19": "Process text for send to an API",
+            "22": "Print the modified text to standard output (stdout)"
+        }),
+        json!({
+            "2": "Demo script for reading and writing text using standard input and output.",
+            "3": "This script simulates processing text and displaying a modified result.",
+            "10": "Simulates text processing by reversing the input.",
+            "11": "This is just a placeholder for demonstration purposes."
+        }),
+    );
+
+    // Set mock server URL as environment variable and setup mock endpoint
+    let mock = mock_openai(&mut server).with_body(&mock_response).create();
 
     // Input Python code with intentional spelling errors
     let input = r#"
@@ -102,12 +147,7 @@ if __name__ == "__main__":
     main()
 "#;
 
-    let language = Language {
-        name: "python".to_string(),
-        comment_symbol: "#".to_string(),
-        ml_comment_symbol: "\"\"\"".to_string(),
-        ml_comment_symbol_close: "\"\"\"".to_string(),
-    };
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
 
     // Run the spell checker through the main entry point
     let result = neospeller::check_spelling(input.to_string(), language).unwrap();
@@ -118,3 +158,1445 @@ if __name__ == "__main__":
     // Verify the result matches expected output
     assert_eq!(result.trim(), expected_output.trim());
 }
+
+#[test]
+fn test_sequential_calls_reuse_client() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    let mock_response = chat_completion_body(json!({"0": "A comment"}), json!({}));
+
+    let mock = mock_openai(&mut server)
+        .with_body(&mock_response)
+        .expect(2)
+        .create();
+
+    for _ in 0..2 {
+        let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+        let result = neospeller::check_spelling("# A coment".to_string(), language).unwrap();
+        assert_eq!(result, "# A comment");
+    }
+
+    mock.assert();
+}
+
+#[test]
+fn test_anthropic_provider() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    let mock_response = json!({
+        "content": [{
+            "type": "text",
+            "text": json!({
+                "single_comments": {"0": "A comment"},
+                "multiline_comments": {}
+            }).to_string()
+        }],
+    });
+
+    env::set_var("ANTHROPIC_API_KEY", "test_key");
+    env::set_var("ANTHROPIC_API_BASE_URL", server.url());
+
+    let mock = server
+        .mock("POST", "/v1/messages")
+        .match_header("x-api-key", "test_key")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(mock_response.to_string())
+        .create();
+
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+    let config = GrammarConfig {
+        provider: ProviderKind::Anthropic,
+        ..GrammarConfig::default()
+    };
+    let result = neospeller::check_spelling_with_config("# A coment".to_string(), language, &config).unwrap();
+
+    mock.assert();
+    assert_eq!(result, "# A comment");
+}
+
+#[test]
+fn test_anthropic_api_key_file() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+    let mock_response = json!({
+        "content": [{
+            "type": "text",
+            "text": json!({"single_comments": {"0": "A comment"}, "multiline_comments": {}}).to_string()
+        }],
+    });
+
+    // The key of the file takes precedence over the environment variable
+    env::set_var("ANTHROPIC_API_KEY", "env_key");
+    env::set_var("ANTHROPIC_API_BASE_URL", server.url());
+
+    let mock = server
+        .mock("POST", "/v1/messages")
+        .match_header("x-api-key", "file_key")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(mock_response.to_string())
+        .create();
+
+    let path = temp_path("anthropic-key.txt");
+    std::fs::write(&path, "file_key\n").unwrap();
+
+    let config = GrammarConfig {
+        provider: ProviderKind::Anthropic,
+        api_key_file: Some(path.clone()),
+        ..GrammarConfig::default()
+    };
+    let language = Language::from_name("python").unwrap();
+    let result = neospeller::check_spelling_with_config("# A coment".to_string(), language, &config).unwrap();
+
+    mock.assert();
+    assert_eq!(result, "# A comment");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_anthropic_multi_line_correction() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    // The model answers with indented JSON over several lines
+    let corrections = json!({
+        "single_comments": {"4": "Say \"hello\" to the user"},
+        "multiline_comments": {"1": "The first line of the docstring", "2": "and the second line"}
+    });
+    let mock_response = json!({
+        "content": [{
+            "type": "text",
+            "text": format!("```json\n{}\n```", serde_json::to_string_pretty(&corrections).unwrap())
+        }],
+    });
+
+    env::set_var("ANTHROPIC_API_KEY", "test_key");
+    env::set_var("ANTHROPIC_API_BASE_URL", server.url());
+
+    let mock = server
+        .mock("POST", "/v1/messages")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(mock_response.to_string())
+        .create();
+
+    let input = "\"\"\"\nThe frist line of the docstring\nand the secnd line\n\"\"\"\n# Say \"helo\" to the user\n";
+    let config = GrammarConfig {
+        provider: ProviderKind::Anthropic,
+        ..GrammarConfig::default()
+    };
+    let language = Language::from_name("python").unwrap();
+    let result = neospeller::check_spelling_with_config(input.to_string(), language, &config).unwrap();
+
+    mock.assert();
+    assert_eq!(
+        result,
+        "\"\"\"\nThe first line of the docstring\nand the second line\n\"\"\"\n# Say \"hello\" to the user\n"
+    );
+}
+
+/// Provider that mangles URLs and fixes a typo, like a careless model would
+struct UrlManglingProvider;
+
+impl GrammarProvider for UrlManglingProvider {
+    fn check(&self, json_data: &str, _language: &str) -> Result<String, NeospellerError> {
+        Ok(json_data
+            .replace("Se ", "See ")
+            .replace("://", ": //")
+            .replace("example", "exemple"))
+    }
+}
+
+#[test]
+fn test_urls_are_not_corrected() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+    let input = "# Se https://example.com/docs for details";
+
+    let result =
+        neospeller::check_spelling_with_provider(input.to_string(), language, &UrlManglingProvider).unwrap();
+
+    assert_eq!(result, "# See https://example.com/docs for details");
+}
+
+#[test]
+fn test_check_mode_detects_corrections() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    let mock_response = chat_completion_body(json!({"0": "A comment", "2": "Already correct"}), json!({}));
+
+    let _mock = mock_openai(&mut server).with_body(&mock_response).create();
+
+    let input = "# A coment\nx = 5\n# Already correct\n";
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+    let result = neospeller::check_spelling(input.to_string(), language).unwrap();
+
+    // `--check` exits with an error when there is some changed line
+    assert_eq!(neospeller::diff::changed_lines(input, &result), 1);
+    assert_eq!(neospeller::diff::changed_lines(&result, &result), 0);
+}
+
+/// Provider that truncates the response of large requests, like a model
+/// reaching its maximum completion tokens
+struct TruncatingProvider {
+    calls: std::cell::Cell<usize>,
+}
+
+impl GrammarProvider for TruncatingProvider {
+    fn check(&self, json_data: &str, _language: &str) -> Result<String, NeospellerError> {
+        self.calls.set(self.calls.get() + 1);
+
+        let output = json_data.replace("coment", "comment");
+        if output.len() > 300 {
+            return Ok(output[..300].to_string());
+        }
+        Ok(output)
+    }
+}
+
+#[test]
+fn test_large_files_are_chunked() {
+    let input: Vec<String> = (0..40).map(|i| format!("x = {}  # coment number {}", i, i)).collect();
+    let input = input.join("\n");
+
+    let provider = TruncatingProvider {
+        calls: std::cell::Cell::new(0),
+    };
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+    let config = GrammarConfig {
+        chunk_size: 200,
+        ..GrammarConfig::default()
+    };
+    let report = neospeller::check_spelling_report(input.clone(), language, &provider, &config).unwrap();
+
+    assert_eq!(report.output, input.replace("coment", "comment"));
+    assert!(provider.calls.get() > 1);
+}
+
+#[test]
+fn test_configured_model_is_sent() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    let mock_response = chat_completion_body(json!({"0": "A comment"}), json!({}));
+
+    let mock = mock_openai(&mut server)
+        .match_body(mockito::Matcher::PartialJson(json!({"model": "gpt-4o"})))
+        .with_body(&mock_response)
+        .create();
+
+    let config = GrammarConfig {
+        model: Some("gpt-4o".to_string()),
+        ..GrammarConfig::default()
+    };
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+    let result = neospeller::check_spelling_with_config("# A coment".to_string(), language, &config).unwrap();
+
+    mock.assert();
+    assert_eq!(result, "# A comment");
+}
+
+#[test]
+fn test_configured_temperature_and_max_tokens_are_sent() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    let mock_response = chat_completion_body(json!({"0": "A comment"}), json!({}));
+
+    let mock = mock_openai(&mut server)
+        .match_body(mockito::Matcher::PartialJson(
+            json!({"temperature": 0.0, "max_completion_tokens": 8000}),
+        ))
+        .with_body(&mock_response)
+        .create();
+
+    let config = GrammarConfig {
+        temperature: GrammarConfig::parse_temperature("0").unwrap(),
+        max_tokens: GrammarConfig::parse_max_tokens("8000").unwrap(),
+        ..GrammarConfig::default()
+    };
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+    let result = neospeller::check_spelling_with_config("# A coment".to_string(), language, &config).unwrap();
+
+    mock.assert();
+    assert_eq!(result, "# A comment");
+}
+
+/// Provider that drops the comment of the first line, like a model
+/// that loses track of the line numbers
+struct DroppingProvider;
+
+impl GrammarProvider for DroppingProvider {
+    fn check(&self, _json_data: &str, _language: &str) -> Result<String, NeospellerError> {
+        Ok(json!({
+            "single_comments": {"2": "Another comment"},
+            "multiline_comments": {}
+        })
+        .to_string())
+    }
+}
+
+#[test]
+fn test_missing_lines_keep_original_text() {
+    let input = "# A coment\nx = 5\n# Anothr coment";
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+
+    let config = GrammarConfig::default();
+    let report =
+        neospeller::check_spelling_report(input.to_string(), language.clone(), &DroppingProvider, &config).unwrap();
+    assert_eq!(report.output, "# A coment\nx = 5\n# Another comment");
+
+    let config = GrammarConfig {
+        strict: true,
+        ..GrammarConfig::default()
+    };
+    let err = neospeller::check_spelling_report(input.to_string(), language, &DroppingProvider, &config).unwrap_err();
+    assert!(matches!(err, NeospellerError::ApiResponse(_)));
+}
+
+#[test]
+fn test_write_file_in_place() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    let mock_response = chat_completion_body(json!({"0": "A comment"}), json!({}));
+
+    let mock = mock_openai(&mut server).with_body(&mock_response).create();
+
+    let path = temp_path("in-place.py");
+    std::fs::write(&path, "# A coment\nx = 5\n").unwrap();
+
+    let config = GrammarConfig::default();
+    let provider = config.provider().unwrap();
+    let file = neospeller::files::check_file(&path, None, provider.as_ref(), &config).unwrap();
+    assert!(file.changed());
+    neospeller::files::write_file(&file).unwrap();
+
+    mock.assert();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "# A comment\nx = 5\n");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_directory_files_are_processed() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let dir = temp_path("dir");
+    std::fs::create_dir_all(dir.join("node_modules")).unwrap();
+    std::fs::write(dir.join("first.py"), "# A coment\n").unwrap();
+    std::fs::write(dir.join("second.py"), "x = 5  # the coment\n").unwrap();
+    std::fs::write(dir.join("node_modules/ignored.py"), "# A coment\n").unwrap();
+
+    let config = GrammarConfig {
+        provider: ProviderKind::Offline,
+        dict: Some(dict_file()),
+        ..GrammarConfig::default()
+    };
+    let provider = config.provider().unwrap();
+
+    let files = neospeller::files::collect_files(std::slice::from_ref(&dir), None, &[], &[]).unwrap();
+    assert_eq!(files.len(), 2);
+
+    for path in &files {
+        let file = neospeller::files::check_file(path, None, provider.as_ref(), &config).unwrap();
+        neospeller::files::write_file(&file).unwrap();
+    }
+
+    assert_eq!(std::fs::read_to_string(dir.join("first.py")).unwrap(), "# A comment\n");
+    assert_eq!(std::fs::read_to_string(dir.join("second.py")).unwrap(), "x = 5  # the comment\n");
+    assert_eq!(std::fs::read_to_string(dir.join("node_modules/ignored.py")).unwrap(), "# A coment\n");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_spelling_mode_prompt_is_sent() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    let mock_response = chat_completion_body(json!({"0": "A comment"}), json!({}));
+
+    let mock = mock_openai(&mut server)
+        .match_body(mockito::Matcher::Regex("fix only the misspelled words".to_string()))
+        .with_body(&mock_response)
+        .create();
+
+    let config = GrammarConfig {
+        mode: CheckMode::Spelling,
+        ..GrammarConfig::default()
+    };
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+    let result = neospeller::check_spelling_with_config("# A coment".to_string(), language, &config).unwrap();
+
+    mock.assert();
+    assert_eq!(result, "# A comment");
+}
+
+#[test]
+fn test_custom_prompt_is_sent() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    let mock_response = json!({
+        "content": [{
+            "type": "text",
+            "text": json!({
+                "single_comments": {"0": "An idempotent comment"},
+                "multiline_comments": {}
+            }).to_string()
+        }],
+    });
+
+    env::set_var("ANTHROPIC_API_KEY", "test_key");
+    env::set_var("ANTHROPIC_API_BASE_URL", server.url());
+
+    let mock = server
+        .mock("POST", "/v1/messages")
+        .match_body(mockito::Matcher::PartialJson(json!({
+            "system": "Fix the python comments, keep the word idempotent."
+        })))
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(mock_response.to_string())
+        .create();
+
+    let path = temp_path("custom-prompt.txt");
+    std::fs::write(&path, "Fix the {language} comments, keep the word idempotent.").unwrap();
+
+    let config = GrammarConfig {
+        provider: ProviderKind::Anthropic,
+        prompt_file: Some(path.clone()),
+        ..GrammarConfig::default()
+    };
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+    let result =
+        neospeller::check_spelling_with_config("# An idempotent coment".to_string(), language, &config).unwrap();
+
+    mock.assert();
+    assert_eq!(result, "# An idempotent comment");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// Provider that "corrects" valid jargon, like a model that doesn't know it
+struct JargonCorrectingProvider;
+
+impl GrammarProvider for JargonCorrectingProvider {
+    fn check(&self, json_data: &str, _language: &str) -> Result<String, NeospellerError> {
+        Ok(json_data.replace("serde", "serve").replace("coment", "comment"))
+    }
+}
+
+#[test]
+fn test_ignored_words_are_not_changed() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let path = temp_path("ignore-words.txt");
+    std::fs::write(&path, "serde\n").unwrap();
+
+    let config = GrammarConfig {
+        ignore_words: Some(path.clone()),
+        ..GrammarConfig::default()
+    };
+    let language = Language::new("rust", "//", "/*", "*/");
+    let input = "// A coment about serde".to_string();
+    let report = neospeller::check_spelling_report(input, language, &JargonCorrectingProvider, &config).unwrap();
+
+    assert_eq!(report.output, "// A comment about serde");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_azure_provider() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    let mock_response = chat_completion_body(json!({"0": "A comment"}), json!({}));
+
+    env::set_var("AZURE_OPENAI_API_KEY", "azure_key");
+    env::set_var("AZURE_OPENAI_ENDPOINT", format!("{}/", server.url()));
+    env::set_var("AZURE_OPENAI_DEPLOYMENT", "neospeller-deployment");
+    env::set_var("AZURE_OPENAI_API_VERSION", "2024-06-01");
+
+    let mock = server
+        .mock("POST", "/openai/deployments/neospeller-deployment/chat/completions")
+        .match_query(mockito::Matcher::UrlEncoded("api-version".into(), "2024-06-01".into()))
+        .match_header("api-key", "azure_key")
+        .match_header("Authorization", mockito::Matcher::Missing)
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(&mock_response)
+        .create();
+
+    let config = GrammarConfig {
+        provider: ProviderKind::Azure,
+        ..GrammarConfig::default()
+    };
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+    let result = neospeller::check_spelling_with_config("# A coment".to_string(), language, &config).unwrap();
+
+    mock.assert();
+    assert_eq!(result, "# A comment");
+}
+
+#[test]
+fn test_ollama_provider_without_api_key() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    let mock_response = chat_completion_body(json!({"0": "A comment"}), json!({}));
+
+    env::remove_var("OPENAI_API_KEY");
+    env::set_var("OLLAMA_API_BASE_URL", server.url());
+
+    let mock = server
+        .mock("POST", "/v1/chat/completions")
+        .match_header("Authorization", mockito::Matcher::Missing)
+        .match_body(mockito::Matcher::PartialJson(json!({"model": "llama3.2"})))
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(&mock_response)
+        .create();
+
+    let config = GrammarConfig {
+        provider: ProviderKind::Ollama,
+        ..GrammarConfig::default()
+    };
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+    let result = neospeller::check_spelling_with_config("# A coment".to_string(), language, &config).unwrap();
+
+    mock.assert();
+    assert_eq!(result, "# A comment");
+}
+
+#[test]
+fn test_error_variants() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    env::set_var("OPENAI_API_BASE_URL", server.url());
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+    let config = GrammarConfig::default();
+
+    // Missing API key
+    env::remove_var("OPENAI_API_KEY");
+    let err = neospeller::check_spelling_with_config("# A coment".to_string(), language.clone(), &config).unwrap_err();
+    assert!(matches!(err, NeospellerError::Config(_)));
+
+    env::set_var("OPENAI_API_KEY", "test_key");
+
+    // Server error
+    let mock = server
+        .mock("POST", "/v1/chat/completions")
+        .with_status(500)
+        .with_body("Internal Server Error")
+        .create();
+    let err = neospeller::check_spelling_with_config("# A coment".to_string(), language.clone(), &config).unwrap_err();
+    assert!(matches!(err, NeospellerError::ApiResponse(_)));
+    mock.remove();
+
+    // The model answers with something that is not the comments JSON
+    let mock_response = chat_completion("I cannot help with that");
+    let _mock = mock_openai(&mut server).with_body(&mock_response).create();
+    let err = neospeller::check_spelling_with_config("# A coment".to_string(), language, &config).unwrap_err();
+    assert!(matches!(err, NeospellerError::JsonParse(_)));
+
+    // Unsupported language
+    let err: NeospellerError = Language::from_name("cobol").unwrap_err().into();
+    assert!(matches!(err, NeospellerError::UnsupportedLanguage(_)));
+}
+
+#[test]
+fn test_verbose_logging_keeps_output() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let _ = env_logger::builder()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+    let input = "# Se the docs\nx = 1  # Se below";
+
+    let result =
+        neospeller::check_spelling_with_provider(input.to_string(), language, &UrlManglingProvider).unwrap();
+
+    assert_eq!(result, "# See the docs\nx = 1  # See below");
+}
+
+#[test]
+fn test_estimate_makes_no_api_call() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    let mock = mock_openai(&mut server).expect(0).create();
+
+    let config = GrammarConfig {
+        model: Some("gpt-4o".to_string()),
+        ..GrammarConfig::default()
+    };
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+    let estimate =
+        neospeller::estimate::estimate("# A coment\nx = 1  # Anoter one", &language, &config).unwrap();
+
+    mock.assert();
+    assert_eq!(estimate.requests, 1);
+    assert!(estimate.input_tokens > estimate.output_tokens);
+    assert!(estimate.cost().unwrap() > 0.0);
+    assert!(estimate.to_string().contains("gpt-4o, $2.50/$10.00 per million tokens"));
+}
+
+#[test]
+fn test_cached_comments_are_not_sent() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    let mock_response = chat_completion_body(json!({"0": "A comment"}), json!({}));
+
+    let mock = mock_openai(&mut server)
+        .with_body(&mock_response)
+        .expect(1)
+        .create();
+
+    let dir = temp_path("cache");
+    let config = GrammarConfig {
+        cache_dir: Some(dir.clone()),
+        ..GrammarConfig::default()
+    };
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+
+    let first = neospeller::check_spelling_with_config("# A coment".to_string(), language.clone(), &config).unwrap();
+    let second = neospeller::check_spelling_with_config("# A coment".to_string(), language, &config).unwrap();
+
+    // Only the first run reaches the API
+    mock.assert();
+    assert_eq!(first, "# A comment");
+    assert_eq!(second, first);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_files_are_checked_concurrently() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    let mock_response = chat_completion_body(json!({"0": "A comment"}), json!({}));
+
+    let mock = mock_openai(&mut server)
+        .with_body(&mock_response)
+        .expect(5)
+        .create();
+
+    let dir = temp_path("jobs");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut paths = Vec::new();
+    for i in 0..6 {
+        let path = dir.join(format!("file{}.py", i));
+        // One of the files is missing, its failure must not abort the others
+        if i != 3 {
+            std::fs::write(&path, format!("# A coment\nx = {}\n", i)).unwrap();
+        }
+        paths.push(path);
+    }
+
+    let config = GrammarConfig::default();
+    let provider = config.provider().unwrap();
+    let results = neospeller::files::check_files(&paths, None, provider.as_ref(), &config, 3);
+
+    mock.assert();
+    assert_eq!(results.len(), paths.len());
+    for (i, (path, result)) in paths.iter().zip(&results).enumerate() {
+        match result {
+            Ok(file) => {
+                assert_eq!(&file.path, path);
+                assert_eq!(file.report.output, format!("# A comment\nx = {}\n", i));
+            }
+            Err(err) => {
+                assert_eq!(i, 3);
+                assert!(matches!(err, NeospellerError::Io(_)));
+            }
+        }
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_api_error_is_explained() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    env::set_var("OPENAI_API_KEY", "bad_key");
+    env::set_var("OPENAI_API_BASE_URL", server.url());
+
+    let error_body = json!({
+        "error": {
+            "message": "Incorrect API key provided: bad_key.",
+            "type": "invalid_request_error",
+            "param": null,
+            "code": "invalid_api_key"
+        }
+    });
+    let mock = server
+        .mock("POST", "/v1/chat/completions")
+        .with_status(401)
+        .with_header("Content-Type", "application/json")
+        .with_body(error_body.to_string())
+        .create();
+
+    let language = Language::new("python", "#", "\"\"\"", "\"\"\"");
+    let err = neospeller::check_spelling_with_config("# A coment".to_string(), language, &GrammarConfig::default())
+        .unwrap_err();
+
+    mock.assert();
+    assert!(matches!(err, NeospellerError::ApiResponse(_)));
+    assert!(err.to_string().contains("401"));
+    assert!(err.to_string().contains("Incorrect API key provided: bad_key. (invalid_request_error)"));
+}
+
+#[test]
+fn test_escaped_characters_are_preserved() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    // Pretty printed JSON, with escaped quotes, tabs and newlines in the values
+    let content = "{\n  \"single_comments\": {\"0\": \"A \\\"quoted\\\"\\tcomment\"},\n  \"multiline_comments\": {\"1\": \"First line\\nSecond line\"}\n}";
+    let mock_response = chat_completion(content);
+
+    env::remove_var("NEOSPELLER_PROVIDER");
+
+    let _mock = mock_openai(&mut server).with_body(&mock_response).create();
+
+    let comments = neospeller::grammar::check_grammar(&CommentCollection::from_comments(Vec::new()), "python").unwrap();
+
+    assert_eq!(
+        comments.get(0, CommentType::Single).map(String::as_str),
+        Some("A \"quoted\"\tcomment")
+    );
+    assert_eq!(
+        comments.get(1, CommentType::Multi).map(String::as_str),
+        Some("First line\nSecond line")
+    );
+}
+
+#[test]
+fn test_typed_check_matches_json_check() {
+    let comments = CommentCollection::from_comments(vec![
+        Comment {
+            line: 0,
+            col: 0,
+            text: "A coment about serde".to_string(),
+            comment_type: CommentType::Single,
+        },
+        Comment {
+            line: 2,
+            col: 0,
+            text: "Another coment\nin two lines".to_string(),
+            comment_type: CommentType::Multi,
+        },
+    ]);
+
+    let typed = JargonCorrectingProvider.check_comments(&comments, "rust").unwrap();
+
+    let json_data = serde_json::to_string(&comments).unwrap();
+    let output = JargonCorrectingProvider.check(&json_data, "rust").unwrap();
+    let parsed: CommentCollection = serde_json::from_str(&output).unwrap();
+
+    let sorted = |collection: &CommentCollection| {
+        let mut comments = collection.to_comments();
+        comments.sort_by_key(|c| c.line);
+        comments
+    };
+    assert_eq!(sorted(&typed), sorted(&parsed));
+    assert_eq!(
+        typed.get(2, CommentType::Multi).map(String::as_str),
+        Some("Another comment\nin two lines")
+    );
+}
+
+#[test]
+fn test_fix_mode_exit_code() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let dir = temp_path("fix");
+    std::fs::create_dir_all(&dir).unwrap();
+    let wrong = dir.join("wrong.py");
+    let correct = dir.join("correct.rs");
+    std::fs::write(&wrong, "# A coment\nx = 5\n").unwrap();
+    std::fs::write(&correct, "// A comment\nlet x = 5;\n").unwrap();
+
+    let run = || {
+        std::process::Command::new(env!("CARGO_BIN_EXE_neospeller"))
+            .args(["--fix", "--provider", "offline", "--no-cache", "--dict"])
+            .arg(dict_file())
+            .arg(&wrong)
+            .arg(&correct)
+            .output()
+            .unwrap()
+    };
+
+    // A file has been fixed, the commit is blocked until it is re-staged
+    let output = run();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(std::fs::read_to_string(&wrong).unwrap(), "# A comment\nx = 5\n");
+    assert_eq!(std::fs::read_to_string(&correct).unwrap(), "// A comment\nlet x = 5;\n");
+
+    // Nothing left to fix
+    let output = run();
+    assert_eq!(output.status.code(), Some(0));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_edits_reconstruct_corrected_buffer() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    let mock_response = chat_completion_body(
+        json!({"0": "A comment", "1": "Already fine", "5": "Another comment"}),
+        json!({"2": "Docstring that", "3": "spans lines"}),
+    );
+
+    env::remove_var("NEOSPELLER_PROVIDER");
+
+    let _mock = mock_openai(&mut server).with_body(&mock_response).create();
+
+    let input = "x = 5  # A coment\ny = 6  # Already fine\n\"\"\"Docstrng that\nspans lines\"\"\"\n\nz = \"# coment\"  # Anotherr coment\n";
+    let language = Language::from_name("python").unwrap();
+
+    let (output, edits) = neospeller::check_spelling_with_edits(input.to_string(), language).unwrap();
+
+    assert_eq!(
+        output,
+        "x = 5  # A comment\ny = 6  # Already fine\n\"\"\"Docstring that\nspans lines\"\"\"\n\nz = \"# coment\"  # Another comment\n"
+    );
+    assert_eq!(edits.len(), 3);
+    assert_eq!((edits[2].start_line, edits[2].start_col), (5, 18));
+
+    // Apply the edits from the end, so the columns of the previous ones stay valid
+    let mut lines: Vec<String> = input.lines().map(String::from).collect();
+    for edit in edits.iter().rev() {
+        assert_eq!(edit.start_line, edit.end_line);
+        lines[edit.start_line].replace_range(edit.start_col..edit.end_col, &edit.new_text);
+    }
+    assert_eq!(lines.join("\n") + "\n", output);
+}
+
+#[test]
+fn test_jsonl_output_is_grouped_by_file() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let dir = temp_path("jsonl");
+    std::fs::create_dir_all(&dir).unwrap();
+    let first = dir.join("first.py");
+    let second = dir.join("second.rs");
+    let correct = dir.join("correct.py");
+    std::fs::write(&first, "# A coment\nx = 5\n# Anothr line\n").unwrap();
+    std::fs::write(&second, "// The functon\nfn main() {}\n").unwrap();
+    std::fs::write(&correct, "# A comment\n").unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_neospeller"))
+        .args(["--format", "jsonl", "--provider", "offline", "--no-cache", "--jobs", "2", "--dict"])
+        .arg(dict_file())
+        .arg(&first)
+        .arg(&second)
+        .arg(&correct)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let records: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records.len(), 3);
+
+    let first = first.display().to_string();
+    let first_records: Vec<&serde_json::Value> = records.iter().filter(|r| r["file"] == first.as_str()).collect();
+    assert_eq!(first_records.len(), 2);
+    assert_eq!(
+        *first_records[0],
+        json!({"file": first, "line": 0, "before": "A coment", "after": "A comment"})
+    );
+    assert_eq!(first_records[1]["after"], "Another line");
+
+    let second = second.display().to_string();
+    let second_records: Vec<&serde_json::Value> = records.iter().filter(|r| r["file"] == second.as_str()).collect();
+    assert_eq!(second_records.len(), 1);
+    assert_eq!(second_records[0]["after"], "The function");
+
+    // The records of a file are never interleaved with the ones of another file
+    let changes_of_file = records.windows(2).filter(|pair| pair[0]["file"] != pair[1]["file"]).count();
+    assert_eq!(changes_of_file, 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Records the comments sent to the model and returns them unchanged
+struct RecordingProvider {
+    sent: std::cell::RefCell<Vec<CommentCollection>>,
+}
+
+impl GrammarProvider for RecordingProvider {
+    fn check(&self, json_data: &str, _language: &str) -> Result<String, NeospellerError> {
+        self.sent.borrow_mut().push(serde_json::from_str(json_data)?);
+        Ok(json_data.to_string())
+    }
+}
+
+/// Provider that fixes a list of typos in the sent JSON, as a model would
+struct FixingProvider {
+    sent: std::cell::RefCell<Vec<String>>,
+    fixes: &'static [(&'static str, &'static str)],
+}
+
+impl GrammarProvider for FixingProvider {
+    fn check(&self, json_data: &str, _language: &str) -> Result<String, NeospellerError> {
+        self.sent.borrow_mut().push(json_data.to_string());
+        Ok(self.fixes.iter().fold(json_data.to_string(), |text, (from, to)| text.replace(from, to)))
+    }
+}
+
+#[test]
+fn test_commented_out_code_is_not_sent() {
+    let language = Language::from_name("python").unwrap();
+    let input = "# Print the profiles to compare them\n# print(f\"profile_list[{position}]: {profiles_list[position]}\")\nx = 5\n";
+
+    let check = |skip_code: bool| {
+        let provider = RecordingProvider {
+            sent: std::cell::RefCell::new(Vec::new()),
+        };
+        let config = GrammarConfig {
+            skip_code,
+            ..GrammarConfig::default()
+        };
+        let report = neospeller::check_spelling_report(input.to_string(), language.clone(), &provider, &config).unwrap();
+        assert_eq!(report.output, input);
+
+        let sent = provider.sent.into_inner();
+        assert_eq!(sent.len(), 1);
+        sent.into_iter().next().unwrap()
+    };
+
+    let sent = check(true);
+    assert_eq!(sent.keys(), vec![(0, CommentType::Single)]);
+    assert_eq!(sent.get(1, CommentType::Single), None);
+
+    // The code is sent when the heuristic is disabled
+    let sent = check(false);
+    assert_eq!(sent.keys(), vec![(0, CommentType::Single), (1, CommentType::Single)]);
+}
+
+#[test]
+fn test_short_comments_are_not_sent() {
+    let language = Language::from_name("rust").unwrap();
+    let input = "// TODO\nlet x = 5; // hack\n// Compute the totl of the items\n";
+
+    let provider = RecordingProvider {
+        sent: std::cell::RefCell::new(Vec::new()),
+    };
+    let config = GrammarConfig {
+        min_words: 3,
+        ..GrammarConfig::default()
+    };
+    let report = neospeller::check_spelling_report(input.to_string(), language, &provider, &config).unwrap();
+    assert_eq!(report.output, input);
+
+    let sent = provider.sent.into_inner();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].keys(), vec![(2, CommentType::Single)]);
+    assert_eq!(
+        sent[0].get(2, CommentType::Single).map(String::as_str),
+        Some("Compute the totl of the items")
+    );
+}
+
+#[test]
+fn test_only_lines_in_range_are_corrected() {
+    let language = Language::from_name("python").unwrap();
+    let input = "# A coment\nx = 5  # Anothr line\n\"\"\"\nThe functon\n\"\"\"\n# Anothr line\n";
+
+    let config = GrammarConfig {
+        provider: ProviderKind::Offline,
+        dict: Some(dict_file()),
+        only_lines: Some(GrammarConfig::parse_line_range("2:4").unwrap()),
+        ..GrammarConfig::default()
+    };
+    let provider = config.provider().unwrap();
+    let report = neospeller::check_spelling_report(input.to_string(), language, provider.as_ref(), &config).unwrap();
+
+    // The comments of the lines 1 and 6 are outside the range, they are untouched
+    assert_eq!(
+        report.output,
+        "# A coment\nx = 5  # Another line\n\"\"\"\nThe function\n\"\"\"\n# Anothr line\n"
+    );
+
+    // A range after the end of the file checks nothing
+    let config = GrammarConfig {
+        only_lines: Some(GrammarConfig::parse_line_range("100:200").unwrap()),
+        ..config
+    };
+    let language = Language::from_name("python").unwrap();
+    let report = neospeller::check_spelling_report(input.to_string(), language, provider.as_ref(), &config).unwrap();
+    assert_eq!(report.output, input);
+}
+
+#[test]
+fn test_unterminated_comment_fails_in_strict_mode() {
+    let language = Language::from_name("rust").unwrap();
+    let input = "/* A coment\nfn main() {}\n";
+
+    let config = GrammarConfig {
+        provider: ProviderKind::Offline,
+        dict: Some(dict_file()),
+        ..GrammarConfig::default()
+    };
+    let provider = config.provider().unwrap();
+    let report = neospeller::check_spelling_report(input.to_string(), language.clone(), provider.as_ref(), &config).unwrap();
+    assert_eq!(report.output, "/* A comment\nfn main() {}\n");
+
+    let config = GrammarConfig { strict: true, ..config };
+    let err = neospeller::check_spelling_report(input.to_string(), language, provider.as_ref(), &config).unwrap_err();
+    assert!(matches!(err, NeospellerError::UnterminatedComment(0)));
+    assert_eq!(err.to_string(), "Unterminated comment in line 1");
+}
+
+#[test]
+fn test_excluded_files_are_skipped() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let dir = temp_path("exclude");
+    std::fs::create_dir_all(dir.join("vendor")).unwrap();
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::write(dir.join("vendor/lib.py"), "# A coment\n").unwrap();
+    std::fs::write(dir.join("src/generated.py"), "# A coment\n").unwrap();
+    std::fs::write(dir.join("src/main.py"), "# A coment\n").unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_neospeller"))
+        .args(["--write", "--provider", "offline", "--no-cache", "--dict"])
+        .arg(dict_file())
+        .args(["--exclude", "vendor/", "--exclude", "*generated*"])
+        .arg(&dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    assert_eq!(std::fs::read_to_string(dir.join("src/main.py")).unwrap(), "# A comment\n");
+    assert_eq!(std::fs::read_to_string(dir.join("src/generated.py")).unwrap(), "# A coment\n");
+    assert_eq!(std::fs::read_to_string(dir.join("vendor/lib.py")).unwrap(), "# A coment\n");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_ignore_file_is_honored() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let dir = temp_path("ignore-file");
+    std::fs::create_dir_all(dir.join("src/generated")).unwrap();
+    std::fs::write(dir.join(".neospellerignore"), "# Generated code\nsrc/generated/\n").unwrap();
+    std::fs::write(dir.join("src/generated/api.py"), "# A coment\n").unwrap();
+    std::fs::write(dir.join("src/main.py"), "# A coment\n").unwrap();
+
+    // The ignore file is read from the working directory
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_neospeller"))
+        .current_dir(&dir)
+        .args(["--write", "--provider", "offline", "--no-cache", "--dict"])
+        .arg(dict_file())
+        .arg("src")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    assert_eq!(std::fs::read_to_string(dir.join("src/main.py")).unwrap(), "# A comment\n");
+    assert_eq!(std::fs::read_to_string(dir.join("src/generated/api.py")).unwrap(), "# A coment\n");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_newline_eof_modes() {
+    let check = |input: &str, newline_eof: &str| {
+        let config = GrammarConfig {
+            provider: ProviderKind::Offline,
+            dict: Some(dict_file()),
+            newline_eof: neospeller::buffer::NewlineEof::from(newline_eof).unwrap(),
+            ..GrammarConfig::default()
+        };
+        let provider = config.provider().unwrap();
+        let language = Language::from_name("python").unwrap();
+        neospeller::check_spelling_report(input.to_string(), language, provider.as_ref(), &config)
+            .unwrap()
+            .output
+    };
+
+    let with_newline = "# A coment\nx = 5\n";
+    let without_newline = "# A coment\nx = 5";
+
+    assert_eq!(check(with_newline, "preserve"), "# A comment\nx = 5\n");
+    assert_eq!(check(without_newline, "preserve"), "# A comment\nx = 5");
+    assert_eq!(check(with_newline, "ensure"), "# A comment\nx = 5\n");
+    assert_eq!(check(without_newline, "ensure"), "# A comment\nx = 5\n");
+    assert_eq!(check("# A coment\n\n\n", "ensure"), "# A comment\n");
+    assert_eq!(check(with_newline, "strip"), "# A comment\nx = 5");
+    assert_eq!(check(without_newline, "strip"), "# A comment\nx = 5");
+}
+
+#[test]
+fn test_temporary_failures_are_retried() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+    env::set_var("OLLAMA_API_BASE_URL", server.url());
+
+    let mock_response = chat_completion_body(json!({"0": "A comment"}), json!({}));
+
+    // The first attempt hits a rate limit, the second one succeeds
+    let rate_limited = server
+        .mock("POST", "/v1/chat/completions")
+        .with_status(429)
+        .with_body(r#"{"error": {"message": "Rate limit reached"}}"#)
+        .expect(1)
+        .create();
+    let mock = server
+        .mock("POST", "/v1/chat/completions")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(&mock_response)
+        .expect(1)
+        .create();
+
+    let config = GrammarConfig {
+        provider: ProviderKind::Ollama,
+        retries: 1,
+        ..GrammarConfig::default()
+    };
+    let language = Language::from_name("python").unwrap();
+    let result = neospeller::check_spelling_with_config("# A coment".to_string(), language.clone(), &config).unwrap();
+
+    rate_limited.assert();
+    mock.assert();
+    assert_eq!(result, "# A comment");
+
+    // Without retries the rate limit is reported
+    server.reset();
+    server
+        .mock("POST", "/v1/chat/completions")
+        .with_status(429)
+        .with_body(r#"{"error": {"message": "Rate limit reached"}}"#)
+        .create();
+    let config = GrammarConfig { retries: 0, ..config };
+    let err = neospeller::check_spelling_with_config("# A coment".to_string(), language, &config).unwrap_err();
+    assert_eq!(err.to_string(), "Unexpected API response: 429 Too Many Requests: Rate limit reached");
+}
+
+#[test]
+fn test_configured_timeout_is_applied() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+    env::set_var("OLLAMA_API_BASE_URL", server.url());
+
+    // The body is sent after the timeout
+    server
+        .mock("POST", "/v1/chat/completions")
+        .with_status(200)
+        .with_chunked_body(|writer| {
+            std::thread::sleep(std::time::Duration::from_millis(500));
+            writer.write_all(b"{}")
+        })
+        .create();
+
+    let config = GrammarConfig {
+        provider: ProviderKind::Ollama,
+        timeout: std::time::Duration::from_millis(100),
+        ..GrammarConfig::default()
+    };
+    let language = Language::from_name("python").unwrap();
+    let err = neospeller::check_spelling_with_config("# A coment".to_string(), language, &config).unwrap_err();
+    assert!(matches!(&err, NeospellerError::Network(network) if network.is_timeout()), "{}", err);
+}
+
+#[test]
+fn test_drifted_lines_are_reconciled() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+    env::set_var("OLLAMA_API_BASE_URL", server.url());
+
+    // The model numbered the lines from one
+    let mock_response = chat_completion_body(
+        json!({"1": "Read the input", "2": "Parse the numbers", "5": "Print the result"}),
+        json!({"4": "The main function"}),
+    );
+    server
+        .mock("POST", "/v1/chat/completions")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(&mock_response)
+        .create();
+
+    let input = "# Read the inpt\n# Parse the numbrs\n\"\"\"\nThe main functon\n\"\"\"\nx = 5  # Print the reslt\n";
+    let language = Language::from_name("python").unwrap();
+    let config = GrammarConfig {
+        provider: ProviderKind::Ollama,
+        ..GrammarConfig::default()
+    };
+    let output = neospeller::check_spelling_with_config(input.to_string(), language.clone(), &config).unwrap();
+    assert_eq!(
+        output,
+        "# Read the input\n# Parse the numbers\n\"\"\"\nThe main function\n\"\"\"\nx = 5  # Print the result\n"
+    );
+
+    let config = GrammarConfig { strict: true, ..config };
+    let err = neospeller::check_spelling_with_config(input.to_string(), language, &config).unwrap_err();
+    assert!(matches!(err, NeospellerError::ApiResponse(_)));
+}
+
+#[test]
+fn test_swift_inline_code_is_not_sent() {
+    let language = Language::from_name("swift").unwrap();
+    let input = "/// Compute the `sum_of` two numbrs.\nfunc sum() {}\n";
+
+    let provider = RecordingProvider {
+        sent: std::cell::RefCell::new(Vec::new()),
+    };
+    let report =
+        neospeller::check_spelling_report(input.to_string(), language, &provider, &GrammarConfig::default()).unwrap();
+    assert_eq!(report.output, input);
+
+    let sent = provider.sent.into_inner();
+    assert_eq!(
+        sent[0].get(0, CommentType::Single).map(String::as_str),
+        Some("Compute the __CODE_0__ two numbrs.")
+    );
+}
+
+#[test]
+fn test_quiet_suppresses_progress_and_summary() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let dir = temp_path("quiet");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("first.py"), "# A coment\n").unwrap();
+    std::fs::write(dir.join("second.py"), "# A comment\n").unwrap();
+
+    let run = |quiet: bool| {
+        let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_neospeller"));
+        command.args(["--check", "--provider", "offline", "--no-cache", "--dict"]).arg(dict_file()).arg(&dir);
+        if quiet {
+            command.arg("--quiet");
+        }
+        command.output().unwrap()
+    };
+
+    let output = run(false);
+    assert!(String::from_utf8(output.stderr).unwrap().contains("2 file(s) processed"));
+
+    // Only the result of --check is printed, stderr is not a terminal so there is no progress either
+    let output = run(true);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr, format!("{}: 1 comment line(s) need corrections\n", dir.join("first.py").display()));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_quiet_silences_warnings() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let run = |quiet: bool| {
+        let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_neospeller"));
+        command
+            .args(["--lang", "rust", "--provider", "offline", "--no-cache", "--dict"])
+            .arg(dict_file())
+            .env_remove("NEOSPELLER_LOG")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        if quiet {
+            command.arg("--quiet");
+        }
+        let mut child = command.spawn().unwrap();
+        child.stdin.take().unwrap().write_all(b"/* A coment\nfn main() {}\n").unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    let output = run(false);
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Unterminated comment in line 1"));
+
+    let output = run(true);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "/* A comment\nfn main() {}\n");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+}
+
+#[test]
+fn test_requests_are_dumped_without_the_api_key() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+
+    let mock_response = chat_completion_body(json!({"0": "A comment"}), json!({}));
+    mock_openai(&mut server).with_body(&mock_response).create();
+
+    let dir = temp_path("dumps");
+    let _ = std::fs::remove_dir_all(&dir);
+    let config = GrammarConfig {
+        provider: ProviderKind::OpenAi,
+        dump_dir: Some(dir.clone()),
+        ..GrammarConfig::default()
+    };
+    let language = Language::from_name("python").unwrap();
+    let output = neospeller::check_spelling_with_config("# A coment\n".to_string(), language, &config).unwrap();
+    assert_eq!(output, "# A comment\n");
+
+    let mut files: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    files.sort();
+    assert_eq!(files.len(), 2);
+    let name = |path: &std::path::Path| path.file_name().unwrap().to_string_lossy().into_owned();
+    assert!(name(&files[0]).ends_with(".request.json"), "{:?}", files);
+    assert_eq!(name(&files[0]).replace(".request.json", ".response.txt"), name(&files[1]));
+
+    let request = std::fs::read_to_string(&files[0]).unwrap();
+    assert!(!request.contains("test_key"));
+    let request: serde_json::Value = serde_json::from_str(&request).unwrap();
+    assert_eq!(request["method"], "POST");
+    assert_eq!(request["url"], format!("{}/v1/chat/completions", server.url()));
+    assert_eq!(request["headers"]["authorization"], "[REDACTED]");
+    let sent: serde_json::Value = serde_json::from_str(request["body"]["messages"][1]["content"].as_str().unwrap()).unwrap();
+    assert_eq!(sent, json!({"single_comments": {"0": "A coment"}, "multiline_comments": {}}));
+
+    assert_eq!(std::fs::read_to_string(&files[1]).unwrap(), mock_response);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_smart_quotes_are_straightened() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+    env::set_var("OLLAMA_API_BASE_URL", server.url());
+
+    let mock_response = chat_completion_body(
+        json!({"0": "Don\u{2019}t change the \u{201C}name\u{201D} field", "1": "The \u{2018}café\u{2019} key"}),
+        json!({}),
+    );
+    server
+        .mock("POST", "/v1/chat/completions")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(&mock_response)
+        .expect(2)
+        .create();
+
+    let input = "# Dont change the \"name\" feild\n# The \u{2018}café\u{2019} kye\n";
+    let check = |straight_quotes: bool| {
+        let config = GrammarConfig {
+            provider: ProviderKind::Ollama,
+            straight_quotes,
+            ..GrammarConfig::default()
+        };
+        let language = Language::from_name("python").unwrap();
+        neospeller::check_spelling_with_config(input.to_string(), language, &config).unwrap()
+    };
+
+    assert_eq!(check(false), "# Don\u{2019}t change the \u{201C}name\u{201D} field\n# The \u{2018}café\u{2019} key\n");
+
+    // The quotes of the original comments are kept
+    assert_eq!(check(true), "# Don't change the \"name\" field\n# The \u{2018}café\u{2019} key\n");
+}
+
+#[test]
+fn test_csharp_xml_doc_tags_are_not_sent() {
+    let language = Language::from_name("csharp").unwrap();
+    let input = r#"/// <summary>
+/// Adds two numbrs and returns the resul.
+/// </summary>
+/// <param name="a">The frist value</param>
+public int Sum(int a, int b) => a + b;
+"#;
+
+    // The model only sees the placeholders of the tags, it fixes the prose around them
+    let provider = FixingProvider {
+        sent: std::cell::RefCell::new(Vec::new()),
+        fixes: &[("numbrs", "numbers"), ("resul.", "result."), ("frist", "first")],
+    };
+    let report =
+        neospeller::check_spelling_report(input.to_string(), language, &provider, &GrammarConfig::default()).unwrap();
+    assert_eq!(
+        report.output,
+        input
+            .replace("numbrs", "numbers")
+            .replace("resul.", "result.")
+            .replace("frist", "first")
+    );
+
+    let sent = provider.sent.into_inner().concat();
+    assert!(!sent.contains("summary") && !sent.contains("param"), "{}", sent);
+    assert!(sent.contains("__TAG_"), "{}", sent);
+}
+
+#[test]
+fn test_keep_case_restores_the_first_letter() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut server = mockito::Server::new();
+    env::set_var("OLLAMA_API_BASE_URL", server.url());
+
+    let mock_response = chat_completion_body(json!({"0": "Retry the Http request", "1": "parse the JSON body"}), json!({}));
+    server
+        .mock("POST", "/v1/chat/completions")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(&mock_response)
+        .expect(2)
+        .create();
+
+    let input = "# retry the HTTP reqest\n# Parse the JSON bdy\n";
+    let check = |keep_case: bool| {
+        let config = GrammarConfig {
+            provider: ProviderKind::Ollama,
+            keep_case,
+            ..GrammarConfig::default()
+        };
+        let language = Language::from_name("python").unwrap();
+        neospeller::check_spelling_with_config(input.to_string(), language, &config).unwrap()
+    };
+
+    assert_eq!(check(false), "# Retry the Http request\n# parse the JSON body\n");
+    assert_eq!(check(true), "# retry the HTTP request\n# Parse the JSON body\n");
+}